@@ -0,0 +1,270 @@
+use anyhow::{anyhow, Context, Result};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use odyn::engine::sprt::{Sprt, SprtStatus};
+use odyn::engine::tournament::{load_openings, Adjudication, EngineConfig, Match, TimeControl};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+/// Adds the flags describing engine `index` (1 or 2).
+fn engine_args(command: Command, index: u8) -> Command {
+    let (engine, name, option): (&'static str, &'static str, &'static str) = if index == 1 {
+        ("engine1", "name1", "option1")
+    } else {
+        ("engine2", "name2", "option2")
+    };
+    command
+        .arg(
+            Arg::new(engine)
+                .long(engine)
+                .required(true)
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("UCI engine to launch"),
+        )
+        .arg(
+            Arg::new(name)
+                .long(name)
+                .value_name("NAME")
+                .help("Name of the engine in the reports, its file name by default"),
+        )
+        .arg(
+            Arg::new(option)
+                .long(option)
+                .value_name("NAME=VALUE")
+                .action(ArgAction::Append)
+                .help("UCI option to set, may be repeated"),
+        )
+}
+
+/// Builds the configuration of engine `index` from the parsed flags.
+fn engine_config(args: &ArgMatches, index: u8) -> Result<EngineConfig> {
+    let path = args
+        .get_one::<PathBuf>(&format!("engine{}", index))
+        .unwrap();
+    let mut config = EngineConfig::new(path);
+    if let Some(name) = args.get_one::<String>(&format!("name{}", index)) {
+        config = config.with_name(name);
+    }
+    for option in args
+        .get_many::<String>(&format!("option{}", index))
+        .into_iter()
+        .flatten()
+    {
+        let (name, value) = option
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected NAME=VALUE, got '{}'", option))?;
+        config = config.with_option(name.trim(), value.trim());
+    }
+    Ok(config)
+}
+
+/// Parses `--sprt elo0,elo1`.
+fn parse_sprt(args: &ArgMatches) -> Result<Option<Sprt>> {
+    let Some(bounds) = args.get_one::<String>("sprt") else {
+        return Ok(None);
+    };
+    let (elo0, elo1) = bounds
+        .split_once(',')
+        .ok_or_else(|| anyhow!("expected ELO0,ELO1, got '{}'", bounds))?;
+    let parse = |elo: &str| -> Result<f64> {
+        elo.trim()
+            .parse()
+            .with_context(|| format!("invalid Elo bound '{}'", elo))
+    };
+    Ok(Some(Sprt {
+        elo0: parse(elo0)?,
+        elo1: parse(elo1)?,
+        alpha: *args.get_one::<f64>("alpha").unwrap(),
+        beta: *args.get_one::<f64>("beta").unwrap(),
+    }))
+}
+
+fn main() -> Result<()> {
+    let command = Command::new("arena")
+        .about("Play a match between two UCI engines and measure their Elo difference");
+    let args = engine_args(engine_args(command, 1), 2)
+        .arg(
+            Arg::new("games")
+                .long("games")
+                .short('n')
+                .value_parser(value_parser!(u32))
+                .default_value("100")
+                .help("Games to play, in pairs with colors swapped"),
+        )
+        .arg(
+            Arg::new("tc")
+                .long("tc")
+                .value_parser(value_parser!(TimeControl))
+                .default_value("10+0.1")
+                .help("Time control as base+increment in seconds"),
+        )
+        .arg(
+            Arg::new("openings")
+                .long("openings")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("EPD or PGN file of openings, played in order"),
+        )
+        .arg(
+            Arg::new("pgn-out")
+                .long("pgn-out")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("File receiving the games"),
+        )
+        .arg(
+            Arg::new("resign-score")
+                .long("resign-score")
+                .value_parser(value_parser!(i32))
+                .help("Centipawns below zero at which a side is adjudicated lost"),
+        )
+        .arg(
+            Arg::new("resign-moves")
+                .long("resign-moves")
+                .value_parser(value_parser!(u32))
+                .default_value("3")
+                .help("Moves in a row the resign score must hold"),
+        )
+        .arg(
+            Arg::new("draw-score")
+                .long("draw-score")
+                .value_parser(value_parser!(i32))
+                .help("Centipawns around zero within which both sides agree to a draw"),
+        )
+        .arg(
+            Arg::new("draw-moves")
+                .long("draw-moves")
+                .value_parser(value_parser!(u32))
+                .default_value("8")
+                .help("Moves in a row the draw score must hold for each side"),
+        )
+        .arg(
+            Arg::new("draw-after")
+                .long("draw-after")
+                .value_parser(value_parser!(u32))
+                .default_value("40")
+                .help("Move number from which draws are adjudicated"),
+        )
+        .arg(
+            Arg::new("max-moves")
+                .long("max-moves")
+                .value_parser(value_parser!(u32))
+                .help("Moves by each side after which the game is drawn"),
+        )
+        .arg(
+            Arg::new("sprt")
+                .long("sprt")
+                .value_name("ELO0,ELO1")
+                .help("Stop when a SPRT of elo0 against elo1 concludes"),
+        )
+        .arg(
+            Arg::new("alpha")
+                .long("alpha")
+                .value_parser(value_parser!(f64))
+                .default_value("0.05")
+                .help("False positive rate of the SPRT"),
+        )
+        .arg(
+            Arg::new("beta")
+                .long("beta")
+                .value_parser(value_parser!(f64))
+                .default_value("0.05")
+                .help("False negative rate of the SPRT"),
+        )
+        .get_matches();
+
+    let sprt = parse_sprt(&args)?;
+    let adjudication = Adjudication {
+        resign_score: args.get_one::<i32>("resign-score").copied(),
+        resign_moves: *args.get_one::<u32>("resign-moves").unwrap(),
+        draw_score: args.get_one::<i32>("draw-score").copied(),
+        draw_moves: *args.get_one::<u32>("draw-moves").unwrap(),
+        draw_after: *args.get_one::<u32>("draw-after").unwrap(),
+        max_moves: args.get_one::<u32>("max-moves").copied(),
+    };
+    let openings = match args.get_one::<PathBuf>("openings") {
+        Some(path) => load_openings(path)?,
+        None => Vec::new(),
+    };
+    let mut pgn_out = match args.get_one::<PathBuf>("pgn-out") {
+        Some(path) => {
+            Some(BufWriter::new(File::create(path).with_context(|| {
+                format!("cannot create {}", path.display())
+            })?))
+        }
+        None => None,
+    };
+
+    let tournament = Match::new(
+        engine_config(&args, 1)?,
+        engine_config(&args, 2)?,
+        *args.get_one::<TimeControl>("tc").unwrap(),
+        *args.get_one::<u32>("games").unwrap(),
+    )
+    .with_adjudication(adjudication)
+    .with_openings(openings);
+    println!(
+        "{} vs {}, {} games at {}",
+        tournament.engines[0].name,
+        tournament.engines[1].name,
+        tournament.games,
+        tournament.time_control
+    );
+
+    let mut write_error = None;
+    let score = tournament.run(|game, score| {
+        if let Some(out) = &mut pgn_out {
+            if let Err(err) = writeln!(out, "{}", game.to_pgn()).and_then(|_| out.flush()) {
+                write_error = Some(err);
+                return false;
+            }
+        }
+
+        let mut line = format!(
+            "game {:4}: {} - {} {} | +{} ={} -{}",
+            game.round, game.white, game.black, game.record, score.wins, score.draws, score.losses
+        );
+        if let Some((elo, margin)) = score.elo() {
+            line.push_str(&format!(" | Elo {:+.1} +- {:.1}", elo, margin));
+        }
+        let Some(sprt) = &sprt else {
+            println!("{}", line);
+            return true;
+        };
+        let (lower, upper) = sprt.bounds();
+        line.push_str(&format!(
+            " | LLR {:.2} ({:.2}, {:.2})",
+            sprt.llr(score),
+            lower,
+            upper
+        ));
+        println!("{}", line);
+        match sprt.status(score) {
+            SprtStatus::Continue => true,
+            status => {
+                println!("SPRT: {:?}", status);
+                false
+            }
+        }
+    })?;
+    if let Some(err) = write_error {
+        return Err(err).context("cannot write the games");
+    }
+
+    println!(
+        "Score of {} vs {}: +{} ={} -{} [{:.3}]",
+        tournament.engines[0].name,
+        tournament.engines[1].name,
+        score.wins,
+        score.draws,
+        score.losses,
+        score.score()
+    );
+    if let Some((elo, margin)) = score.elo() {
+        println!("Elo difference: {:+.1} +- {:.1}", elo, margin);
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use anyhow::{Context, Result};
+use clap::{value_parser, Arg, Command};
+use odyn::engine::implementations::book::{BookBuilder, BookFilter};
+use odyn::game::pgn::read_games;
+use std::{fs, path::PathBuf};
+
+fn main() -> Result<()> {
+    let args = Command::new("book")
+        .about("Build a Polyglot opening book from PGN games")
+        .arg(
+            Arg::new("pgn")
+                .required(true)
+                .num_args(1..)
+                .value_name("PGN")
+                .value_parser(value_parser!(PathBuf))
+                .help("Files of games to learn the book from"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .default_value("book.bin")
+                .help("Polyglot book to write"),
+        )
+        .arg(
+            Arg::new("max-ply")
+                .long("max-ply")
+                .value_parser(value_parser!(usize))
+                .default_value("20")
+                .help("Plies of each game to add to the book"),
+        )
+        .arg(
+            Arg::new("min-games")
+                .long("min-games")
+                .value_parser(value_parser!(u32))
+                .default_value("1")
+                .help("Games a move must have been played in"),
+        )
+        .arg(
+            Arg::new("min-score")
+                .long("min-score")
+                .value_parser(value_parser!(f64))
+                .default_value("0")
+                .help("Score between 0 and 1 a move must have reached for its side"),
+        )
+        .arg(
+            Arg::new("min-elo")
+                .long("min-elo")
+                .value_parser(value_parser!(u32))
+                .default_value("0")
+                .help("Rating the player of a move must have"),
+        )
+        .get_matches();
+
+    let output = args.get_one::<PathBuf>("output").unwrap();
+    let mut builder = BookBuilder::new(BookFilter {
+        max_ply: *args.get_one::<usize>("max-ply").unwrap(),
+        min_games: *args.get_one::<u32>("min-games").unwrap(),
+        min_score: *args.get_one::<f64>("min-score").unwrap(),
+        min_elo: *args.get_one::<u32>("min-elo").unwrap(),
+    });
+
+    let mut skipped = 0;
+    for path in args.get_many::<PathBuf>("pgn").unwrap() {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("cannot read games {}", path.display()))?;
+        for game in read_games(&text) {
+            match game {
+                Ok(game) => {
+                    if !builder.add_game(&game) {
+                        skipped += 1;
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{}: {:#}", path.display(), err);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+
+    let book = builder.build();
+    book.save(output)?;
+    println!(
+        "{} games read, {} skipped, {} book moves -> {}",
+        builder.games(),
+        skipped,
+        book.len(),
+        output.display()
+    );
+    Ok(())
+}
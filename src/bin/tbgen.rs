@@ -0,0 +1,55 @@
+use anyhow::Result;
+use clap::{value_parser, Arg, Command};
+use odyn::engine::implementations::tablebase::{generate, Material, DEFAULT_MATERIALS, EXTENSION};
+use std::{fs, path::PathBuf, time::Instant};
+
+fn main() -> Result<()> {
+    let args = Command::new("tbgen")
+        .about("Generate Odyn's distance-to-mate tablebases for pawnless endings")
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .value_name("DIR")
+                .value_parser(value_parser!(PathBuf))
+                .default_value("tablebases")
+                .help("Directory receiving the tables"),
+        )
+        .arg(
+            Arg::new("endings")
+                .num_args(0..)
+                .value_name("ENDING")
+                .help("Endings to generate, like KRvK, all supported ones by default"),
+        )
+        .get_matches();
+
+    let output = args.get_one::<PathBuf>("output").unwrap();
+    let endings: Vec<String> = match args.get_many::<String>("endings") {
+        Some(endings) => endings.cloned().collect(),
+        None => DEFAULT_MATERIALS
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+    };
+    fs::create_dir_all(output)?;
+
+    for ending in endings {
+        let material: Material = ending.parse()?;
+        let start = Instant::now();
+        let table = generate(&material)?;
+        let path = output.join(format!("{}.{}", material, EXTENSION));
+        table.save(&path)?;
+
+        let (won, lost) = table.counts();
+        println!(
+            "{}: {} won, {} lost, longest mate in {} moves, {:.1}s -> {}",
+            material,
+            won,
+            lost,
+            table.max_dtm().div_ceil(2),
+            start.elapsed().as_secs_f64(),
+            path.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use clap::{value_parser, Arg, Command};
+use odyn::engine::{
+    implementations::eval_params::EvalParams,
+    tuning::{load_dataset, Tuner},
+};
+use std::path::PathBuf;
+
+/// Epochs between two progress reports and checkpoints of the output file.
+const REPORT_INTERVAL: usize = 50;
+
+fn main() -> Result<()> {
+    let args = Command::new("tune")
+        .about("Texel tuning of the parameters of Odyn's tapered evaluator")
+        .arg(
+            Arg::new("dataset")
+                .required(true)
+                .value_name("EPD")
+                .value_parser(value_parser!(PathBuf))
+                .help("Quiet positions labelled with c9 \"1-0\" or a trailing result"),
+        )
+        .arg(
+            Arg::new("params")
+                .long("params")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Parameters to start from, the built-in ones by default"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .default_value("tuned_params.txt")
+                .help("File receiving the tuned parameters"),
+        )
+        .arg(
+            Arg::new("epochs")
+                .long("epochs")
+                .value_parser(value_parser!(usize))
+                .default_value("1000"),
+        )
+        .arg(
+            Arg::new("learning-rate")
+                .long("learning-rate")
+                .value_parser(value_parser!(f64))
+                .default_value("1.0")
+                .help("Step of each epoch, in centipawns"),
+        )
+        .get_matches();
+
+    let dataset = args.get_one::<PathBuf>("dataset").unwrap();
+    let output = args.get_one::<PathBuf>("output").unwrap();
+    let epochs = *args.get_one::<usize>("epochs").unwrap();
+    let learning_rate = *args.get_one::<f64>("learning-rate").unwrap();
+    let params = match args.get_one::<PathBuf>("params") {
+        Some(path) => EvalParams::from_file(path)?,
+        None => EvalParams::default(),
+    };
+
+    let positions = load_dataset(dataset)?;
+    println!("loaded {} positions", positions.len());
+
+    let mut tuner = Tuner::new(&positions, &params);
+    drop(positions);
+    let k = tuner.optimize_k();
+    println!("K = {:.4}, initial error {:.6}", k, tuner.error());
+
+    for epoch in 1..=epochs {
+        tuner.step(learning_rate);
+        if epoch % REPORT_INTERVAL == 0 || epoch == epochs {
+            println!("epoch {:5}: error {:.6}", epoch, tuner.error());
+            tuner.params().save(output)?;
+        }
+    }
+
+    println!("tuned parameters written to {}", output.display());
+    Ok(())
+}
@@ -73,13 +73,17 @@ pub const GO_COMMAND: &str = "go";
 pub const STOP_COMMAND: &str = "stop";
 pub const PONDERHIT_COMMAND: &str = "ponderhit";
 pub const QUIT_COMMAND: &str = "quit";
+/// Non-standard command printing the evaluation of the current position term by term.
+pub const EVAL_COMMAND: &str = "eval";
 
 /// UCI protocol acknowledgement responses.
 pub const READY_OK: &str = "readyok";
 pub const UCI_OK: &str = "uciok";
 
-/// Simplistic piece values used by the default evaluator.
-pub const PIECE_VALUES: [f32; 7] = [0.0, 1.0, 3.0, 3.0, 5.0, 9.0, f32::INFINITY];
+/// Simplistic piece values in centipawns used by the default evaluator.
+///
+/// The king is never captured, so it carries no material value.
+pub const PIECE_VALUES: [i32; 7] = [0, 100, 300, 300, 500, 900, 0];
 
 /// Number of possible moves encoded as (from_square, to_square) = 64 * 64.
 pub const NUM_MOVES: i64 = 64 * 64;
@@ -6,3 +6,13 @@ pub mod engine;
 pub mod evaluator;
 /// Built-in Odyn engine and evaluator implementations.
 pub mod implementations;
+/// Options advertised by engines.
+pub mod options;
+/// Search limits, progress reports and results shared between the UCI layer and engines.
+pub mod search;
+/// Elo and SPRT statistics of engine matches.
+pub mod sprt;
+/// Matches between UCI engines running as local processes.
+pub mod tournament;
+/// Texel tuning of the evaluation parameters.
+pub mod tuning;
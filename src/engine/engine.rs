@@ -1,9 +1,170 @@
-use crate::game::chess_move;
+use std::sync::{atomic::AtomicBool, Arc};
 
-/// Defines the minimal behavior required from a chess engine.
+use anyhow::{anyhow, Result};
+
+use crate::{
+    engine::{
+        evaluator::EvalTrace,
+        options::EngineOption,
+        search::{InfoSink, Score, SearchLimits, SearchResult, StopTimer},
+    },
+    game::{chess_move, chessboard::Chessboard, player::Player},
+};
+
+/// Defines the behavior required from a chess engine driven by the UCI wrapper.
 pub trait ChessEngine {
+    /// Forgets everything learned about the previous game (hash tables, history, ...).
+    fn new_game(&mut self) {}
+
+    /// Loads the position to search: the one reached by playing `moves` from `start`.
+    ///
+    /// Engines needing the earlier positions, to detect repetitions, can get them from
+    /// [`Chessboard::replay`].
+    fn set_position(&mut self, start: &Chessboard, moves: &[chess_move::Move]);
+
+    /// Searches the loaded position until `limits` are reached or `stop` is raised.
+    ///
+    /// Progress is reported to `info` while searching. Once `stop` is set the engine must
+    /// return the best move found so far as soon as possible.
+    fn search(
+        &mut self,
+        limits: SearchLimits,
+        info: &dyn InfoSink,
+        stop: &AtomicBool,
+    ) -> SearchResult;
+
+    /// Searches the `lines` best moves of the loaded position (MultiPV analysis).
+    ///
+    /// Returns `(move, score, principal variation)` tuples ranked best first. Engines
+    /// without MultiPV support return their single best line.
+    fn analyze(
+        &mut self,
+        limits: SearchLimits,
+        _lines: usize,
+        info: &dyn InfoSink,
+        stop: &AtomicBool,
+    ) -> Vec<(chess_move::Move, Score, Vec<chess_move::Move>)> {
+        self.search(limits, info, stop).lines
+    }
+
+    /// Changes the value of one of the options listed by [`ChessEngine::options`].
+    fn set_option(&mut self, name: &str, _value: &str) -> Result<()> {
+        Err(anyhow!("unknown option '{}'", name))
+    }
+
+    /// Lists the options this engine understands.
+    fn options(&self) -> Vec<EngineOption> {
+        Vec::new()
+    }
+
+    /// Returns the static evaluation of `board` from White's point of view, term by term.
+    ///
+    /// Engines without an evaluator to inspect return `None`.
+    fn eval_trace(&self, _board: &Chessboard) -> Option<EvalTrace> {
+        None
+    }
+}
+
+/// Minimal engine that only knows how to pick a move in a position.
+///
+/// Wrap it in a [`SimpleEngineAdapter`] to use it wherever a [`ChessEngine`] is expected.
+pub trait SimpleEngine {
     /// Loads a position and optional move history into the engine.
     fn position(&mut self, fen: &str, moves: Vec<chess_move::Move>);
-    /// Returns the currently selected best move and its score, if any.
-    fn current_best_move(&self) -> Option<(chess_move::Move, f32)>;
+    /// Returns the currently selected best move and its score in centipawns, if any.
+    fn current_best_move(&self) -> Option<(chess_move::Move, i32)>;
+}
+
+/// Exposes a [`SimpleEngine`] through the [`ChessEngine`] trait.
+///
+/// The search ignores limits and the stop flag, and reports the score of the selected
+/// move.
+pub struct SimpleEngineAdapter<E: SimpleEngine> {
+    engine: E,
+}
+
+impl<E: SimpleEngine> SimpleEngineAdapter<E> {
+    /// Wraps `engine`.
+    pub fn new(engine: E) -> Self {
+        Self { engine }
+    }
+
+    /// Returns the wrapped engine.
+    pub fn into_inner(self) -> E {
+        self.engine
+    }
+}
+
+impl<E: SimpleEngine> ChessEngine for SimpleEngineAdapter<E> {
+    fn set_position(&mut self, start: &Chessboard, moves: &[chess_move::Move]) {
+        self.engine.position(&start.to_fen(" "), moves.to_vec());
+    }
+
+    fn search(
+        &mut self,
+        _limits: SearchLimits,
+        _info: &dyn InfoSink,
+        _stop: &AtomicBool,
+    ) -> SearchResult {
+        match self.engine.current_best_move() {
+            Some((best_move, score)) => SearchResult {
+                best_move: Some(best_move),
+                score: Some(Score::from_value(score)),
+                pv: vec![best_move],
+                lines: vec![(best_move, Score::from_value(score), vec![best_move])],
+                ..Default::default()
+            },
+            None => SearchResult::default(),
+        }
+    }
+}
+
+/// Plays the moves of a [`ChessEngine`] searching with fixed `limits`.
+///
+/// Time limits are enforced by raising the stop flag once the budget of the side to move
+/// has elapsed, as the UCI wrapper does.
+pub struct EnginePlayer<E: ChessEngine> {
+    engine: E,
+    limits: SearchLimits,
+}
+
+impl<E: ChessEngine> EnginePlayer<E> {
+    /// Wraps `engine`, which searches every move with `limits`.
+    pub fn new(engine: E, limits: SearchLimits) -> Self {
+        Self { engine, limits }
+    }
+
+    /// Returns the wrapped engine.
+    pub fn engine(&mut self) -> &mut E {
+        &mut self.engine
+    }
+}
+
+impl<E: ChessEngine> Player for EnginePlayer<E> {
+    fn new_game(&mut self) {
+        self.engine.new_game();
+    }
+
+    fn next_move(
+        &mut self,
+        start: &Chessboard,
+        moves: &[chess_move::Move],
+    ) -> Result<chess_move::Move> {
+        if self.limits.is_unbounded() {
+            return Err(anyhow!("an engine player cannot wait for stop"));
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let color_id = start.replay(moves).0.current_turn();
+        let _timer = self
+            .limits
+            .time_budget(color_id)
+            .map(|budget| StopTimer::start(Arc::clone(&stop), budget));
+
+        self.engine.set_position(start, moves);
+        let result = self.engine.search(self.limits.clone(), &|_: &_| {}, &stop);
+        result
+            .best_move
+            .ok_or_else(|| anyhow!("the engine returned no move"))
+    }
 }
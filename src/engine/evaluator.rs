@@ -1,9 +1,171 @@
-use core::f32;
+use anyhow::Result;
 
-use crate::game::chessboard::Chessboard;
+use crate::{
+    constants::WHITE_ID,
+    engine::{
+        implementations::pst_evaluator::{taper, PstEvaluator},
+        options::EngineOption,
+    },
+    game::{chess_move::Move, chessboard::Chessboard},
+};
+
+/// Middlegame and endgame values of a score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PhaseScore {
+    pub midgame: i32,
+    pub endgame: i32,
+}
+
+impl PhaseScore {
+    /// Creates a score.
+    pub const fn new(midgame: i32, endgame: i32) -> Self {
+        Self { midgame, endgame }
+    }
+
+    /// Creates a score worth `value` in every phase.
+    pub const fn flat(value: i32) -> Self {
+        Self::new(value, value)
+    }
+
+    /// Interpolates between the middlegame and endgame values according to `phase`.
+    pub fn taper(self, phase: i32) -> i32 {
+        taper(self.midgame, self.endgame, phase)
+    }
+}
+
+impl std::ops::Add for PhaseScore {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.midgame + other.midgame, self.endgame + other.endgame)
+    }
+}
+
+impl std::ops::Sub for PhaseScore {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.midgame - other.midgame, self.endgame - other.endgame)
+    }
+}
+
+/// Contribution of one evaluation term, for each colour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermTrace {
+    /// Name of the term, such as `mobility`.
+    pub name: String,
+    /// Score of the term for White, higher when good for White.
+    pub white: PhaseScore,
+    /// Score of the term for Black, higher when good for Black.
+    pub black: PhaseScore,
+}
+
+impl TermTrace {
+    /// Returns the contribution of the term from White's point of view.
+    pub fn total(&self) -> PhaseScore {
+        self.white - self.black
+    }
+}
+
+/// Breakdown of an evaluation into its terms, returned by
+/// [`ChessEvaluator::evaluate_with_trace`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EvalTrace {
+    /// Contribution of every term.
+    pub terms: Vec<TermTrace>,
+    /// Game phase blending the middlegame and endgame totals, from 0 to
+    /// [`MAX_PHASE`](crate::engine::implementations::pst_evaluator::MAX_PHASE).
+    pub phase: i32,
+    /// The evaluation, from the point of view of the colour it was requested for.
+    pub score: i32,
+}
+
+impl EvalTrace {
+    /// Creates a trace attributing all of `score`, from the point of view of `color_id`,
+    /// to a single term.
+    pub fn single(name: &str, board: &Chessboard, color_id: u8, score: i32) -> Self {
+        let (white, black) = if color_id == WHITE_ID {
+            (PhaseScore::flat(score), PhaseScore::default())
+        } else {
+            (PhaseScore::default(), PhaseScore::flat(score))
+        };
+
+        Self {
+            terms: vec![TermTrace {
+                name: name.to_string(),
+                white,
+                black,
+            }],
+            phase: PstEvaluator::phase(board),
+            score,
+        }
+    }
+
+    /// Returns the sum of the terms from White's point of view.
+    pub fn total(&self) -> PhaseScore {
+        self.terms
+            .iter()
+            .fold(PhaseScore::default(), |total, term| total + term.total())
+    }
+}
+
+/// Evaluation state updated move by move along the line a search thread explores.
+///
+/// The state holds one entry per ply: the entry of a position is derived from the entry
+/// of its parent, so that the search never has to undo moves.
+pub trait IncrementalEval {
+    /// Sets the entry of ply 0 to `board`, the root of the search.
+    fn refresh(&mut self, board: &Chessboard);
+
+    /// Sets the entry of `ply` to the position reached by playing `move_` from the position
+    /// of `ply - 1`, where `flags` were returned by
+    /// [`Chessboard::make_move_unchecked`]. Null moves pass `None`.
+    fn play(&mut self, ply: u8, move_: Option<Move>, flags: u16);
+
+    /// Evaluates `board`, the position of the entry of `ply`, like
+    /// [`ChessEvaluator::evaluate`].
+    fn evaluate(&self, board: &Chessboard, ply: u8, color_id: u8) -> i32;
+}
 
 /// Scores a board position from the perspective of a given color.
 pub trait ChessEvaluator {
-    /// Returns a higher value for positions better for `color_id`.
-    fn evaluate(&self, board: &Chessboard, color_id: u8) -> f32;
+    /// Returns a score in centipawns, higher for positions better for `color_id`.
+    ///
+    /// Scores must stay below [`MATE_BOUND`](crate::engine::search::MATE_BOUND) in
+    /// magnitude, which is reserved for mates.
+    fn evaluate(&self, board: &Chessboard, color_id: u8) -> i32;
+
+    /// Evaluates `board` like [`ChessEvaluator::evaluate`], also returning the
+    /// contribution of each term.
+    ///
+    /// The default implementation reports the whole score as a single term.
+    fn evaluate_with_trace(&self, board: &Chessboard, color_id: u8) -> EvalTrace {
+        EvalTrace::single(
+            "evaluation",
+            board,
+            color_id,
+            self.evaluate(board, color_id),
+        )
+    }
+
+    /// Returns a fresh incremental state for one search thread, for evaluators that are
+    /// faster when updated move by move than when evaluating every position from scratch.
+    ///
+    /// The default implementation returns `None`: the search then calls
+    /// [`ChessEvaluator::evaluate`].
+    fn incremental(&self) -> Option<Box<dyn IncrementalEval + '_>> {
+        None
+    }
+
+    /// Lists the UCI options understood by the evaluator.
+    fn options(&self) -> Vec<EngineOption> {
+        Vec::new()
+    }
+
+    /// Applies the option `name` if it is one of [`ChessEvaluator::options`].
+    ///
+    /// Returns `Ok(false)` when `name` is not an option of the evaluator.
+    fn set_option(&mut self, _name: &str, _value: &str) -> Result<bool> {
+        Ok(false)
+    }
 }
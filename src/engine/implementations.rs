@@ -1,6 +1,21 @@
+pub mod activity;
+pub mod alpha_beta;
 pub mod blocks;
+pub mod book;
+pub mod combinators;
 pub mod dynamics;
+pub mod eval_params;
+pub mod kpk;
+pub mod muzero;
+pub mod nnue;
 pub mod odyn_engine;
 pub mod odyn_evaluator;
+pub mod pawn_structure;
 pub mod prediction;
+pub mod pst_evaluator;
 pub mod repr;
+pub mod search_params;
+pub mod selectable_evaluator;
+pub mod skill;
+pub mod tablebase;
+pub mod transposition;
@@ -0,0 +1,309 @@
+use std::sync::Arc;
+
+use crate::{
+    constants::{self, BISHOP_ID, KING_ID, KNIGHT_ID, PAWN_ID, QUEEN_ID, ROOK_ID, WHITE_ID},
+    engine::{
+        evaluator::{ChessEvaluator, EvalTrace},
+        implementations::{
+            eval_params::{EvalParams, Term, TermScore, TermSink, TermTracer},
+            pst_evaluator::{taper, PstEvaluator},
+        },
+    },
+    game::{
+        chessboard::Chessboard,
+        utility::{east_one, file_fill, iter_bits, west_one},
+    },
+};
+
+/// Pieces whose mobility and attacks on the enemy king are scored.
+const PIECES: [u8; 4] = [KNIGHT_ID, BISHOP_ID, ROOK_ID, QUEEN_ID];
+
+/// Number of safe squares of an average piece, which scores zero.
+const MOBILITY_BASELINE: [i32; 7] = [0, 0, 4, 7, 7, 14, 0];
+
+/// Attack units per king zone square attacked, indexed by piece id.
+const ATTACK_WEIGHTS: [u32; 7] = [0, 0, 2, 2, 3, 5, 0];
+/// Pieces needed on the king zone before attacks are considered dangerous.
+const MIN_ATTACKERS: u32 = 2;
+
+/// Evaluator of piece mobility and king safety, tapered like [`PstEvaluator`].
+///
+/// Mobility counts the squares each knight, bishop, rook and queen can move to, except
+/// squares attacked by enemy pawns. King safety rewards the pawn shield, penalises
+/// storming enemy pawns and open files around the king, and turns the attacks on the king
+/// zone into a non-linear danger score, looked up in [`Term::KingDanger`].
+#[derive(Debug, Clone, Default)]
+pub struct ActivityEvaluator {
+    params: Arc<EvalParams>,
+}
+
+impl ActivityEvaluator {
+    /// Creates an evaluator weighting terms with `params`.
+    pub fn new(params: Arc<EvalParams>) -> Self {
+        Self { params }
+    }
+
+    /// Adds the mobility and king safety terms of both colours to `sink`.
+    pub fn terms(board: &Chessboard, sink: &mut impl TermSink) {
+        for color_id in [WHITE_ID, constants::BLACK_ID] {
+            mobility_terms(board, color_id, sink);
+            shelter_terms(board, color_id, sink);
+            danger_terms(board, color_id, sink);
+        }
+    }
+
+    /// Returns the middlegame and endgame scores from White's point of view.
+    pub fn midgame_endgame(&self, board: &Chessboard) -> (i32, i32) {
+        let mut score = TermScore::new(&self.params);
+        Self::terms(board, &mut score);
+        (score.midgame, score.endgame)
+    }
+
+    /// Returns the middlegame and endgame mobility score of `color_id`.
+    pub fn mobility(&self, board: &Chessboard, color_id: u8) -> (i32, i32) {
+        let mut score = TermScore::for_color(&self.params, color_id);
+        mobility_terms(board, color_id, &mut score);
+        (score.midgame, score.endgame)
+    }
+
+    /// Returns the middlegame and endgame king safety score of `color_id`.
+    pub fn king_safety(&self, board: &Chessboard, color_id: u8) -> (i32, i32) {
+        let mut score = TermScore::for_color(&self.params, color_id);
+        shelter_terms(board, color_id, &mut score);
+        danger_terms(board, color_id, &mut score);
+        (score.midgame, score.endgame)
+    }
+
+    /// Scores the pawns around the king of `color_id`: shield, storm and open files.
+    pub fn king_shelter(&self, board: &Chessboard, color_id: u8) -> i32 {
+        let mut score = TermScore::for_color(&self.params, color_id);
+        shelter_terms(board, color_id, &mut score);
+        score.midgame
+    }
+
+    /// Returns the middlegame penalty for the enemy pieces attacking the zone around the
+    /// king of `color_id`, as a positive number.
+    pub fn king_danger(&self, board: &Chessboard, color_id: u8) -> i32 {
+        let mut score = TermScore::for_color(&self.params, color_id);
+        danger_terms(board, color_id, &mut score);
+        -score.midgame
+    }
+}
+
+impl ChessEvaluator for ActivityEvaluator {
+    fn evaluate(&self, board: &Chessboard, color_id: u8) -> i32 {
+        let (midgame, endgame) = self.midgame_endgame(board);
+        let score = taper(midgame, endgame, PstEvaluator::phase(board));
+
+        if color_id == WHITE_ID {
+            score
+        } else {
+            -score
+        }
+    }
+
+    fn evaluate_with_trace(&self, board: &Chessboard, color_id: u8) -> EvalTrace {
+        let mut tracer = TermTracer::new(&self.params);
+        Self::terms(board, &mut tracer);
+        tracer.into_trace(board, self.evaluate(board, color_id))
+    }
+}
+
+/// Returns the number of squares the piece on `square` can safely move to.
+///
+/// Captures count, but not squares held by friendly pieces or attacked by enemy pawns.
+pub fn safe_mobility(board: &Chessboard, square: u32) -> u32 {
+    let (piece_id, color_id) = board.piece_at(square);
+    let enemy_id = constants::opposite(color_id);
+    let bitboard = board.bitboard();
+    let piece = 1 << square;
+    let occupancy = board.occupancy();
+
+    let quiet = bitboard.generate_moves_with_occupancy(piece_id, color_id, piece, occupancy);
+    let captures = bitboard.generate_attacks_with_occupancy(piece_id, color_id, piece, occupancy)
+        & bitboard.get_color_board(enemy_id);
+    let enemy_pawns = board.piece_board(PAWN_ID, enemy_id);
+    let pawn_attacks =
+        bitboard.generate_attacks_with_occupancy(PAWN_ID, enemy_id, enemy_pawns, occupancy);
+
+    ((quiet | captures) & !pawn_attacks).count_ones()
+}
+
+/// Adds the safe squares of the pieces of `color_id` beyond the average to `sink`.
+fn mobility_terms(board: &Chessboard, color_id: u8, sink: &mut impl TermSink) {
+    for (index, piece_id) in PIECES.into_iter().enumerate() {
+        for square in iter_bits(board.piece_board(piece_id, color_id)) {
+            let squares =
+                safe_mobility(board, square) as i32 - MOBILITY_BASELINE[piece_id as usize];
+            sink.add(color_id, Term::Mobility.index(index), squares);
+        }
+    }
+}
+
+/// Adds the shield, storm and open file terms around the king of `color_id` to `sink`.
+fn shelter_terms(board: &Chessboard, color_id: u8, sink: &mut impl TermSink) {
+    let king = board.piece_board(KING_ID, color_id);
+    let own_pawns = board.piece_board(PAWN_ID, color_id);
+    let enemy_pawns = board.piece_board(PAWN_ID, constants::opposite(color_id));
+    let king_files = king | west_one(king) | east_one(king);
+
+    let mut rank = king_files;
+    for distance in 0..Term::Storm.size() {
+        rank = forward(rank, color_id);
+        if distance < Term::Shield.size() {
+            let shield = (own_pawns & rank).count_ones() as i32;
+            sink.add(color_id, Term::Shield.index(distance), shield);
+        }
+        let storm = (enemy_pawns & rank).count_ones() as i32;
+        sink.add(color_id, Term::Storm.index(distance), storm);
+    }
+
+    for square in iter_bits(king_files) {
+        let file = file_fill(1 << square);
+        if own_pawns & file == 0 {
+            let term = if enemy_pawns & file == 0 {
+                Term::OpenFile
+            } else {
+                Term::SemiOpenFile
+            };
+            sink.add(color_id, term.index(0), 1);
+        }
+    }
+}
+
+/// Adds the danger of the enemy pieces attacking the zone around the king of `color_id` to
+/// `sink`.
+fn danger_terms(board: &Chessboard, color_id: u8, sink: &mut impl TermSink) {
+    let enemy_id = constants::opposite(color_id);
+    let bitboard = board.bitboard();
+    let occupancy = board.occupancy();
+    let king = board.piece_board(KING_ID, color_id);
+    if king == 0 {
+        return;
+    }
+    let ring = king | bitboard.generate_attacks_with_occupancy(KING_ID, color_id, king, occupancy);
+    let zone = ring | forward(ring, color_id);
+
+    let (mut attackers, mut units) = (0, 0);
+    for piece_id in PIECES {
+        for square in iter_bits(board.piece_board(piece_id, enemy_id)) {
+            let attacks = bitboard.generate_attacks_with_occupancy(
+                piece_id,
+                enemy_id,
+                1 << square,
+                occupancy,
+            ) & zone;
+            if attacks != 0 {
+                attackers += 1;
+                units += ATTACK_WEIGHTS[piece_id as usize] * attacks.count_ones();
+            }
+        }
+    }
+
+    if attackers >= MIN_ATTACKERS {
+        let units = (units as usize).min(Term::KingDanger.size() - 1);
+        sink.add(color_id, Term::KingDanger.index(units), 1);
+    }
+}
+
+/// Shifts `board` one rank towards the side of the opponent of `color_id`.
+fn forward(board: u64, color_id: u8) -> u64 {
+    if color_id == WHITE_ID {
+        board >> 8
+    } else {
+        board << 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::BLACK_ID;
+
+    fn board(fen: &str) -> Chessboard {
+        Chessboard::from_fen(fen, " ")
+    }
+
+    #[test]
+    fn test_mobility_excludes_squares_attacked_by_enemy_pawns() {
+        // Knight on d4 (index 35).
+        assert_eq!(
+            safe_mobility(&board("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1"), 35),
+            8
+        );
+        assert_eq!(
+            safe_mobility(&board("4k3/3p4/8/8/3N4/8/8/4K3 w - - 0 1"), 35),
+            6
+        );
+        // Own pieces block, enemy pieces can be captured.
+        assert_eq!(
+            safe_mobility(&board("4k3/8/2n5/5P2/3N4/8/8/4K3 w - - 0 1"), 35),
+            7
+        );
+
+        let evaluator = ActivityEvaluator::default();
+        let centre = evaluator.mobility(&board("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1"), WHITE_ID);
+        let rim = evaluator.mobility(&board("4k3/8/8/8/8/8/8/N3K3 w - - 0 1"), WHITE_ID);
+        assert!(centre.0 > rim.0 && centre.1 > rim.1);
+    }
+
+    #[test]
+    fn test_king_in_the_centre_is_less_safe_than_castled() {
+        let evaluator = ActivityEvaluator::default();
+        let castled = board("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        let centre = board("6k1/5ppp/8/8/8/4K3/5PPP/8 w - - 0 1");
+        assert!(
+            evaluator.king_shelter(&castled, WHITE_ID) > evaluator.king_shelter(&centre, WHITE_ID)
+        );
+        assert!(
+            evaluator.king_safety(&castled, WHITE_ID).0
+                > evaluator.king_safety(&centre, WHITE_ID).0
+        );
+    }
+
+    #[test]
+    fn test_pawn_storm_and_open_files_reduce_shelter() {
+        let evaluator = ActivityEvaluator::default();
+        let intact = board("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1");
+        let stormed = board("6k1/8/8/8/6p1/8/5PPP/6K1 w - - 0 1");
+        let open = board("6k1/8/8/8/8/8/5P1P/6K1 w - - 0 1");
+        let semi_open = board("6k1/6p1/8/8/8/8/5P1P/6K1 w - - 0 1");
+
+        assert!(
+            evaluator.king_shelter(&stormed, WHITE_ID) < evaluator.king_shelter(&intact, WHITE_ID)
+        );
+        assert!(
+            evaluator.king_shelter(&semi_open, WHITE_ID)
+                < evaluator.king_shelter(&intact, WHITE_ID)
+        );
+        assert!(
+            evaluator.king_shelter(&open, WHITE_ID) < evaluator.king_shelter(&semi_open, WHITE_ID)
+        );
+    }
+
+    #[test]
+    fn test_king_danger_needs_several_attackers() {
+        let evaluator = ActivityEvaluator::default();
+        let alone = board("6k1/5ppp/8/8/8/3Q4/5PPP/6K1 b - - 0 1");
+        let queen_rook = board("6k1/5ppp/4R3/8/8/3Q4/5PPP/6K1 b - - 0 1");
+        assert_eq!(evaluator.king_danger(&alone, BLACK_ID), 0);
+
+        let queen = board("6k1/5ppp/8/3Q4/8/8/5PPP/6K1 b - - 0 1");
+        let queen_knight = board("6k1/5ppp/8/3Q2N1/8/8/5PPP/6K1 b - - 0 1");
+        assert_eq!(evaluator.king_danger(&queen, BLACK_ID), 0);
+        assert!(evaluator.king_danger(&queen_knight, BLACK_ID) > 0);
+        assert!(evaluator.king_danger(&queen_rook, BLACK_ID) > 0);
+    }
+
+    #[test]
+    fn test_activity_is_symmetric_between_colours() {
+        let white = board("r1bq1rk1/pp3ppp/2n2n2/2bpp3/4P3/2NP1N2/PPP1BPPP/R1BQ1RK1 w - - 0 1");
+        let black = board("r1bq1rk1/ppp1bppp/2np1n2/4p3/2BPP3/2N2N2/PP3PPP/R1BQ1RK1 b - - 0 1");
+        let evaluator = ActivityEvaluator::default();
+
+        assert_eq!(
+            evaluator.evaluate(&white, WHITE_ID),
+            evaluator.evaluate(&black, BLACK_ID)
+        );
+    }
+}
@@ -0,0 +1,1101 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    constants::{self, EMPTY_ID, PIECE_VALUES},
+    engine::{
+        evaluator::{ChessEvaluator, IncrementalEval},
+        implementations::{
+            kpk,
+            search_params::SearchParams,
+            tablebase::Tablebases,
+            transposition::{Bound, TranspositionTable, TtEntry},
+        },
+        search::{InfoSink, Score, SearchInfo, SearchResult, MATE_BOUND, MATE_VALUE},
+    },
+    game::{chess_move::Move, chessboard::Chessboard},
+};
+
+/// Deepest iteration the search will ever start.
+pub const MAX_DEPTH: u8 = 64;
+/// Deepest ply reachable with extensions, past which nodes are resolved by quiescence.
+const MAX_PLY: usize = 128;
+/// Bound larger than any score.
+const INFINITE: i32 = MATE_VALUE + 1;
+/// Root moves are only reported with `currmove` once the search has run that long.
+const CURRMOVE_REPORT_DELAY: Duration = Duration::from_secs(1);
+/// Helper threads publish their node count in batches of that many nodes.
+const NODE_FLUSH_INTERVAL: u64 = 1024;
+/// Mixed into the keys of the mate search, whose scores mean something else, so that its
+/// entries and those of normal searches share the table without ever matching each other.
+const MATE_SEARCH_SALT: u64 = 0x6d61_7465_5f6f_6e6c;
+
+/// Iteration skipping pattern of helper threads, indexed by `(thread_id - 1) % 20`.
+///
+/// A helper skips `depth` when `(depth + SKIP_PHASE[i]) / SKIP_SIZE[i]` is odd, so that
+/// helpers spread over different depths instead of duplicating the main thread's work.
+const SKIP_SIZE: [u8; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+const SKIP_PHASE: [u8; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+
+/// Width of the windows used to test whether a move beats alpha.
+const NULL_WINDOW: i32 = 1;
+/// Earlier iterations are too unstable to center an aspiration window on.
+const ASPIRATION_MIN_DEPTH: u8 = 4;
+/// Aspiration windows wider than that many centipawns are opened fully.
+const ASPIRATION_MAX_WINDOW: i32 = 500;
+const NULL_MOVE_MIN_DEPTH: u8 = 3;
+const REVERSE_FUTILITY_MAX_DEPTH: u8 = 6;
+const FUTILITY_MAX_DEPTH: u8 = 3;
+const RAZORING_MAX_DEPTH: u8 = 2;
+const LMR_MIN_DEPTH: u8 = 3;
+/// Number of moves searched at full depth before late move reductions apply.
+const LMR_MIN_MOVES: usize = 3;
+
+/// Root move with its score and principal variation.
+type RootLine = (Move, i32, Vec<Move>);
+
+/// History heuristic scores indexed by `[start square][end square]`.
+type History = [[i32; 64]; 64];
+
+/// Depth-limited negamax search with alpha-beta pruning, a transposition table and a
+/// capture-only quiescence search.
+///
+/// The selective techniques (null move, reductions, pruning, extensions) are configured
+/// with [`AlphaBeta::with_params`].
+///
+/// Several searchers can run the same position in parallel (Lazy SMP) by sharing the
+/// transposition table; see [`AlphaBeta::with_thread`].
+pub struct AlphaBeta<'a, E: ChessEvaluator> {
+    evaluator: &'a E,
+    tt: &'a TranspositionTable,
+    info: &'a dyn InfoSink,
+    stop: &'a AtomicBool,
+    node_limit: Option<u64>,
+    start: Instant,
+    nodes: u64,
+    seldepth: u8,
+    aborted: bool,
+    /// Triangular principal variation table indexed by ply.
+    pv: Vec<Vec<Move>>,
+    /// Quiet moves that caused cutoffs in this thread's search.
+    history: Box<History>,
+    /// `0` for the main thread, which alone reports progress.
+    thread_id: usize,
+    /// Nodes searched by the helper threads, if any.
+    shared_nodes: Option<&'a AtomicU64>,
+    flushed_nodes: u64,
+    /// Depth and score of the last completed iteration.
+    completed: Option<(u8, i32)>,
+    params: SearchParams,
+    /// Late move reductions indexed by `[depth][move index]`.
+    lmr: Box<[[u8; 64]; 64]>,
+    /// Depth of the current iteration, used to bound extensions.
+    root_depth: u8,
+    /// Whether leaves only tell checkmates apart, see [`AlphaBeta::mate_search`].
+    mate_only: bool,
+    /// Number of principal variations to search.
+    multipv: usize,
+    /// Root moves left out of the current search, already covered by better lines.
+    excluded: Vec<Move>,
+    /// Zobrist keys of the game history followed by the positions of the current line.
+    keys: Vec<u64>,
+    /// Index in `keys` of the root position.
+    root_index: usize,
+    /// Score of a draw for the side to move at the root, negated.
+    contempt: i32,
+    /// Evaluation state following the current line, for evaluators supporting it.
+    incremental: Option<Box<dyn IncrementalEval + 'a>>,
+    /// Endings scored exactly wherever the search reaches them.
+    tablebases: Option<&'a Tablebases>,
+}
+
+impl<'a, E: ChessEvaluator> AlphaBeta<'a, E> {
+    /// Creates a searcher that gives up once `stop` is raised or `node_limit` is reached.
+    pub fn new(
+        evaluator: &'a E,
+        tt: &'a TranspositionTable,
+        info: &'a dyn InfoSink,
+        stop: &'a AtomicBool,
+        node_limit: Option<u64>,
+    ) -> Self {
+        Self {
+            evaluator,
+            tt,
+            info,
+            stop,
+            node_limit,
+            start: Instant::now(),
+            nodes: 0,
+            seldepth: 0,
+            aborted: false,
+            pv: vec![Vec::new(); MAX_PLY],
+            history: Box::new([[0; 64]; 64]),
+            thread_id: 0,
+            shared_nodes: None,
+            flushed_nodes: 0,
+            completed: None,
+            lmr: SearchParams::default().lmr_table(),
+            params: SearchParams::default(),
+            root_depth: 0,
+            mate_only: false,
+            multipv: 1,
+            excluded: Vec::new(),
+            keys: Vec::new(),
+            root_index: 0,
+            contempt: 0,
+            incremental: evaluator.incremental(),
+            tablebases: None,
+        }
+    }
+
+    /// Sets the Zobrist keys of the positions played before the root, oldest first, so
+    /// that repetitions of earlier positions are scored as draws.
+    pub fn with_history(mut self, history: &[u64]) -> Self {
+        self.keys = history.to_vec();
+        self.root_index = history.len();
+        self
+    }
+
+    /// Makes the engine avoid draws: they score `-contempt` centipawns for the side to
+    /// move at the root.
+    pub fn with_contempt(mut self, contempt: i32) -> Self {
+        self.contempt = contempt;
+        self
+    }
+
+    /// Searches the `lines` best root moves instead of only the best one (MultiPV).
+    pub fn with_multipv(mut self, lines: usize) -> Self {
+        self.multipv = lines.max(1);
+        self
+    }
+
+    /// Scores the positions of the endings in `tablebases` exactly instead of searching
+    /// them.
+    pub fn with_tablebases(mut self, tablebases: &'a Tablebases) -> Self {
+        self.tablebases = Some(tablebases);
+        self
+    }
+
+    /// Replaces the default selective search parameters.
+    pub fn with_params(mut self, params: SearchParams) -> Self {
+        self.lmr = params.lmr_table();
+        self.params = params;
+        self
+    }
+
+    /// Makes this searcher thread `thread_id` of a parallel search.
+    ///
+    /// Helper threads (`thread_id > 0`) add their nodes to `shared_nodes` and skip some
+    /// iterations; the main thread (`0`) includes them in its reports and node limit.
+    pub fn with_thread(mut self, thread_id: usize, shared_nodes: &'a AtomicU64) -> Self {
+        self.thread_id = thread_id;
+        self.shared_nodes = Some(shared_nodes);
+        self
+    }
+
+    /// Returns the number of nodes visited so far, by every thread for the main thread.
+    pub fn nodes(&self) -> u64 {
+        match self.shared_nodes {
+            Some(shared) if self.is_main() => self.nodes + shared.load(Ordering::Relaxed),
+            _ => self.nodes,
+        }
+    }
+
+    /// Returns the depth and score of the last fully searched iteration.
+    pub fn completed_iteration(&self) -> Option<(u8, i32)> {
+        self.completed
+    }
+
+    /// Returns whether the last search was interrupted by `stop` or the node limit.
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Runs an iterative deepening search of `board` up to `max_depth` plies.
+    ///
+    /// Every completed iteration refines the best moves, so an interrupted search answers
+    /// with the result of the last one. An `info` report is emitted for each line after
+    /// each iteration.
+    pub fn iterative_deepening(&mut self, board: &Chessboard, max_depth: u8) -> SearchResult {
+        let mut lines: Vec<RootLine> = Vec::new();
+
+        for depth in 1..=max_depth.clamp(1, MAX_DEPTH) {
+            if self.skips_depth(depth) {
+                continue;
+            }
+
+            let iteration = self.search_lines(board, depth, &lines);
+            if self.aborted {
+                // A partial iteration still improves on the previous best move.
+                if let Some(best) = iteration.into_iter().next() {
+                    lines.retain(|line| line.0 != best.0);
+                    lines.insert(0, best);
+                }
+                break;
+            }
+            if iteration.is_empty() {
+                break;
+            }
+
+            lines = iteration;
+            self.completed = Some((depth, lines[0].1));
+            for (index, (_, score, pv)) in lines.iter().enumerate() {
+                let multipv = (self.multipv > 1).then_some(index as u32 + 1);
+                self.report(depth, multipv, *score, pv.clone());
+            }
+
+            let found_mate = lines[0].1.abs() >= MATE_BOUND;
+            if found_mate && self.multipv == 1 {
+                break;
+            }
+        }
+
+        self.flush_nodes();
+        let mut result = SearchResult {
+            lines: lines
+                .iter()
+                .map(|(move_, score, pv)| (*move_, Score::from_value(*score), pv.clone()))
+                .collect(),
+            ..Default::default()
+        };
+        if let Some((move_, score, pv)) = lines.into_iter().next() {
+            result.best_move = Some(move_);
+            result.ponder_move = pv.get(1).copied();
+            result.score = Some(Score::from_value(score));
+            result.pv = pv;
+        }
+        result
+    }
+
+    /// Searches the best [`AlphaBeta::with_multipv`] root moves to `depth` plies, each one
+    /// excluding the moves found before it, and returns them ranked best first.
+    ///
+    /// `previous` holds the lines of the last iteration, used for move ordering and
+    /// aspiration windows. An interrupted search returns the lines completed so far.
+    fn search_lines(
+        &mut self,
+        board: &Chessboard,
+        depth: u8,
+        previous: &[RootLine],
+    ) -> Vec<RootLine> {
+        let mut lines: Vec<RootLine> = Vec::new();
+
+        for index in 0..self.multipv {
+            self.excluded = lines.iter().map(|line| line.0).collect();
+            let first_move = previous
+                .iter()
+                .map(|line| line.0)
+                .find(|move_| !self.excluded.contains(move_));
+            let previous_score = previous.get(index).map(|line| line.1);
+
+            match self.aspiration_search(board, depth, first_move, previous_score) {
+                Some(line) => lines.push(line),
+                None => break,
+            }
+            if self.aborted {
+                break;
+            }
+        }
+        self.excluded.clear();
+
+        // Lines are searched best first, but a later one can still come out higher.
+        lines.sort_by_key(|line| -line.1);
+        lines
+    }
+
+    /// Looks for a forced mate in at most `moves` moves for the side to move.
+    ///
+    /// Unlike [`AlphaBeta::iterative_deepening`], the search is exhaustive: no selective
+    /// technique applies and leaves other than checkmates score `0`. A mate score is thus
+    /// a proof, and its absence after a completed search refutes any mate within `moves`.
+    /// Mates against the side to move within that distance are found as well.
+    pub fn mate_search(&mut self, board: &Chessboard, moves: u32) -> SearchResult {
+        self.params = SearchParams::exhaustive();
+        self.mate_only = true;
+
+        let moves = moves.clamp(1, MAX_DEPTH as u32 / 2);
+        let mut result = SearchResult::default();
+
+        for distance in 1..=moves {
+            let depth = 2 * distance as u8;
+            let best = self.search_root(board, depth, result.best_move, -INFINITE, INFINITE);
+
+            if let Some((move_, score, pv)) = best {
+                result = SearchResult {
+                    best_move: Some(move_),
+                    ponder_move: pv.get(1).copied(),
+                    pv: pv.clone(),
+                    ..Default::default()
+                };
+                if !self.aborted && score.abs() >= MATE_BOUND {
+                    self.completed = Some((depth, score));
+                    result.score = Some(Score::from_value(score));
+                    result.lines = vec![(move_, Score::from_value(score), pv.clone())];
+                    self.report(depth, None, score, pv);
+                    break;
+                }
+            }
+
+            if self.aborted {
+                break;
+            }
+        }
+
+        if result.score.is_none() && self.is_main() {
+            let outcome = if self.aborted {
+                "not proven"
+            } else {
+                "refuted"
+            };
+            self.info.info(&SearchInfo {
+                string: Some(format!("mate in {} {}", moves, outcome)),
+                ..Default::default()
+            });
+        }
+
+        self.flush_nodes();
+        result
+    }
+
+    /// Searches the root in a window around `previous_score`, widening it until the score
+    /// falls inside.
+    fn aspiration_search(
+        &mut self,
+        board: &Chessboard,
+        depth: u8,
+        mut first_move: Option<Move>,
+        previous_score: Option<i32>,
+    ) -> Option<RootLine> {
+        let center = match previous_score {
+            Some(score)
+                if self.params.aspiration
+                    && depth >= ASPIRATION_MIN_DEPTH
+                    && score.abs() < MATE_BOUND =>
+            {
+                score
+            }
+            _ => return self.search_root(board, depth, first_move, -INFINITE, INFINITE),
+        };
+
+        let mut delta = self.params.aspiration_window as i32;
+        let mut alpha = center - delta;
+        let mut beta = center + delta;
+
+        loop {
+            let best = self.search_root(board, depth, first_move, alpha, beta);
+            if self.aborted {
+                return best;
+            }
+
+            delta *= 2;
+            let widen_fully = delta > ASPIRATION_MAX_WINDOW;
+            match &best {
+                None => {
+                    alpha = if widen_fully {
+                        -INFINITE
+                    } else {
+                        center - delta
+                    }
+                }
+                Some((move_, score, _)) if *score >= beta => {
+                    first_move = Some(*move_);
+                    beta = if widen_fully {
+                        INFINITE
+                    } else {
+                        center + delta
+                    };
+                }
+                Some(_) => return best,
+            }
+        }
+    }
+
+    /// Searches `board` to `depth` plies in the `(alpha, beta)` window, trying `first_move`
+    /// before the other root moves.
+    ///
+    /// Returns the best fully searched root move with its score and principal variation,
+    /// or `None` if no move scores above `alpha`. The search stops at the first move
+    /// reaching `beta`. When the search is interrupted, the result only covers the root
+    /// moves completed before the interruption. Moves already covered by a better MultiPV
+    /// line are skipped.
+    pub fn search_root(
+        &mut self,
+        board: &Chessboard,
+        depth: u8,
+        first_move: Option<Move>,
+        mut alpha: i32,
+        beta: i32,
+    ) -> Option<RootLine> {
+        let mut moves = board.legal_moves_vec(board.current_turn());
+        moves.retain(|move_| !self.excluded.contains(move_));
+        order_moves(board, &mut moves, first_move, &self.history);
+
+        self.root_depth = depth;
+        self.keys.truncate(self.root_index);
+        self.keys.push(board.zobrist_key());
+        if let Some(incremental) = &mut self.incremental {
+            incremental.refresh(board);
+        }
+        let mut best = None;
+
+        for (index, move_) in moves.into_iter().enumerate() {
+            if self.is_main() && self.start.elapsed() >= CURRMOVE_REPORT_DELAY {
+                self.info.info(&SearchInfo {
+                    depth: Some(depth),
+                    currmove: Some(move_),
+                    currmovenumber: Some(index as u32 + 1),
+                    ..Default::default()
+                });
+            }
+
+            let mut next = board.clone();
+            let flags = next.make_move_unchecked(move_);
+            self.play(1, Some(move_), flags);
+
+            let mut score;
+            if index == 0 || !self.params.pvs {
+                score = -self.negamax(&next, depth - 1, 1, -beta, -alpha, true);
+            } else {
+                score = -self.negamax(&next, depth - 1, 1, -alpha - NULL_WINDOW, -alpha, true);
+                if score > alpha && score < beta && !self.aborted {
+                    score = -self.negamax(&next, depth - 1, 1, -beta, -alpha, true);
+                }
+            }
+
+            if self.aborted {
+                break;
+            }
+
+            if score > alpha {
+                alpha = score;
+                let mut pv = vec![move_];
+                pv.extend_from_slice(&self.pv[1]);
+
+                // A new best move in the middle of an iteration is worth reporting.
+                if index > 0 && score < beta && self.multipv == 1 {
+                    self.report(depth, None, score, pv.clone());
+                }
+                best = Some((move_, score, pv));
+
+                if score >= beta {
+                    break;
+                }
+            }
+        }
+        self.keys.pop();
+
+        if let Some((move_, score, _)) = best {
+            // The best of a subset of the root moves says nothing about the position.
+            if !self.aborted && self.excluded.is_empty() {
+                self.tt.store(TtEntry {
+                    key: self.tt_key(board.zobrist_key()),
+                    best_move: Some(move_),
+                    score,
+                    depth,
+                    bound: if score >= beta {
+                        Bound::Lower
+                    } else {
+                        Bound::Exact
+                    },
+                });
+            }
+        }
+
+        best
+    }
+
+    /// Fail-soft negamax search of `board` with the selective techniques enabled in
+    /// [`SearchParams`]. `allow_null` is false right after a null move.
+    ///
+    /// Repetitions and positions drawn by the fifty-move rule score as draws.
+    fn negamax(
+        &mut self,
+        board: &Chessboard,
+        depth: u8,
+        ply: u8,
+        alpha: i32,
+        beta: i32,
+        allow_null: bool,
+    ) -> i32 {
+        let key = board.zobrist_key();
+        if self.is_draw(board, key) {
+            self.pv[ply as usize].clear();
+            return self.draw_score(ply);
+        }
+
+        self.keys.push(key);
+        let score = self.search_node(board, depth, ply, alpha, beta, allow_null);
+        self.keys.pop();
+        score
+    }
+
+    /// Searches a node whose key was just pushed on `keys`.
+    fn search_node(
+        &mut self,
+        board: &Chessboard,
+        depth: u8,
+        ply: u8,
+        mut alpha: i32,
+        beta: i32,
+        allow_null: bool,
+    ) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+
+        self.pv[ply as usize].clear();
+        if kpk::probe(board) == Some(false) {
+            return self.draw_score(ply);
+        }
+        if let Some(score) = self
+            .tablebases
+            .and_then(|tablebases| tablebases.probe_dtm(board))
+        {
+            // Mates count from the root.
+            return match score {
+                _ if score >= MATE_BOUND => score - ply as i32,
+                _ if score <= -MATE_BOUND => score + ply as i32,
+                _ => self.draw_score(ply),
+            };
+        }
+        if depth == 0 || ply as usize >= MAX_PLY - 1 {
+            return if self.mate_only {
+                self.mate_leaf(board, ply)
+            } else {
+                self.quiescence(board, ply, alpha, beta)
+            };
+        }
+
+        // No line from here can beat a mate already found closer to the root.
+        alpha = alpha.max(-MATE_VALUE + ply as i32);
+        let beta = beta.min(MATE_VALUE - ply as i32 - 1);
+        if alpha >= beta {
+            return alpha;
+        }
+
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+
+        let key = self.tt_key(self.keys[self.keys.len() - 1]);
+        let tt_entry = self.tt.probe(key);
+        if let Some(entry) = tt_entry {
+            if entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff {
+                    return score;
+                }
+            }
+        }
+
+        let color_id = board.current_turn();
+        let in_check = board.is_king_attacked(color_id);
+        let is_pv = beta - alpha > NULL_WINDOW;
+        let static_eval = self.evaluate(board, ply, color_id);
+
+        if !is_pv && !in_check && beta.abs() < MATE_BOUND {
+            if let Some(score) = self.prune_node(board, depth, ply, alpha, beta, static_eval) {
+                return score;
+            }
+
+            if self.params.null_move
+                && allow_null
+                && depth >= NULL_MOVE_MIN_DEPTH
+                && static_eval >= beta
+                && board.has_non_pawn_material(color_id)
+            {
+                let reduction = self.params.null_move_reduction + depth / 6;
+                let mut next = board.clone();
+                next.make_null_move();
+                self.play(ply + 1, None, 0);
+                let score = -self.negamax(
+                    &next,
+                    depth.saturating_sub(1 + reduction),
+                    ply + 1,
+                    -beta,
+                    -beta + NULL_WINDOW,
+                    false,
+                );
+
+                if self.aborted {
+                    return 0;
+                }
+                if score >= beta {
+                    // Mates found after passing are not proven.
+                    return if score >= MATE_BOUND { beta } else { score };
+                }
+            }
+        }
+
+        let mut moves = board.legal_moves_vec(color_id);
+        if moves.is_empty() {
+            return if in_check {
+                -MATE_VALUE + ply as i32
+            } else {
+                self.draw_score(ply)
+            };
+        }
+
+        order_moves(
+            board,
+            &mut moves,
+            tt_entry.and_then(|entry| entry.best_move),
+            &self.history,
+        );
+
+        let futile = self.params.futility
+            && !is_pv
+            && !in_check
+            && depth <= FUTILITY_MAX_DEPTH
+            && alpha.abs() < MATE_BOUND
+            && static_eval + self.params.futility_margin as i32 * depth as i32 <= alpha;
+        let extended = (ply + depth).saturating_sub(self.root_depth);
+        let can_extend =
+            self.params.check_extensions && extended < self.params.check_extension_limit;
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITE;
+        let mut best_move = None;
+
+        for (index, move_) in moves.into_iter().enumerate() {
+            let mut next = board.clone();
+            let flags = next.make_move_unchecked(move_);
+
+            let tactical = is_tactical(board, move_);
+            let gives_check = next.is_king_attacked(constants::opposite(color_id));
+
+            // Quiet moves cannot bring a hopeless position back above alpha.
+            if futile && index > 0 && !tactical && !gives_check {
+                best_score = best_score.max(static_eval);
+                continue;
+            }
+            self.play(ply + 1, Some(move_), flags);
+
+            let new_depth = if can_extend && gives_check {
+                depth
+            } else {
+                depth - 1
+            };
+
+            let mut score;
+            if index == 0 {
+                score = -self.negamax(&next, new_depth, ply + 1, -beta, -alpha, true);
+            } else {
+                let reduction = if self.params.lmr
+                    && depth >= LMR_MIN_DEPTH
+                    && index >= LMR_MIN_MOVES
+                    && !tactical
+                    && !in_check
+                    && !gives_check
+                {
+                    self.lmr[depth.min(63) as usize][index.min(63)].min(new_depth.saturating_sub(1))
+                } else {
+                    0
+                };
+                let (child_alpha, child_beta) = if self.params.pvs {
+                    (-alpha - NULL_WINDOW, -alpha)
+                } else {
+                    (-beta, -alpha)
+                };
+
+                score = -self.negamax(
+                    &next,
+                    new_depth - reduction,
+                    ply + 1,
+                    child_alpha,
+                    child_beta,
+                    true,
+                );
+                if reduction > 0 && score > alpha && !self.aborted {
+                    score = -self.negamax(&next, new_depth, ply + 1, child_alpha, child_beta, true);
+                }
+                if self.params.pvs && score > alpha && score < beta && !self.aborted {
+                    score = -self.negamax(&next, new_depth, ply + 1, -beta, -alpha, true);
+                }
+            }
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(move_);
+            }
+
+            if score > alpha {
+                alpha = score;
+                self.update_pv(ply, move_);
+            }
+
+            if score >= beta {
+                if !tactical {
+                    let entry =
+                        &mut self.history[move_.start_index as usize][move_.end_index as usize];
+                    *entry = entry.saturating_add(depth as i32 * depth as i32);
+                }
+                break;
+            }
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(TtEntry {
+            key,
+            best_move,
+            score: score_to_tt(best_score, ply),
+            depth,
+            bound,
+        });
+
+        best_score
+    }
+
+    /// Applies reverse futility pruning and razoring to a non-PV node not in check.
+    ///
+    /// Returns the score to answer with when the node can be cut.
+    fn prune_node(
+        &mut self,
+        board: &Chessboard,
+        depth: u8,
+        ply: u8,
+        alpha: i32,
+        beta: i32,
+        static_eval: i32,
+    ) -> Option<i32> {
+        let margin = self.params.reverse_futility_margin as i32 * depth as i32;
+        if self.params.reverse_futility
+            && depth <= REVERSE_FUTILITY_MAX_DEPTH
+            && static_eval - margin >= beta
+        {
+            return Some(static_eval - margin);
+        }
+
+        let margin = self.params.razor_margin as i32 * depth as i32;
+        if self.params.razoring && depth <= RAZORING_MAX_DEPTH && static_eval + margin <= alpha {
+            let score = self.quiescence(board, ply, alpha, alpha + NULL_WINDOW);
+            if score <= alpha {
+                return Some(score);
+            }
+        }
+
+        None
+    }
+
+    fn quiescence(&mut self, board: &Chessboard, ply: u8, mut alpha: i32, beta: i32) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+
+        let color_id = board.current_turn();
+        let stand_pat = self.evaluate(board, ply, color_id);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut captures: Vec<Move> = board
+            .legal_moves_vec(color_id)
+            .into_iter()
+            .filter(|move_| is_tactical(board, *move_))
+            .collect();
+        order_moves(board, &mut captures, None, &self.history);
+
+        for move_ in captures {
+            let mut next = board.clone();
+            let flags = next.make_move_unchecked(move_);
+            let child_ply = ply.saturating_add(1);
+            self.play(child_ply, Some(move_), flags);
+            let score = -self.quiescence(&next, child_ply, -beta, -alpha);
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+
+    /// Returns whether the position reached with `key` is drawn by repetition or by the
+    /// fifty-move rule.
+    ///
+    /// A single repetition inside the searched line is enough, while positions of the
+    /// game history must have occurred twice already (threefold repetition).
+    fn is_draw(&self, board: &Chessboard, key: u64) -> bool {
+        let clock = board.halfmove_clock() as usize;
+        if clock >= 100 {
+            // Checkmate on the last move still wins.
+            let color_id = board.current_turn();
+            return !board.is_king_attacked(color_id)
+                || !board.legal_moves_vec(color_id).is_empty();
+        }
+
+        // Only positions with the same side to move since the last irreversible move can
+        // repeat.
+        let len = self.keys.len();
+        if len < 2 {
+            return false;
+        }
+        let earliest = len.saturating_sub(clock);
+        let mut repetitions = 0;
+        for index in (earliest..=len - 2).rev().step_by(2) {
+            if self.keys[index] != key {
+                continue;
+            }
+            if index >= self.root_index {
+                return true;
+            }
+            repetitions += 1;
+            if repetitions >= 2 {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Score of a draw at `ply`, from the point of view of the side to move there.
+    fn draw_score(&self, ply: u8) -> i32 {
+        if self.mate_only {
+            0
+        } else if ply % 2 == 1 {
+            self.contempt
+        } else {
+            -self.contempt
+        }
+    }
+
+    /// Scores a leaf of the mate search, where only checkmates are known.
+    fn mate_leaf(&mut self, board: &Chessboard, ply: u8) -> i32 {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+
+        let color_id = board.current_turn();
+        if board.is_king_attacked(color_id) && board.legal_moves_vec(color_id).is_empty() {
+            -MATE_VALUE + ply as i32
+        } else {
+            0
+        }
+    }
+
+    /// Evaluates `board`, reached at `ply`, keeping the result out of the mate range.
+    /// King and pawn versus king endings are scored by the [`kpk`] bitbase.
+    fn evaluate(&self, board: &Chessboard, ply: u8, color_id: u8) -> i32 {
+        if let Some(score) = kpk::score(board, color_id) {
+            return score;
+        }
+        let score = match &self.incremental {
+            Some(incremental) => incremental.evaluate(board, ply, color_id),
+            None => self.evaluator.evaluate(board, color_id),
+        };
+        score.clamp(-MATE_BOUND + 1, MATE_BOUND - 1)
+    }
+
+    /// Derives the incremental evaluation state of `ply` from its parent, if the
+    /// evaluator has one.
+    fn play(&mut self, ply: u8, move_: Option<Move>, flags: u16) {
+        if let Some(incremental) = &mut self.incremental {
+            incremental.play(ply, move_, flags);
+        }
+    }
+
+    /// Makes `move_` followed by the child's principal variation the variation at `ply`.
+    fn update_pv(&mut self, ply: u8, move_: Move) {
+        let ply = ply as usize;
+        let (current, deeper) = self.pv.split_at_mut(ply + 1);
+        let line = &mut current[ply];
+
+        line.clear();
+        line.push(move_);
+        if let Some(child) = deeper.first() {
+            line.extend_from_slice(child);
+        }
+    }
+
+    /// Returns the key under which the position with Zobrist key `key` is stored.
+    fn tt_key(&self, key: u64) -> u64 {
+        if self.mate_only {
+            key ^ MATE_SEARCH_SALT
+        } else {
+            key
+        }
+    }
+
+    fn report(&self, depth: u8, multipv: Option<u32>, score: i32, pv: Vec<Move>) {
+        if !self.is_main() {
+            return;
+        }
+
+        let time = self.start.elapsed();
+        let millis = time.as_millis() as u64;
+        let nodes = self.nodes();
+
+        self.info.info(&SearchInfo {
+            depth: Some(depth),
+            seldepth: Some(self.seldepth.max(depth)),
+            multipv,
+            score: Some(Score::from_value(score)),
+            nodes: Some(nodes),
+            nps: (millis > 0).then(|| nodes * 1000 / millis),
+            time: Some(time),
+            hashfull: Some(self.tt.hashfull()),
+            pv,
+            ..Default::default()
+        });
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+
+        if !self.is_main() && self.nodes - self.flushed_nodes >= NODE_FLUSH_INTERVAL {
+            self.flush_nodes();
+        }
+
+        let out_of_nodes = self.node_limit.is_some_and(|limit| self.nodes() >= limit);
+        self.aborted = out_of_nodes || self.stop.load(Ordering::Relaxed);
+        self.aborted
+    }
+
+    fn is_main(&self) -> bool {
+        self.thread_id == 0
+    }
+
+    /// Publishes the nodes of a helper thread not yet added to the shared count.
+    fn flush_nodes(&mut self) {
+        if let Some(shared) = self.shared_nodes.filter(|_| !self.is_main()) {
+            shared.fetch_add(self.nodes - self.flushed_nodes, Ordering::Relaxed);
+            self.flushed_nodes = self.nodes;
+        }
+    }
+
+    fn skips_depth(&self, depth: u8) -> bool {
+        if self.is_main() {
+            return false;
+        }
+
+        let index = (self.thread_id - 1) % SKIP_SIZE.len();
+        (depth + SKIP_PHASE[index]) / SKIP_SIZE[index] % 2 == 1
+    }
+}
+
+/// Mate scores are stored relative to the node so that they stay valid at any ply.
+fn score_to_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+fn is_tactical(board: &Chessboard, move_: Move) -> bool {
+    move_.promotion_piece != 0 || board.piece_at(move_.end_index).0 != EMPTY_ID
+}
+
+/// Orders `moves` with `first_move` first, then captures by MVV-LVA, then quiet moves by
+/// history score.
+fn order_moves(
+    board: &Chessboard,
+    moves: &mut [Move],
+    first_move: Option<Move>,
+    history: &History,
+) {
+    moves.sort_by_cached_key(|move_| {
+        if Some(*move_) == first_move {
+            return (0, 0);
+        }
+
+        let (victim, _) = board.piece_at(move_.end_index);
+        if victim == EMPTY_ID && move_.promotion_piece == 0 {
+            return (
+                2,
+                -history[move_.start_index as usize][move_.end_index as usize],
+            );
+        }
+
+        let (attacker, _) = board.piece_at(move_.start_index);
+        let gain = PIECE_VALUES[victim as usize] + PIECE_VALUES[move_.promotion_piece as usize];
+        (1, attacker as i32 - gain)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::START_FEN, engine::implementations::odyn_evaluator::OdynEvaluator,
+        uci::protocol::parse_uci_move,
+    };
+
+    #[test]
+    fn test_repetitions_and_fifty_move_rule_are_draws() {
+        let tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let silent = |_: &SearchInfo| {};
+
+        let mut board = Chessboard::from_fen(START_FEN, " ");
+        let mut keys = vec![board.zobrist_key()];
+        for move_ in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            board.make_move_unchecked(parse_uci_move(move_).unwrap());
+            keys.push(board.zobrist_key());
+        }
+        // The start position came back once: not yet a draw in the game history...
+        let searcher =
+            AlphaBeta::new(&OdynEvaluator, &tt, &silent, &stop, None).with_history(&keys[..4]);
+        assert!(!searcher.is_draw(&board, keys[4]));
+        // ...but a repetition of the root inside the searched line is.
+        let mut searcher =
+            AlphaBeta::new(&OdynEvaluator, &tt, &silent, &stop, None).with_history(&[]);
+        searcher.keys.extend_from_slice(&keys[..4]);
+        assert!(searcher.is_draw(&board, keys[4]));
+
+        let searcher = AlphaBeta::new(&OdynEvaluator, &tt, &silent, &stop, None).with_history(&[]);
+        let board = Chessboard::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 100 80", " ");
+        assert!(searcher.is_draw(&board, board.zobrist_key()));
+        // Mate delivered on the hundredth ply stands.
+        let board = Chessboard::from_fen("R3k3/8/4K3/8/8/8/8/8 b - - 100 80", " ");
+        assert!(!searcher.is_draw(&board, board.zobrist_key()));
+    }
+
+    #[test]
+    fn test_mate_scores_are_stored_relative_to_the_node() {
+        // Mated 3 plies below a node found at ply 5.
+        let score = -MATE_VALUE + 8;
+        assert_eq!(score_to_tt(score, 5), -MATE_VALUE + 3);
+        assert_eq!(score_from_tt(score_to_tt(score, 5), 2), -MATE_VALUE + 5);
+
+        assert_eq!(score_to_tt(120, 5), 120);
+        assert_eq!(score_from_tt(-120, 5), -120);
+    }
+}
@@ -0,0 +1,466 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::Result;
+
+use crate::{
+    constants::WHITE_ID,
+    engine::{
+        evaluator::{ChessEvaluator, EvalTrace, PhaseScore},
+        implementations::pst_evaluator::PstEvaluator,
+        options::EngineOption,
+    },
+    game::chessboard::Chessboard,
+};
+
+/// Number of entries of the cache of [`Cached`] built with [`Cached::new`].
+pub const EVAL_CACHE_ENTRIES: usize = 1 << 16;
+
+/// Distinguishes the cache keys of the two colours evaluating the same position.
+const BLACK_KEY: u64 = 0x9e37_79b9_7f4a_7c15;
+/// Marks a filled slot of the evaluation cache, so that empty slots never match.
+const FILLED: u64 = 1 << 32;
+
+/// Evaluator shared between search threads.
+pub type BoxedEvaluator = Box<dyn ChessEvaluator + Send + Sync>;
+
+/// Sum of evaluators, each scaled by a weight.
+///
+/// ```
+/// use odyn::engine::implementations::{
+///     combinators::WeightedSum, odyn_evaluator::OdynEvaluator, pst_evaluator::PstEvaluator,
+/// };
+///
+/// let evaluator = WeightedSum::new()
+///     .with(0.5, OdynEvaluator)
+///     .with(0.5, PstEvaluator::default());
+/// ```
+#[derive(Default)]
+pub struct WeightedSum {
+    terms: Vec<(f32, BoxedEvaluator)>,
+}
+
+impl WeightedSum {
+    /// Creates an empty sum, evaluating every position to 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `evaluator` to the sum, scaled by `weight`.
+    pub fn with(
+        mut self,
+        weight: f32,
+        evaluator: impl ChessEvaluator + Send + Sync + 'static,
+    ) -> Self {
+        self.terms.push((weight, Box::new(evaluator)));
+        self
+    }
+}
+
+impl fmt::Debug for WeightedSum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let weights: Vec<f32> = self.terms.iter().map(|(weight, _)| *weight).collect();
+        f.debug_struct("WeightedSum")
+            .field("weights", &weights)
+            .finish()
+    }
+}
+
+fn scale(value: i32, weight: f32) -> i32 {
+    (value as f32 * weight).round() as i32
+}
+
+impl ChessEvaluator for WeightedSum {
+    fn evaluate(&self, board: &Chessboard, color_id: u8) -> i32 {
+        self.terms
+            .iter()
+            .map(|(weight, evaluator)| scale(evaluator.evaluate(board, color_id), *weight))
+            .sum()
+    }
+
+    fn evaluate_with_trace(&self, board: &Chessboard, color_id: u8) -> EvalTrace {
+        let mut trace = EvalTrace {
+            phase: PstEvaluator::phase(board),
+            score: self.evaluate(board, color_id),
+            ..EvalTrace::default()
+        };
+
+        for (weight, evaluator) in &self.terms {
+            let part = evaluator.evaluate_with_trace(board, color_id);
+            trace.terms.extend(part.terms.into_iter().map(|mut term| {
+                for score in [&mut term.white, &mut term.black] {
+                    *score = PhaseScore::new(
+                        scale(score.midgame, *weight),
+                        scale(score.endgame, *weight),
+                    );
+                }
+                term
+            }));
+        }
+        trace
+    }
+
+    fn options(&self) -> Vec<EngineOption> {
+        let mut options: Vec<EngineOption> = Vec::new();
+        for (_, evaluator) in &self.terms {
+            for option in evaluator.options() {
+                if !options
+                    .iter()
+                    .any(|known| known.name.eq_ignore_ascii_case(&option.name))
+                {
+                    options.push(option);
+                }
+            }
+        }
+        options
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<bool> {
+        let mut applied = false;
+        for (_, evaluator) in &mut self.terms {
+            applied |= evaluator.set_option(name, value)?;
+        }
+        Ok(applied)
+    }
+}
+
+/// Blends a middlegame and an endgame evaluator according to the game phase, computed
+/// like in [`PstEvaluator::phase`].
+#[derive(Debug, Clone, Default)]
+pub struct PhaseBlend<M, E> {
+    midgame: M,
+    endgame: E,
+}
+
+impl<M: ChessEvaluator, E: ChessEvaluator> PhaseBlend<M, E> {
+    /// Creates a blend using `midgame` with every piece on the board and `endgame` once
+    /// only pawns are left.
+    pub fn new(midgame: M, endgame: E) -> Self {
+        Self { midgame, endgame }
+    }
+}
+
+impl<M: ChessEvaluator, E: ChessEvaluator> ChessEvaluator for PhaseBlend<M, E> {
+    fn evaluate(&self, board: &Chessboard, color_id: u8) -> i32 {
+        PhaseScore::new(
+            self.midgame.evaluate(board, color_id),
+            self.endgame.evaluate(board, color_id),
+        )
+        .taper(PstEvaluator::phase(board))
+    }
+
+    /// Reports the terms of the middlegame evaluator in the middlegame column and those of
+    /// the endgame evaluator in the endgame column, each already blended by its own phase.
+    fn evaluate_with_trace(&self, board: &Chessboard, color_id: u8) -> EvalTrace {
+        let midgame = self.midgame.evaluate_with_trace(board, color_id);
+        let endgame = self.endgame.evaluate_with_trace(board, color_id);
+        let mut trace = EvalTrace {
+            phase: PstEvaluator::phase(board),
+            score: self.evaluate(board, color_id),
+            ..EvalTrace::default()
+        };
+
+        for mut term in midgame.terms {
+            term.white = PhaseScore::new(term.white.taper(midgame.phase), 0);
+            term.black = PhaseScore::new(term.black.taper(midgame.phase), 0);
+            trace.terms.push(term);
+        }
+        for mut term in endgame.terms {
+            term.white = PhaseScore::new(0, term.white.taper(endgame.phase));
+            term.black = PhaseScore::new(0, term.black.taper(endgame.phase));
+            trace.terms.push(term);
+        }
+        trace
+    }
+
+    fn options(&self) -> Vec<EngineOption> {
+        let mut options = self.midgame.options();
+        for option in self.endgame.options() {
+            if !options
+                .iter()
+                .any(|known| known.name.eq_ignore_ascii_case(&option.name))
+            {
+                options.push(option);
+            }
+        }
+        options
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<bool> {
+        let midgame = self.midgame.set_option(name, value)?;
+        let endgame = self.endgame.set_option(name, value)?;
+        Ok(midgame || endgame)
+    }
+}
+
+/// One slot of the evaluation cache, with the key XOR-ed with the data like in the
+/// transposition table.
+#[derive(Default)]
+struct CacheSlot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Caches the evaluations of an expensive evaluator, keyed by
+/// [`Chessboard::zobrist_key`].
+pub struct Cached<E> {
+    inner: E,
+    slots: Vec<CacheSlot>,
+}
+
+impl<E: ChessEvaluator> Cached<E> {
+    /// Wraps `inner` with a cache of [`EVAL_CACHE_ENTRIES`] entries.
+    pub fn new(inner: E) -> Self {
+        Self::with_entries(inner, EVAL_CACHE_ENTRIES)
+    }
+
+    /// Wraps `inner` with a cache of `entries` entries.
+    pub fn with_entries(inner: E, entries: usize) -> Self {
+        Self {
+            inner,
+            slots: (0..entries.max(1)).map(|_| CacheSlot::default()).collect(),
+        }
+    }
+
+    /// Returns the wrapped evaluator.
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    /// Empties the cache.
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            *slot = CacheSlot::default();
+        }
+    }
+
+    fn key(board: &Chessboard, color_id: u8) -> u64 {
+        if color_id == WHITE_ID {
+            board.zobrist_key()
+        } else {
+            board.zobrist_key() ^ BLACK_KEY
+        }
+    }
+
+    fn slot(&self, key: u64) -> &CacheSlot {
+        &self.slots[(key % self.slots.len() as u64) as usize]
+    }
+}
+
+impl<E: Clone + ChessEvaluator> Clone for Cached<E> {
+    /// Clones start with an empty cache.
+    fn clone(&self) -> Self {
+        Self::with_entries(self.inner.clone(), self.slots.len())
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for Cached<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cached")
+            .field("inner", &self.inner)
+            .field("entries", &self.slots.len())
+            .finish()
+    }
+}
+
+impl<E: ChessEvaluator> ChessEvaluator for Cached<E> {
+    fn evaluate(&self, board: &Chessboard, color_id: u8) -> i32 {
+        let key = Self::key(board, color_id);
+        let slot = self.slot(key);
+        let data = slot.data.load(Ordering::Relaxed);
+        if data & FILLED != 0 && slot.key.load(Ordering::Relaxed) ^ data == key {
+            return data as u32 as i32;
+        }
+
+        let score = self.inner.evaluate(board, color_id);
+        let data = score as u32 as u64 | FILLED;
+        slot.key.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+        score
+    }
+
+    fn evaluate_with_trace(&self, board: &Chessboard, color_id: u8) -> EvalTrace {
+        self.inner.evaluate_with_trace(board, color_id)
+    }
+
+    fn options(&self) -> Vec<EngineOption> {
+        self.inner.options()
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<bool> {
+        let applied = self.inner.set_option(name, value)?;
+        if applied {
+            self.clear();
+        }
+        Ok(applied)
+    }
+}
+
+/// Adapts an evaluator scoring positions for the side to move, such as a neural network,
+/// to the colour the search asks for.
+///
+/// The inner evaluator is only ever queried for the side to move; the score is negated
+/// when the other colour asks.
+#[derive(Debug, Clone, Default)]
+pub struct SideToMove<E> {
+    inner: E,
+}
+
+impl<E: ChessEvaluator> SideToMove<E> {
+    /// Wraps `inner`.
+    pub fn new(inner: E) -> Self {
+        Self { inner }
+    }
+}
+
+impl<E: ChessEvaluator> ChessEvaluator for SideToMove<E> {
+    fn evaluate(&self, board: &Chessboard, color_id: u8) -> i32 {
+        let turn = board.current_turn();
+        let score = self.inner.evaluate(board, turn);
+        if color_id == turn {
+            score
+        } else {
+            -score
+        }
+    }
+
+    fn evaluate_with_trace(&self, board: &Chessboard, color_id: u8) -> EvalTrace {
+        let mut trace = self.inner.evaluate_with_trace(board, board.current_turn());
+        trace.score = self.evaluate(board, color_id);
+        trace
+    }
+
+    fn options(&self) -> Vec<EngineOption> {
+        self.inner.options()
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<bool> {
+        self.inner.set_option(name, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::{
+        constants::{BLACK_ID, START_FEN},
+        engine::implementations::{
+            odyn_evaluator::OdynEvaluator, selectable_evaluator::SelectableEvaluator,
+        },
+    };
+
+    const MIDDLEGAME: &str = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+    const ENDGAME: &str = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 1";
+
+    fn board(fen: &str) -> Chessboard {
+        Chessboard::from_fen(fen, " ")
+    }
+
+    /// Scores every position 100 for the side it is asked about, counting its calls.
+    #[derive(Default)]
+    struct Constant {
+        calls: AtomicUsize,
+    }
+
+    impl ChessEvaluator for Constant {
+        fn evaluate(&self, _board: &Chessboard, _color_id: u8) -> i32 {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            100
+        }
+    }
+
+    #[test]
+    fn test_weighted_sum_scales_each_evaluator() {
+        let board = board(ENDGAME);
+        let sum = WeightedSum::new()
+            .with(2.0, OdynEvaluator)
+            .with(0.5, Constant::default());
+
+        assert_eq!(sum.evaluate(&board, WHITE_ID), 2 * 100 + 50);
+        assert_eq!(WeightedSum::new().evaluate(&board, WHITE_ID), 0);
+
+        let trace = sum.evaluate_with_trace(&board, WHITE_ID);
+        assert_eq!(trace.score, 250);
+        assert_eq!(trace.terms.len(), 2);
+        assert_eq!(trace.total().taper(trace.phase), 250);
+    }
+
+    #[test]
+    fn test_phase_blend_follows_the_game_phase() {
+        let blend = PhaseBlend::new(Constant::default(), OdynEvaluator);
+
+        // Only the middlegame evaluator counts with every piece on the board.
+        assert_eq!(blend.evaluate(&board(START_FEN), WHITE_ID), 100);
+        // Only the endgame evaluator counts once the pieces are gone.
+        assert_eq!(blend.evaluate(&board(ENDGAME), WHITE_ID), 100);
+        assert_eq!(blend.evaluate(&board(ENDGAME), BLACK_ID), -100);
+
+        let middlegame = board(MIDDLEGAME);
+        let trace = blend.evaluate_with_trace(&middlegame, WHITE_ID);
+        assert_eq!(trace.score, blend.evaluate(&middlegame, WHITE_ID));
+        assert_eq!(trace.total().taper(trace.phase), trace.score);
+    }
+
+    #[test]
+    fn test_cached_evaluates_each_position_once() {
+        let cached = Cached::with_entries(Constant::default(), 64);
+        let start = board(START_FEN);
+
+        assert_eq!(cached.evaluate(&start, WHITE_ID), 100);
+        assert_eq!(cached.evaluate(&start, WHITE_ID), 100);
+        assert_eq!(cached.inner().calls.load(Ordering::Relaxed), 1);
+
+        // Each colour and position has its own entry.
+        cached.evaluate(&start, BLACK_ID);
+        cached.evaluate(&board(ENDGAME), WHITE_ID);
+        assert_eq!(cached.inner().calls.load(Ordering::Relaxed), 3);
+
+        let mut cached = Cached::new(SelectableEvaluator::default());
+        let score = cached.evaluate(&board(MIDDLEGAME), WHITE_ID);
+        assert!(cached.set_option("Evaluator", "Tapered").unwrap());
+        assert_ne!(cached.evaluate(&board(MIDDLEGAME), WHITE_ID), score);
+    }
+
+    #[test]
+    fn test_side_to_move_negates_for_the_other_colour() {
+        let evaluator = SideToMove::new(Constant::default());
+        let white_to_move = board(START_FEN);
+        let black_to_move = board(ENDGAME);
+
+        assert_eq!(evaluator.evaluate(&white_to_move, WHITE_ID), 100);
+        assert_eq!(evaluator.evaluate(&white_to_move, BLACK_ID), -100);
+        assert_eq!(evaluator.evaluate(&black_to_move, BLACK_ID), 100);
+        assert_eq!(evaluator.evaluate(&black_to_move, WHITE_ID), -100);
+
+        let trace = evaluator.evaluate_with_trace(&black_to_move, WHITE_ID);
+        assert_eq!(trace.score, -100);
+        assert_eq!(trace.total().taper(trace.phase), -100);
+    }
+
+    #[test]
+    fn test_traces_add_up_to_the_evaluation() {
+        let evaluator = SelectableEvaluator::new(
+            crate::engine::implementations::selectable_evaluator::EvaluatorKind::Tapered,
+        );
+        for fen in [START_FEN, MIDDLEGAME, ENDGAME] {
+            let board = board(fen);
+            for color_id in [WHITE_ID, BLACK_ID] {
+                let trace = evaluator.evaluate_with_trace(&board, color_id);
+                let total = trace.total().taper(trace.phase);
+                let total = if color_id == WHITE_ID { total } else { -total };
+                assert_eq!(trace.score, evaluator.evaluate(&board, color_id));
+                // Each component rounds its own taper.
+                assert!((total - trace.score).abs() <= 2);
+            }
+        }
+
+        let trace = OdynEvaluator.evaluate_with_trace(&board(ENDGAME), BLACK_ID);
+        assert_eq!(trace.terms[0].white.midgame, 100);
+        assert_eq!(trace.terms[0].black.midgame, 0);
+        assert_eq!(trace.score, -100);
+    }
+}
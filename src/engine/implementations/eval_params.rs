@@ -0,0 +1,710 @@
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    constants::{BISHOP_ID, KNIGHT_ID, PAWN_ID, QUEEN_ID, ROOK_ID, WHITE_ID},
+    engine::{
+        evaluator::{EvalTrace, PhaseScore, TermTrace},
+        implementations::pst_evaluator::PstEvaluator,
+    },
+    game::chessboard::Chessboard,
+};
+
+/// Middlegame and endgame value of one evaluation parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Weight {
+    /// Value with every piece on the board.
+    pub midgame: i32,
+    /// Value in a pawn endgame.
+    pub endgame: i32,
+}
+
+impl Weight {
+    /// Creates a weight.
+    pub const fn new(midgame: i32, endgame: i32) -> Self {
+        Self { midgame, endgame }
+    }
+}
+
+/// Group of parameters of the tapered evaluation, stored contiguously in [`EvalParams`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Term {
+    /// Piece values, from pawn to king.
+    Material,
+    /// Piece-square tables from White's point of view with `a8` first, one per piece.
+    PawnTable,
+    KnightTable,
+    BishopTable,
+    RookTable,
+    QueenTable,
+    KingTable,
+    /// Passed pawns with a free path, by relative rank.
+    Passed,
+    /// Passed pawns with a piece in their path, by relative rank.
+    PassedBlocked,
+    /// Candidate passers, by relative rank.
+    Candidate,
+    Isolated,
+    Doubled,
+    Backward,
+    Connected,
+    Phalanx,
+    /// Each group of adjacent files holding pawns.
+    Island,
+    /// Each safe square above the average, for knights, bishops, rooks and queens.
+    Mobility,
+    /// Own pawns one and two ranks in front of the king.
+    Shield,
+    /// Enemy pawns on the king files, by distance to the king minus one.
+    Storm,
+    /// Files next to the king without pawns.
+    OpenFile,
+    /// Files next to the king without own pawns.
+    SemiOpenFile,
+    /// Attacks on the king zone, by attack units.
+    KingDanger,
+}
+
+impl Term {
+    /// Every term, in the order of the parameter vector.
+    pub const ALL: [Term; 22] = [
+        Term::Material,
+        Term::PawnTable,
+        Term::KnightTable,
+        Term::BishopTable,
+        Term::RookTable,
+        Term::QueenTable,
+        Term::KingTable,
+        Term::Passed,
+        Term::PassedBlocked,
+        Term::Candidate,
+        Term::Isolated,
+        Term::Doubled,
+        Term::Backward,
+        Term::Connected,
+        Term::Phalanx,
+        Term::Island,
+        Term::Mobility,
+        Term::Shield,
+        Term::Storm,
+        Term::OpenFile,
+        Term::SemiOpenFile,
+        Term::KingDanger,
+    ];
+
+    /// Returns the number of parameters of the term.
+    pub const fn size(self) -> usize {
+        match self {
+            Term::Material => 6,
+            Term::PawnTable
+            | Term::KnightTable
+            | Term::BishopTable
+            | Term::RookTable
+            | Term::QueenTable
+            | Term::KingTable
+            | Term::KingDanger => 64,
+            Term::Passed | Term::PassedBlocked | Term::Candidate => 8,
+            Term::Mobility | Term::Storm => 4,
+            Term::Shield => 2,
+            Term::Isolated
+            | Term::Doubled
+            | Term::Backward
+            | Term::Connected
+            | Term::Phalanx
+            | Term::Island
+            | Term::OpenFile
+            | Term::SemiOpenFile => 1,
+        }
+    }
+
+    /// Returns the index of the first parameter of the term.
+    pub const fn offset(self) -> usize {
+        let mut offset = 0;
+        let mut index = 0;
+        while index < self as usize {
+            offset += Term::ALL[index].size();
+            index += 1;
+        }
+        offset
+    }
+
+    /// Returns the name of the term in a parameter file.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Term::Material => "material",
+            Term::PawnTable => "pawn",
+            Term::KnightTable => "knight",
+            Term::BishopTable => "bishop",
+            Term::RookTable => "rook",
+            Term::QueenTable => "queen",
+            Term::KingTable => "king",
+            Term::Passed => "passed",
+            Term::PassedBlocked => "passed_blocked",
+            Term::Candidate => "candidate",
+            Term::Isolated => "isolated",
+            Term::Doubled => "doubled",
+            Term::Backward => "backward",
+            Term::Connected => "connected",
+            Term::Phalanx => "phalanx",
+            Term::Island => "island",
+            Term::Mobility => "mobility",
+            Term::Shield => "shield",
+            Term::Storm => "storm",
+            Term::OpenFile => "open_file",
+            Term::SemiOpenFile => "semi_open_file",
+            Term::KingDanger => "king_danger",
+        }
+    }
+
+    /// Returns the piece-square table of `piece_id`.
+    pub const fn table(piece_id: u8) -> Term {
+        match piece_id {
+            PAWN_ID => Term::PawnTable,
+            KNIGHT_ID => Term::KnightTable,
+            BISHOP_ID => Term::BishopTable,
+            ROOK_ID => Term::RookTable,
+            QUEEN_ID => Term::QueenTable,
+            _ => Term::KingTable,
+        }
+    }
+
+    /// Returns the index of parameter `index` of the term in the parameter vector.
+    pub const fn index(self, index: usize) -> usize {
+        self.offset() + index
+    }
+
+    /// Returns the term owning parameter `index` of the parameter vector.
+    pub fn containing(index: usize) -> Term {
+        Term::ALL
+            .into_iter()
+            .rev()
+            .find(|term| term.offset() <= index)
+            .unwrap_or(Term::Material)
+    }
+}
+
+/// Total number of parameters of the tapered evaluation.
+pub const PARAM_COUNT: usize = Term::KingDanger.offset() + Term::KingDanger.size();
+
+const PHASE_NAMES: [&str; 2] = ["mg", "eg"];
+
+/// Receives the terms of an evaluation as counts of parameters.
+///
+/// Evaluating a position adds up the weights of its terms; tuning needs the counts
+/// themselves, which are linear features of the parameters.
+pub trait TermSink {
+    /// Adds the parameter at `index` (see [`Term::index`]) `count` times to the score of
+    /// `color_id`.
+    fn add(&mut self, color_id: u8, index: usize, count: i32);
+}
+
+/// Collects the raw `(parameter index, count)` features, with positive counts favouring
+/// White.
+impl TermSink for Vec<(usize, i32)> {
+    fn add(&mut self, color_id: u8, index: usize, count: i32) {
+        self.push((index, if color_id == WHITE_ID { count } else { -count }));
+    }
+}
+
+/// Sums the weights of the terms from the point of view of one colour.
+pub struct TermScore<'a> {
+    params: &'a EvalParams,
+    color_id: u8,
+    /// Middlegame score.
+    pub midgame: i32,
+    /// Endgame score.
+    pub endgame: i32,
+}
+
+impl<'a> TermScore<'a> {
+    /// Creates an empty score from White's point of view, weighting terms with `params`.
+    pub fn new(params: &'a EvalParams) -> Self {
+        Self::for_color(params, WHITE_ID)
+    }
+
+    /// Creates an empty score from the point of view of `color_id`.
+    pub fn for_color(params: &'a EvalParams, color_id: u8) -> Self {
+        Self {
+            params,
+            color_id,
+            midgame: 0,
+            endgame: 0,
+        }
+    }
+}
+
+impl TermSink for TermScore<'_> {
+    fn add(&mut self, color_id: u8, index: usize, count: i32) {
+        let weight = self.params.weights[index];
+        let count = if color_id == self.color_id {
+            count
+        } else {
+            -count
+        };
+        self.midgame += weight.midgame * count;
+        self.endgame += weight.endgame * count;
+    }
+}
+
+/// Sums the weights of the terms per [`Term`] and colour, for evaluation traces.
+pub struct TermTracer<'a> {
+    params: &'a EvalParams,
+    /// Score of each term for Black and White, and whether the term was seen.
+    scores: [([PhaseScore; 2], bool); Term::ALL.len()],
+}
+
+impl<'a> TermTracer<'a> {
+    /// Creates an empty trace weighting terms with `params`.
+    pub fn new(params: &'a EvalParams) -> Self {
+        Self {
+            params,
+            scores: [([PhaseScore::default(); 2], false); Term::ALL.len()],
+        }
+    }
+
+    /// Returns the trace of `board` evaluated to `score`, with the terms that were added.
+    pub fn into_trace(self, board: &Chessboard, score: i32) -> EvalTrace {
+        EvalTrace {
+            terms: self.into_terms(),
+            phase: PstEvaluator::phase(board),
+            score,
+        }
+    }
+
+    /// Returns the terms that were added, in the order of [`Term::ALL`].
+    pub fn into_terms(self) -> Vec<TermTrace> {
+        Term::ALL
+            .into_iter()
+            .zip(self.scores)
+            .filter(|(_, (_, seen))| *seen)
+            .map(|(term, ([black, white], _))| TermTrace {
+                name: term.name().to_string(),
+                white,
+                black,
+            })
+            .collect()
+    }
+}
+
+impl TermSink for TermTracer<'_> {
+    fn add(&mut self, color_id: u8, index: usize, count: i32) {
+        let weight = self.params.weights[index];
+        let (scores, seen) = &mut self.scores[Term::containing(index) as usize];
+        scores[color_id as usize] = scores[color_id as usize]
+            + PhaseScore::new(weight.midgame * count, weight.endgame * count);
+        *seen = true;
+    }
+}
+
+/// Weights of every term of the tapered evaluation, as one parameter vector.
+///
+/// Parameters can be saved and loaded in a plain text format (see
+/// [`EvalParams::from_file`]) to be tuned outside the engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalParams {
+    weights: Vec<Weight>,
+}
+
+impl Default for EvalParams {
+    /// Uses the PeSTO material and piece-square tables, tuned by Ronald Friederich for
+    /// Rofchade, and hand-picked values for the other terms.
+    fn default() -> Self {
+        let mut params = Self {
+            weights: vec![Weight::default(); PARAM_COUNT],
+        };
+
+        params.set_term(
+            Term::Material,
+            &[82, 337, 365, 477, 1025, 0],
+            &[94, 281, 297, 512, 936, 0],
+        );
+        params.set_term(Term::PawnTable, &MG_PAWN, &EG_PAWN);
+        params.set_term(Term::KnightTable, &MG_KNIGHT, &EG_KNIGHT);
+        params.set_term(Term::BishopTable, &MG_BISHOP, &EG_BISHOP);
+        params.set_term(Term::RookTable, &MG_ROOK, &EG_ROOK);
+        params.set_term(Term::QueenTable, &MG_QUEEN, &EG_QUEEN);
+        params.set_term(Term::KingTable, &MG_KING, &EG_KING);
+
+        params.set_term(
+            Term::Passed,
+            &[0, 5, 10, 15, 30, 50, 80, 0],
+            &[0, 10, 15, 25, 45, 75, 120, 0],
+        );
+        params.set_term(
+            Term::PassedBlocked,
+            &[0, 2, 5, 7, 15, 25, 40, 0],
+            &[0, 5, 7, 12, 22, 37, 60, 0],
+        );
+        params.set_term(
+            Term::Candidate,
+            &[0, 2, 4, 8, 15, 25, 0, 0],
+            &[0, 4, 8, 14, 25, 40, 0, 0],
+        );
+        params.set_term(Term::Isolated, &[-10], &[-15]);
+        params.set_term(Term::Doubled, &[-10], &[-20]);
+        params.set_term(Term::Backward, &[-8], &[-10]);
+        params.set_term(Term::Connected, &[8], &[10]);
+        params.set_term(Term::Phalanx, &[5], &[8]);
+        params.set_term(Term::Island, &[-5], &[-10]);
+
+        params.set_term(Term::Mobility, &[4, 5, 2, 1], &[4, 5, 4, 2]);
+        params.set_term(Term::Shield, &[12, 6], &[0, 0]);
+        params.set_term(Term::Storm, &[-10, -20, -12, -6], &[0, 0, 0, 0]);
+        params.set_term(Term::OpenFile, &[-25], &[0]);
+        params.set_term(Term::SemiOpenFile, &[-12], &[0]);
+        // Few attack units are harmless, coordinated attacks are not.
+        for units in 0..Term::KingDanger.size() {
+            let danger = (units * units / 2).min(500) as i32;
+            params.set(Term::KingDanger, units, Weight::new(-danger, -danger / 4));
+        }
+
+        params
+    }
+}
+
+impl EvalParams {
+    /// Returns every weight, indexed by [`Term::index`].
+    pub fn weights(&self) -> &[Weight] {
+        &self.weights
+    }
+
+    /// Returns every weight mutably, indexed by [`Term::index`].
+    pub fn weights_mut(&mut self) -> &mut [Weight] {
+        &mut self.weights
+    }
+
+    /// Returns parameter `index` of `term`.
+    pub fn get(&self, term: Term, index: usize) -> Weight {
+        self.weights[term.index(index)]
+    }
+
+    /// Changes parameter `index` of `term`.
+    pub fn set(&mut self, term: Term, index: usize, weight: Weight) {
+        self.weights[term.index(index)] = weight;
+    }
+
+    fn set_term(&mut self, term: Term, midgame: &[i32], endgame: &[i32]) {
+        for (index, (&midgame, &endgame)) in midgame.iter().zip(endgame).enumerate() {
+            self.set(term, index, Weight::new(midgame, endgame));
+        }
+    }
+
+    /// Loads parameters saved with the [`Display`](fmt::Display) implementation.
+    ///
+    /// The file holds two sections per [`Term`], in any order: a `<term> <phase>` header
+    /// (`material mg`, `pawn eg`, `king_danger mg`, ...) followed by the values of the
+    /// term for that phase. Text after `#` is ignored.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("cannot read parameters from {}", path.display()))?;
+        text.parse()
+            .with_context(|| format!("invalid parameters in {}", path.display()))
+    }
+
+    /// Writes the parameters to `path` in the format read by [`EvalParams::from_file`].
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_string())
+            .with_context(|| format!("cannot write parameters to {}", path.display()))
+    }
+}
+
+impl fmt::Display for EvalParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (position, term) in Term::ALL.iter().enumerate() {
+            for (phase, name) in PHASE_NAMES.iter().enumerate() {
+                if position > 0 || phase > 0 {
+                    writeln!(f)?;
+                }
+                writeln!(f, "{} {}", term.name(), name)?;
+
+                let weights = &self.weights[term.offset()..term.offset() + term.size()];
+                for row in weights.chunks(8) {
+                    let values: Vec<String> = row
+                        .iter()
+                        .map(|weight| {
+                            let value = if phase == 0 {
+                                weight.midgame
+                            } else {
+                                weight.endgame
+                            };
+                            format!("{:4}", value)
+                        })
+                        .collect();
+                    writeln!(f, "{}", values.join(" "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for EvalParams {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut params = Self {
+            weights: vec![Weight::default(); PARAM_COUNT],
+        };
+        let mut seen = [[false; 2]; Term::ALL.len()];
+
+        let mut tokens = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(str::split_whitespace);
+
+        while let Some(name) = tokens.next() {
+            let term = Term::ALL
+                .into_iter()
+                .find(|term| term.name() == name)
+                .ok_or_else(|| anyhow!("unknown section '{}'", name))?;
+            let phase_name = tokens
+                .next()
+                .ok_or_else(|| anyhow!("missing phase after '{}'", name))?;
+            let phase = PHASE_NAMES
+                .iter()
+                .position(|&candidate| candidate == phase_name)
+                .ok_or_else(|| anyhow!("unknown phase '{}', expected mg or eg", phase_name))?;
+            if std::mem::replace(&mut seen[term as usize][phase], true) {
+                return Err(anyhow!("duplicate section '{} {}'", name, phase_name));
+            }
+
+            for index in 0..term.size() {
+                let token = tokens.next().ok_or_else(|| {
+                    anyhow!("'{} {}' needs {} values", name, phase_name, term.size())
+                })?;
+                let value = token.parse::<i32>().map_err(|_| {
+                    anyhow!("invalid value '{}' in '{} {}'", token, name, phase_name)
+                })?;
+
+                let weight = &mut params.weights[term.index(index)];
+                if phase == 0 {
+                    weight.midgame = value;
+                } else {
+                    weight.endgame = value;
+                }
+            }
+        }
+
+        for term in Term::ALL {
+            for (phase, present) in seen[term as usize].iter().enumerate() {
+                if !present {
+                    return Err(anyhow!(
+                        "missing section '{} {}'",
+                        term.name(),
+                        PHASE_NAMES[phase]
+                    ));
+                }
+            }
+        }
+
+        Ok(params)
+    }
+}
+
+#[rustfmt::skip]
+const MG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,  0,   0,
+     98, 134,  61,  95,  68, 126, 34, -11,
+     -6,   7,  26,  31,  65,  56, 25, -20,
+    -14,  13,   6,  21,  23,  12, 17, -23,
+    -27,  -2,  -5,  12,  17,   6, 10, -25,
+    -26,  -4,  -4, -10,   3,   3, 33, -12,
+    -35,  -1, -20, -23, -15,  24, 38, -22,
+      0,   0,   0,   0,   0,   0,  0,   0,
+];
+
+#[rustfmt::skip]
+const EG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const MG_KNIGHT: [i32; 64] = [
+    -167, -89, -34, -49,  61, -97, -15, -107,
+     -73, -41,  72,  36,  23,  62,   7,  -17,
+     -47,  60,  37,  65,  84, 129,  73,   44,
+      -9,  17,  19,  53,  37,  69,  18,   22,
+     -13,   4,  16,  13,  28,  19,  21,   -8,
+     -23,  -9,  12,  10,  19,  17,  25,  -16,
+     -29, -53, -12,  -3,  -1,  18, -14,  -19,
+    -105, -21, -58, -33, -17, -28, -19,  -23,
+];
+
+#[rustfmt::skip]
+const EG_KNIGHT: [i32; 64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,
+];
+
+#[rustfmt::skip]
+const MG_BISHOP: [i32; 64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,
+];
+
+#[rustfmt::skip]
+const EG_BISHOP: [i32; 64] = [
+    -14, -21, -11,  -8, -7,  -9, -17, -24,
+     -8,  -4,   7, -12, -3, -13,  -4, -14,
+      2,  -8,   0,  -1, -2,   6,   0,   4,
+     -3,   9,  12,   9, 14,  10,   3,   2,
+     -6,   3,  13,  19,  7,  10,  -3,  -9,
+    -12,  -3,   8,  10, 13,   3,  -7, -15,
+    -14, -18,  -7,  -1,  4,  -9, -15, -27,
+    -23,  -9, -23,  -5, -9, -16,  -5, -17,
+];
+
+#[rustfmt::skip]
+const MG_ROOK: [i32; 64] = [
+     32,  42,  32,  51, 63,  9,  31,  43,
+     27,  32,  58,  62, 80, 67,  26,  44,
+     -5,  19,  26,  36, 17, 45,  61,  16,
+    -24, -11,   7,  26, 24, 35,  -8, -20,
+    -36, -26, -12,  -1,  9, -7,   6, -23,
+    -45, -25, -16, -17,  3,  0,  -5, -33,
+    -44, -16, -20,  -9, -1, 11,  -6, -71,
+    -19, -13,   1,  17, 16,  7, -37, -26,
+];
+
+#[rustfmt::skip]
+const EG_ROOK: [i32; 64] = [
+    13, 10, 18, 15, 12,  12,   8,   5,
+    11, 13, 13, 11, -3,   3,   8,   3,
+     7,  7,  7,  5,  4,  -3,  -5,  -3,
+     4,  3, 13,  1,  2,   1,  -1,   2,
+     3,  5,  8,  4, -5,  -6,  -8, -11,
+    -4,  0, -5, -1, -7, -12,  -8, -16,
+    -6, -6,  0,  2, -9,  -9, -11,  -3,
+    -9,  2,  3, -1, -5, -13,   4, -20,
+];
+
+#[rustfmt::skip]
+const MG_QUEEN: [i32; 64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,
+];
+
+#[rustfmt::skip]
+const EG_QUEEN: [i32; 64] = [
+     -9,  22,  22,  27,  27,  19,  10,  20,
+    -17,  20,  32,  41,  58,  25,  30,   0,
+    -20,   6,   9,  49,  47,  35,  19,   9,
+      3,  22,  24,  45,  57,  40,  57,  36,
+    -18,  28,  19,  47,  31,  34,  39,  23,
+    -16, -27,  15,   6,   9,  17,  10,   5,
+    -22, -23, -30, -16, -16, -23, -36, -32,
+    -33, -28, -22, -43,  -5, -32, -20, -41,
+];
+
+#[rustfmt::skip]
+const MG_KING: [i32; 64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+     -9,  24,   2, -16, -20,   6,  22, -22,
+    -17, -20, -12, -27, -30, -25, -14, -36,
+    -49,  -1, -27, -39, -46, -44, -33, -51,
+    -14, -14, -22, -46, -44, -30, -15, -27,
+      1,   7,  -8, -64, -43, -16,   9,   8,
+    -15,  36,  12, -54,   8, -28,  24,  14,
+];
+
+#[rustfmt::skip]
+const EG_KING: [i32; 64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terms_tile_the_parameter_vector() {
+        let mut next = 0;
+        for term in Term::ALL {
+            assert_eq!(term.offset(), next);
+            next += term.size();
+        }
+        assert_eq!(next, PARAM_COUNT);
+        assert_eq!(EvalParams::default().weights().len(), PARAM_COUNT);
+        assert_eq!(Term::table(ROOK_ID), Term::RookTable);
+        assert_eq!(Term::containing(0), Term::Material);
+        assert_eq!(Term::containing(Term::Passed.index(7)), Term::Passed);
+        assert_eq!(Term::containing(PARAM_COUNT - 1), Term::KingDanger);
+    }
+
+    #[test]
+    fn test_parameters_round_trip_through_text() {
+        let mut params = EvalParams::default();
+        params.set(Term::RookTable, 3, Weight::new(7, -42));
+        params.set(Term::Island, 0, Weight::new(-1, -2));
+
+        let text = format!("# tuned parameters\n{}", params);
+        assert_eq!(text.parse::<EvalParams>().unwrap(), params);
+
+        let path = std::env::temp_dir().join("odyn_eval_params_round_trip.txt");
+        params.save(&path).unwrap();
+        assert_eq!(EvalParams::from_file(&path).unwrap(), params);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_parameters_are_rejected() {
+        let text = EvalParams::default().to_string();
+
+        assert!(text
+            .replace("king eg", "king mg")
+            .parse::<EvalParams>()
+            .is_err());
+        assert!(text.replacen("0", "x", 1).parse::<EvalParams>().is_err());
+        assert!(format!("{}\n1 2", text).parse::<EvalParams>().is_err());
+        assert!("pawn mg 1 2 3".parse::<EvalParams>().is_err());
+        assert!("material mg".parse::<EvalParams>().is_err());
+    }
+
+    #[test]
+    fn test_default_king_danger_grows_faster_than_attacks() {
+        let params = EvalParams::default();
+        let danger = |units| -params.get(Term::KingDanger, units).midgame;
+        let cap = danger(Term::KingDanger.size() - 1);
+
+        for units in 1..Term::KingDanger.size() - 1 {
+            let growth = danger(units + 1) - danger(units);
+            assert!(growth >= danger(units) - danger(units - 1) || danger(units + 1) == cap);
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use lazy_static::lazy_static;
+
+use crate::{
+    constants::{self, KING_ID, PAWN_ID, WHITE_ID},
+    game::{
+        chessboard::Chessboard,
+        utility::{index_to_square, square_to_index},
+    },
+};
+
+/// Score of a won king and pawn ending, on top of which [`score`] adds progress terms.
+/// It is far above any material balance and far below the mate range.
+pub const KPK_WIN_SCORE: i32 = 10_000;
+
+/// Positions in the bitbase: side to move, both kings, and the pawn on files `a` to `d`
+/// and ranks 2 to 7.
+const POSITIONS: usize = 2 * 64 * 64 * 4 * 6;
+
+lazy_static! {
+    /// One bit per position, set when the side with the pawn wins.
+    static ref BITBASE: Vec<u64> = generate();
+}
+
+/// Generates the bitbase now rather than on the first probe, which would otherwise
+/// stall a search.
+pub fn init() {
+    lazy_static::initialize(&BITBASE);
+}
+
+/// Returns whether the side with the pawn wins the king and pawn versus king ending on
+/// `board`, or `None` if `board` holds other pieces.
+///
+/// The position is looked up in a bitbase generated by retrograde analysis on first use.
+/// Rule-50 and repetition draws are left to the search.
+pub fn probe(board: &Chessboard) -> Option<bool> {
+    let occupancy = board.occupancy();
+    if occupancy.count_ones() != 3 {
+        return None;
+    }
+
+    let pawns =
+        board.piece_board(PAWN_ID, WHITE_ID) | board.piece_board(PAWN_ID, constants::BLACK_ID);
+    if pawns.count_ones() != 1 {
+        return None;
+    }
+    let pawn = pawns.trailing_zeros();
+    let strong_id = board.piece_at(pawn).1;
+    let strong_king = board.piece_board(KING_ID, strong_id).trailing_zeros();
+    let weak_king = board
+        .piece_board(KING_ID, constants::opposite(strong_id))
+        .trailing_zeros();
+
+    // Bring the pawn to a White pawn on the queen side.
+    let mut flip = if strong_id == WHITE_ID { 0 } else { 56 };
+    if index_to_square(pawn).1 >= 4 {
+        flip ^= 7;
+    }
+    Some(is_win(
+        board.current_turn() == strong_id,
+        strong_king ^ flip,
+        weak_king ^ flip,
+        pawn ^ flip,
+    ))
+}
+
+/// Scores a king and pawn versus king ending for `color_id`, or returns `None` if `board`
+/// holds other pieces.
+///
+/// Draws score 0. Wins score [`KPK_WIN_SCORE`] plus a bonus for advancing the pawn and
+/// bringing the strong king to it, so that the search makes progress.
+pub fn score(board: &Chessboard, color_id: u8) -> Option<i32> {
+    if !probe(board)? {
+        return Some(0);
+    }
+
+    let pawns =
+        board.piece_board(PAWN_ID, WHITE_ID) | board.piece_board(PAWN_ID, constants::BLACK_ID);
+    let pawn = pawns.trailing_zeros();
+    let strong_id = board.piece_at(pawn).1;
+    let strong_king = board.piece_board(KING_ID, strong_id).trailing_zeros();
+
+    let (row, _) = index_to_square(pawn);
+    let advance = if strong_id == WHITE_ID { 7 - row } else { row } as i32;
+    let score = KPK_WIN_SCORE + 20 * advance - 5 * distance(strong_king, pawn) as i32;
+    Some(if color_id == strong_id { score } else { -score })
+}
+
+/// Looks up a position with a White pawn on files `a` to `d`, ranks 2 to 7.
+fn is_win(white_to_move: bool, white_king: u32, black_king: u32, pawn: u32) -> bool {
+    let index = index(white_to_move, white_king, black_king, pawn);
+    BITBASE[index / 64] & (1 << (index % 64)) != 0
+}
+
+fn index(white_to_move: bool, white_king: u32, black_king: u32, pawn: u32) -> usize {
+    let (row, col) = index_to_square(pawn);
+    debug_assert!((1..=6).contains(&row) && col < 4);
+    let pawn = (row as usize - 1) * 4 + col as usize;
+    usize::from(white_to_move) + 2 * (black_king as usize + 64 * (white_king as usize + 64 * pawn))
+}
+
+/// Chebyshev distance between two squares, the number of king moves between them.
+fn distance(a: u32, b: u32) -> u32 {
+    let (a_row, a_col) = index_to_square(a);
+    let (b_row, b_col) = index_to_square(b);
+    a_row.abs_diff(b_row).max(a_col.abs_diff(b_col))
+}
+
+/// Squares a king on `square` attacks.
+fn king_moves(square: u32) -> impl Iterator<Item = u32> {
+    let (row, col) = index_to_square(square);
+    let rows = row.saturating_sub(1)..=(row + 1).min(7);
+    rows.flat_map(move |target_row| {
+        (col.saturating_sub(1)..=(col + 1).min(7))
+            .map(move |target_col| square_to_index(target_row, target_col))
+    })
+    .filter(move |&target| target != square)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+/// Position of the bitbase, White having the pawn.
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    white_to_move: bool,
+    white_king: u32,
+    black_king: u32,
+    pawn: u32,
+}
+
+impl Position {
+    fn from_index(index: usize) -> Self {
+        let white_to_move = index % 2 == 1;
+        let black_king = (index / 2 % 64) as u32;
+        let white_king = (index / 128 % 64) as u32;
+        let pawn = index / (128 * 64);
+        let (row, col) = (pawn / 4 + 1, pawn % 4);
+        Self {
+            white_to_move,
+            white_king,
+            black_king,
+            pawn: (row * 8 + col) as u32,
+        }
+    }
+
+    fn index(self) -> usize {
+        index(
+            self.white_to_move,
+            self.white_king,
+            self.black_king,
+            self.pawn,
+        )
+    }
+
+    /// Whether the White pawn attacks `square`.
+    fn pawn_attacks(self, square: u32) -> bool {
+        let (row, col) = index_to_square(self.pawn);
+        let (target_row, target_col) = index_to_square(square);
+        target_row + 1 == row && target_col.abs_diff(col) == 1
+    }
+
+    /// Classifies the position without looking at its successors.
+    fn classify(self) -> Outcome {
+        let promotion = self.pawn - 8;
+        if distance(self.white_king, self.black_king) <= 1
+            || self.white_king == self.pawn
+            || self.black_king == self.pawn
+            || (self.white_to_move && self.pawn_attacks(self.black_king))
+        {
+            return Outcome::Invalid;
+        }
+
+        // The pawn promotes, and the new queen cannot be taken.
+        if self.white_to_move
+            && index_to_square(self.pawn).0 == 1
+            && self.white_king != promotion
+            && self.black_king != promotion
+            && (distance(self.black_king, promotion) > 1
+                || distance(self.white_king, promotion) == 1)
+        {
+            return Outcome::Win;
+        }
+
+        if !self.white_to_move {
+            let guarded =
+                |square: u32| distance(self.white_king, square) <= 1 || self.pawn_attacks(square);
+            // Stalemate, or the pawn can be taken.
+            if king_moves(self.black_king).all(guarded)
+                || (distance(self.black_king, self.pawn) == 1
+                    && distance(self.white_king, self.pawn) > 1)
+            {
+                return Outcome::Draw;
+            }
+        }
+
+        Outcome::Unknown
+    }
+
+    /// Classifies the position from the outcomes of its successors.
+    fn retrograde(self, outcomes: &[Outcome]) -> Outcome {
+        let mut successors = Vec::with_capacity(10);
+        if self.white_to_move {
+            for king in king_moves(self.white_king) {
+                successors.push(Position {
+                    white_to_move: false,
+                    white_king: king,
+                    ..self
+                });
+            }
+            let (row, _) = index_to_square(self.pawn);
+            if row > 1 {
+                let push = Position {
+                    white_to_move: false,
+                    pawn: self.pawn - 8,
+                    ..self
+                };
+                successors.push(push);
+                let blocked = push.pawn == self.white_king || push.pawn == self.black_king;
+                if row == 6 && !blocked {
+                    successors.push(Position {
+                        pawn: self.pawn - 16,
+                        ..push
+                    });
+                }
+            }
+        } else {
+            for king in king_moves(self.black_king) {
+                successors.push(Position {
+                    white_to_move: true,
+                    black_king: king,
+                    ..self
+                });
+            }
+        }
+
+        // Moves leading to invalid positions are illegal and skipped.
+        let (good, bad) = if self.white_to_move {
+            (Outcome::Win, Outcome::Draw)
+        } else {
+            (Outcome::Draw, Outcome::Win)
+        };
+        let mut unknown = false;
+        for successor in successors {
+            let outcome = if successor.pawn == successor.white_king
+                || successor.pawn == successor.black_king
+            {
+                Outcome::Invalid
+            } else {
+                outcomes[successor.index()]
+            };
+            if outcome == good {
+                return good;
+            }
+            unknown |= outcome == Outcome::Unknown;
+        }
+        if unknown {
+            Outcome::Unknown
+        } else {
+            bad
+        }
+    }
+}
+
+/// Generates the bitbase, iterating over the unknown positions until none can be
+/// resolved from its successors. The remaining positions are draws.
+fn generate() -> Vec<u64> {
+    let mut outcomes: Vec<Outcome> = (0..POSITIONS)
+        .map(|index| Position::from_index(index).classify())
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in 0..POSITIONS {
+            if outcomes[index] != Outcome::Unknown {
+                continue;
+            }
+            let outcome = Position::from_index(index).retrograde(&outcomes);
+            if outcome != Outcome::Unknown {
+                outcomes[index] = outcome;
+                changed = true;
+            }
+        }
+    }
+
+    let mut bits = vec![0; POSITIONS / 64];
+    for (index, outcome) in outcomes.into_iter().enumerate() {
+        if outcome == Outcome::Win {
+            bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::BLACK_ID;
+
+    /// Builds a FEN with only the given pieces, each a `(square, letter)` pair.
+    fn fen(pieces: &[(u32, char)], white_to_move: bool) -> String {
+        let mut rows = Vec::new();
+        for row in 0..8 {
+            let mut text = String::new();
+            let mut empty = 0;
+            for col in 0..8 {
+                match pieces
+                    .iter()
+                    .find(|(square, _)| *square == square_to_index(row, col))
+                {
+                    Some((_, letter)) => {
+                        if empty > 0 {
+                            text.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        text.push(*letter);
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                text.push_str(&empty.to_string());
+            }
+            rows.push(text);
+        }
+        let turn = if white_to_move { "w" } else { "b" };
+        format!("{} {} - - 0 1", rows.join("/"), turn)
+    }
+
+    #[test]
+    fn test_opposition() {
+        let white = Chessboard::from_fen("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1", " ");
+        let black = Chessboard::from_fen("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1", " ");
+        assert_eq!(probe(&white), Some(false));
+        assert_eq!(probe(&black), Some(true));
+        assert_eq!(score(&white, WHITE_ID), Some(0));
+        assert!(score(&black, BLACK_ID).unwrap() <= -KPK_WIN_SCORE);
+
+        // The same positions with colours swapped.
+        let black = Chessboard::from_fen("8/8/8/4p3/4k3/8/4K3/8 b - - 0 1", " ");
+        let white = Chessboard::from_fen("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1", " ");
+        assert_eq!(probe(&black), Some(false));
+        assert_eq!(probe(&white), Some(true));
+
+        // Rook pawns with the defending king in front are draws.
+        let board = Chessboard::from_fen("7k/8/6K1/7P/8/8/8/8 w - - 0 1", " ");
+        assert_eq!(probe(&board), Some(false));
+        // A king on the 6th rank in front of its pawn wins whoever moves.
+        let board = Chessboard::from_fen("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", " ");
+        assert_eq!(probe(&board), Some(true));
+        // The pawn is lost.
+        let board = Chessboard::from_fen("8/8/8/8/8/8/3kP3/7K b - - 0 1", " ");
+        assert_eq!(probe(&board), Some(false));
+
+        assert_eq!(
+            probe(&Chessboard::from_fen(constants::START_FEN, " ")),
+            None
+        );
+        let board = Chessboard::from_fen("4k3/8/4K3/4P3/8/8/8/7N w - - 0 1", " ");
+        assert_eq!(probe(&board), None);
+    }
+
+    #[test]
+    fn test_bitbase_agrees_with_move_generation() {
+        // Every legal move of a won position for the defender, and one legal move of a won
+        // position for the attacker, must lead to a won position. Pawns below the 7th rank
+        // cannot promote, so every successor is either in the bitbase or a bare king draw.
+        let mut rng = fastrand::Rng::with_seed(42);
+        let mut checked = 0;
+        while checked < 2000 {
+            let pawn_row = rng.u32(2..=6);
+            let pawn = square_to_index(pawn_row, rng.u32(0..8));
+            let white_king = rng.u32(0..64);
+            let black_king = rng.u32(0..64);
+            let white_to_move = rng.bool();
+            let pieces = [(pawn, 'P'), (white_king, 'K'), (black_king, 'k')];
+            if white_king == pawn || black_king == pawn || distance(white_king, black_king) <= 1 {
+                continue;
+            }
+
+            let board = Chessboard::from_fen(&fen(&pieces, white_to_move), " ");
+            let color_id = board.current_turn();
+            if board.is_king_attacked(constants::opposite(color_id)) {
+                continue;
+            }
+
+            let outcomes: Vec<bool> = board
+                .legal_moves_vec(color_id)
+                .into_iter()
+                .map(|move_| {
+                    let mut next = board.clone();
+                    next.make_move_unchecked(move_);
+                    probe(&next).unwrap_or(false)
+                })
+                .collect();
+            let expected = if white_to_move {
+                outcomes.iter().any(|&win| win)
+            } else {
+                !outcomes.is_empty() && outcomes.iter().all(|&win| win)
+            };
+            assert_eq!(
+                probe(&board),
+                Some(expected),
+                "{}",
+                fen(&pieces, white_to_move)
+            );
+            checked += 1;
+        }
+    }
+}
@@ -0,0 +1,897 @@
+//! MuZero-style Monte Carlo tree search in the latent space of learned networks.
+//!
+//! The root position is embedded once by the representation network; every other node of
+//! the tree is a hidden state produced by the dynamics network from its parent and an
+//! action, so the rules of chess are only applied at the root. Children are chosen with
+//! the PUCT formula of MuZero, over values normalised by the bounds seen in the tree.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+
+use anyhow::{anyhow, Result};
+use tch::{nn, Kind, Tensor};
+
+use crate::{
+    constants::{NUM_MOVES, QUEEN_ID, START_FEN},
+    engine::{
+        engine::ChessEngine,
+        implementations::{
+            dynamics::DynamicsNet, prediction::PredictionNet, repr::RepresentationNet,
+        },
+        options::{self, EngineOption},
+        search::{InfoSink, Score, SearchInfo, SearchLimits, SearchResult},
+    },
+    game::{chess_move::Move, chessboard::Chessboard},
+};
+
+/// UCI name of the number of simulations run per search without a `nodes` limit.
+pub const SIMULATIONS_OPTION: &str = "Simulations";
+const MAX_SIMULATIONS: i64 = 1_000_000;
+/// Kernel size of the convolutions of the representation network.
+const KERNEL_SIZE: i64 = 3;
+/// Largest centipawn score reported for a value close to ±1.
+const MAX_REPORTED_CENTIPAWNS: f64 = 2000.0;
+
+/// Output of the networks for one node of the tree.
+pub struct Inference<S> {
+    /// Hidden state of the node.
+    pub state: S,
+    /// Prior probability of each of the [`NUM_MOVES`] actions, indexed by
+    /// [`action_index`].
+    pub policy: Vec<f32>,
+    /// Value in `[-1, 1]` from the point of view of the side to move.
+    pub value: f32,
+}
+
+/// Learned model searched by [`MuZeroEngine`].
+pub trait LatentModel {
+    /// Hidden state standing for a position.
+    type State;
+
+    /// Embeds `board` and predicts its policy and value (representation and prediction).
+    fn initial_inference(&self, board: &Chessboard) -> Inference<Self::State>;
+
+    /// Plays `action` in `state` and predicts the next state, its policy and value, and
+    /// the reward of the transition for the side that played it (dynamics and
+    /// prediction).
+    fn recurrent_inference(
+        &self,
+        state: &Self::State,
+        action: usize,
+    ) -> (Inference<Self::State>, f32);
+}
+
+/// Returns the action of `move_`: `from * 64 + to`, promotions sharing the action of the
+/// pawn move. The search therefore only considers queen promotions.
+pub fn action_index(move_: Move) -> usize {
+    (move_.start_index * 64 + move_.end_index) as usize
+}
+
+/// The three MuZero networks.
+pub struct MuZeroNets {
+    pub representation: RepresentationNet,
+    pub dynamics: DynamicsNet,
+    pub prediction: PredictionNet,
+}
+
+impl MuZeroNets {
+    /// Creates the networks under `path` with hidden states of `state_dim` values and
+    /// hidden layers of `hidden_dim` units.
+    pub fn new(path: nn::Path, state_dim: i64, hidden_dim: i64) -> Self {
+        Self {
+            representation: RepresentationNet::new(
+                path.clone() / "repr",
+                hidden_dim,
+                state_dim,
+                KERNEL_SIZE,
+            ),
+            dynamics: DynamicsNet::new(path.clone() / "dyn", state_dim, hidden_dim),
+            prediction: PredictionNet::new(path / "pred", state_dim, hidden_dim),
+        }
+    }
+
+    fn predict(&self, state: Tensor) -> Inference<Tensor> {
+        let (policy, value) = self.prediction.forward(&state);
+        Inference {
+            policy: Vec::<f32>::try_from(&policy.to_kind(Kind::Float))
+                .expect("the policy is a vector"),
+            value: value.double_value(&[]) as f32,
+            state,
+        }
+    }
+}
+
+impl LatentModel for MuZeroNets {
+    type State = Tensor;
+
+    fn initial_inference(&self, board: &Chessboard) -> Inference<Tensor> {
+        tch::no_grad(|| self.predict(self.representation.forward(&board.to_tensor())))
+    }
+
+    fn recurrent_inference(&self, state: &Tensor, action: usize) -> (Inference<Tensor>, f32) {
+        tch::no_grad(|| {
+            let one_hot = Tensor::zeros([NUM_MOVES], (Kind::Float, state.device()));
+            let _ = one_hot.narrow(0, action as i64, 1).fill_(1.0);
+            let (next_state, reward) = self.dynamics.forward(state, &one_hot);
+            (self.predict(next_state), reward.double_value(&[]) as f32)
+        })
+    }
+}
+
+/// Parameters of the tree search, with the defaults of MuZero for board games.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MctsConfig {
+    /// Simulations per search when the limits set no `nodes`.
+    pub simulations: u32,
+    /// Constants of the exploration term of PUCT.
+    pub pb_c_base: f32,
+    pub pb_c_init: f32,
+    /// Discount of the values of later states.
+    pub discount: f32,
+    /// Concentration of the Dirichlet noise mixed into the root priors.
+    pub dirichlet_alpha: f64,
+    /// Share of the noise in the root priors, `0` to disable it.
+    pub exploration_fraction: f32,
+    /// Temperature of the visit counts when choosing the move: `0` plays the most visited
+    /// move, `1` samples moves in proportion to their visits.
+    pub temperature: f32,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        Self {
+            simulations: 800,
+            pb_c_base: 19652.0,
+            pb_c_init: 1.25,
+            discount: 1.0,
+            dirichlet_alpha: 0.3,
+            exploration_fraction: 0.25,
+            temperature: 0.0,
+        }
+    }
+}
+
+/// Outcome of a [`MuZeroEngine::think`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MctsResult {
+    /// Move chosen, `None` when the position has no legal move.
+    pub best_move: Option<Move>,
+    /// Share of the root visits received by every legal move, summing to 1.
+    pub visits: Vec<(Move, f32)>,
+    /// Value of the root from the point of view of the side to move.
+    pub value: f32,
+    /// Simulations run.
+    pub simulations: u32,
+}
+
+/// Bounds of the values met in the tree, used to bring them into `[0, 1]`.
+#[derive(Debug, Clone, Copy)]
+struct MinMaxStats {
+    min: f32,
+    max: f32,
+}
+
+impl MinMaxStats {
+    fn new() -> Self {
+        Self {
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+        }
+    }
+
+    fn update(&mut self, value: f32) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn normalize(&self, value: f32) -> f32 {
+        if self.max > self.min {
+            (value - self.min) / (self.max - self.min)
+        } else {
+            value
+        }
+    }
+}
+
+/// Action leading from a node to one of its children.
+struct Edge {
+    action: usize,
+    prior: f32,
+    /// Index of the child in the tree, once the action has been tried.
+    child: Option<usize>,
+}
+
+struct Node<S> {
+    /// Hidden state, `None` until the node is expanded.
+    state: Option<S>,
+    /// Reward of the action leading here, for the side that played it.
+    reward: f32,
+    visits: u32,
+    /// Sum of the values backed up through the node, for its side to move.
+    value_sum: f32,
+    edges: Vec<Edge>,
+}
+
+impl<S> Node<S> {
+    fn new(reward: f32) -> Self {
+        Self {
+            state: None,
+            reward,
+            visits: 0,
+            value_sum: 0.0,
+            edges: Vec::new(),
+        }
+    }
+
+    fn value(&self) -> f32 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.value_sum / self.visits as f32
+        }
+    }
+}
+
+/// Chess engine choosing its moves by Monte Carlo tree search over a [`LatentModel`].
+///
+/// The root is expanded with the legal moves of the position only, their priors
+/// renormalised and mixed with Dirichlet noise. Below it the search trusts the model, and
+/// the move is chosen from the visit counts of the root.
+pub struct MuZeroEngine<M: LatentModel> {
+    model: M,
+    config: MctsConfig,
+    chessboard: Chessboard,
+    rng: fastrand::Rng,
+}
+
+impl<M: LatentModel> MuZeroEngine<M> {
+    /// Creates an engine searching `model` with the default configuration.
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            config: MctsConfig::default(),
+            chessboard: Chessboard::from_fen(START_FEN, " "),
+            rng: fastrand::Rng::new(),
+        }
+    }
+
+    /// Replaces the search parameters.
+    pub fn with_config(mut self, config: MctsConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Makes the noise and the move choices replay the same way for the same `seed`.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = fastrand::Rng::with_seed(seed);
+        self
+    }
+
+    /// Returns the search parameters.
+    pub fn config(&self) -> &MctsConfig {
+        &self.config
+    }
+
+    /// Returns the searched model.
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    /// Searches `board` within `limits` and chooses a move from the visit counts.
+    ///
+    /// The search runs `nodes` simulations, [`MctsConfig::simulations`] without that
+    /// limit, and stops earlier once the time budget of the side to move is spent or
+    /// `stop` is raised. Infinite and ponder searches only end on `stop`. `depth` bounds
+    /// the plies searched below the root.
+    pub fn think(
+        &mut self,
+        board: &Chessboard,
+        limits: &SearchLimits,
+        stop: &AtomicBool,
+    ) -> MctsResult {
+        let (simulations, deadline) = if limits.is_unbounded() {
+            (u32::MAX, None)
+        } else {
+            let simulations = limits.nodes.map_or(self.config.simulations, |nodes| {
+                nodes.min(u32::MAX as u64) as u32
+            });
+            let budget = limits.time_budget(board.current_turn());
+            (simulations, budget.map(|budget| Instant::now() + budget))
+        };
+        let max_depth = limits.depth.map(usize::from);
+
+        let mut moves = board.legal_moves_vec(board.current_turn());
+        // Underpromotions share the action of the queen promotion: keep one move per action.
+        moves.retain(|move_| move_.promotion_piece == 0 || move_.promotion_piece == QUEEN_ID);
+        if moves.is_empty() {
+            return MctsResult {
+                best_move: None,
+                visits: Vec::new(),
+                value: 0.0,
+                simulations: 0,
+            };
+        }
+
+        let root = self.model.initial_inference(board);
+        let mut priors: Vec<f32> = moves
+            .iter()
+            .map(|&move_| root.policy[action_index(move_)].max(0.0))
+            .collect();
+        let total: f32 = priors.iter().sum();
+        for prior in &mut priors {
+            *prior = if total > 0.0 {
+                *prior / total
+            } else {
+                1.0 / moves.len() as f32
+            };
+        }
+        let fraction = self.config.exploration_fraction;
+        if fraction > 0.0 {
+            let noise = sample_dirichlet(&mut self.rng, self.config.dirichlet_alpha, moves.len());
+            for (prior, noise) in priors.iter_mut().zip(noise) {
+                *prior = (1.0 - fraction) * *prior + fraction * noise as f32;
+            }
+        }
+
+        let mut tree = vec![Node::new(0.0)];
+        tree[0].edges = moves
+            .iter()
+            .zip(priors)
+            .map(|(&move_, prior)| Edge {
+                action: action_index(move_),
+                prior,
+                child: None,
+            })
+            .collect();
+        tree[0].state = Some(root.state);
+        tree[0].visits = 1;
+        tree[0].value_sum = root.value;
+
+        let mut stats = MinMaxStats::new();
+        let mut simulated = 0;
+        while simulated < simulations
+            && !stop.load(Ordering::Relaxed)
+            && deadline.is_none_or(|deadline| Instant::now() < deadline)
+        {
+            self.simulate(&mut tree, &mut stats, max_depth);
+            simulated += 1;
+        }
+
+        let counts: Vec<u32> = tree[0]
+            .edges
+            .iter()
+            .map(|edge| edge.child.map_or(0, |child| tree[child].visits))
+            .collect();
+        let visited = counts.iter().sum::<u32>().max(1) as f32;
+        let index = self.choose(&counts, &tree[0].edges);
+        MctsResult {
+            best_move: Some(moves[index]),
+            visits: moves
+                .iter()
+                .zip(&counts)
+                .map(|(&move_, &count)| (move_, count as f32 / visited))
+                .collect(),
+            value: tree[0].value(),
+            simulations: simulated,
+        }
+    }
+
+    /// Descends from the root to a new node, expands it with the model and backs its
+    /// value up the path.
+    ///
+    /// A node without any action of positive prior, or `max_depth` plies below the root,
+    /// is terminal: reaching it backs its value up again instead of descending.
+    fn simulate(
+        &self,
+        tree: &mut Vec<Node<M::State>>,
+        stats: &mut MinMaxStats,
+        max_depth: Option<usize>,
+    ) {
+        let mut path = vec![0];
+        let mut node = 0;
+        let (parent, edge) = loop {
+            if tree[node].edges.is_empty() || max_depth.is_some_and(|depth| path.len() > depth) {
+                let value = tree[node].value();
+                self.backup(tree, &path, value, stats);
+                return;
+            }
+            let edge = self.select(tree, node, stats);
+            match tree[node].edges[edge].child {
+                Some(child) if tree[child].state.is_some() => {
+                    node = child;
+                    path.push(node);
+                }
+                _ => break (node, edge),
+            }
+        };
+
+        let state = tree[parent].state.as_ref().expect("the parent is expanded");
+        let action = tree[parent].edges[edge].action;
+        let (inference, reward) = self.model.recurrent_inference(state, action);
+        let mut leaf = Node::new(reward);
+        leaf.edges = inference
+            .policy
+            .iter()
+            .enumerate()
+            .filter(|(_, &prior)| prior > 0.0)
+            .map(|(action, &prior)| Edge {
+                action,
+                prior,
+                child: None,
+            })
+            .collect();
+        leaf.state = Some(inference.state);
+        tree.push(leaf);
+        let child = tree.len() - 1;
+        tree[parent].edges[edge].child = Some(child);
+        path.push(child);
+        self.backup(tree, &path, inference.value, stats);
+    }
+
+    /// Adds `value`, seen by the side to move at the last node of `path`, to every node
+    /// of the path.
+    fn backup(
+        &self,
+        tree: &mut [Node<M::State>],
+        path: &[usize],
+        mut value: f32,
+        stats: &mut MinMaxStats,
+    ) {
+        // Values are kept for the side to move at each node: going up a ply turns the
+        // value of the child into the reward plus the discounted, negated value.
+        for &index in path.iter().rev() {
+            let node = &mut tree[index];
+            node.value_sum += value;
+            node.visits += 1;
+            if index != 0 {
+                stats.update(node.reward - self.config.discount * node.value());
+            }
+            value = node.reward - self.config.discount * value;
+        }
+    }
+
+    /// Returns the edge of `node` with the best PUCT score.
+    fn select(&self, tree: &[Node<M::State>], node: usize, stats: &MinMaxStats) -> usize {
+        let parent = &tree[node];
+        let parent_visits = parent.visits as f32;
+        let exploration = ((parent_visits + self.config.pb_c_base + 1.0) / self.config.pb_c_base)
+            .ln()
+            + self.config.pb_c_init;
+
+        let mut best = (0, f32::NEG_INFINITY);
+        for (index, edge) in parent.edges.iter().enumerate() {
+            let child = edge.child.map(|child| &tree[child]);
+            let visits = child.map_or(0, |child| child.visits);
+            let prior_score =
+                exploration * parent_visits.sqrt() / (visits as f32 + 1.0) * edge.prior;
+            let value_score = match child {
+                Some(child) if child.visits > 0 => {
+                    stats.normalize(child.reward - self.config.discount * child.value())
+                }
+                _ => 0.0,
+            };
+            let score = prior_score + value_score;
+            if score > best.1 {
+                best = (index, score);
+            }
+        }
+        best.0
+    }
+
+    /// Picks the index of the root move to play from the visit `counts`.
+    fn choose(&mut self, counts: &[u32], edges: &[Edge]) -> usize {
+        let temperature = self.config.temperature;
+        if temperature <= 0.0 || counts.iter().all(|&count| count == 0) {
+            // Most visited, ties going to the highest prior.
+            return (0..counts.len())
+                .max_by(|&a, &b| {
+                    counts[a]
+                        .cmp(&counts[b])
+                        .then(edges[a].prior.total_cmp(&edges[b].prior))
+                })
+                .unwrap_or(0);
+        }
+
+        let weights: Vec<f64> = counts
+            .iter()
+            .map(|&count| (count as f64).powf(1.0 / temperature as f64))
+            .collect();
+        let mut target = self.rng.f64() * weights.iter().sum::<f64>();
+        for (index, weight) in weights.iter().enumerate() {
+            if target < *weight {
+                return index;
+            }
+            target -= weight;
+        }
+        weights
+            .iter()
+            .rposition(|&weight| weight > 0.0)
+            .unwrap_or(0)
+    }
+}
+
+impl<M: LatentModel> ChessEngine for MuZeroEngine<M> {
+    fn set_position(&mut self, start: &Chessboard, moves: &[Move]) {
+        self.chessboard = start.replay(moves).0;
+    }
+
+    fn search(
+        &mut self,
+        limits: SearchLimits,
+        info: &dyn InfoSink,
+        stop: &AtomicBool,
+    ) -> SearchResult {
+        let started = Instant::now();
+        let board = self.chessboard.clone();
+        let result = self.think(&board, &limits, stop);
+        let Some(best_move) = result.best_move else {
+            return SearchResult::default();
+        };
+
+        let score = Score::Centipawns(value_to_centipawns(result.value));
+        info.info(&SearchInfo {
+            score: Some(score),
+            nodes: Some(result.simulations as u64),
+            time: Some(started.elapsed()),
+            pv: vec![best_move],
+            ..Default::default()
+        });
+
+        SearchResult {
+            best_move: Some(best_move),
+            ponder_move: None,
+            score: Some(score),
+            pv: vec![best_move],
+            lines: vec![(best_move, score, vec![best_move])],
+        }
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<()> {
+        if name.eq_ignore_ascii_case(SIMULATIONS_OPTION) {
+            self.config.simulations = options::parse_spin(name, value, 1, MAX_SIMULATIONS)? as u32;
+            return Ok(());
+        }
+        Err(anyhow!("unknown option '{}'", name))
+    }
+
+    fn options(&self) -> Vec<EngineOption> {
+        vec![EngineOption::spin(
+            SIMULATIONS_OPTION,
+            MctsConfig::default().simulations as i64,
+            1,
+            MAX_SIMULATIONS,
+        )]
+    }
+}
+
+/// Converts a value in `[-1, 1]` to centipawns, reading `(1 + value) / 2` as the
+/// expected score of a player that many centipawns ahead.
+fn value_to_centipawns(value: f32) -> i32 {
+    let score = ((1.0 + value as f64) / 2.0).clamp(1e-6, 1.0 - 1e-6);
+    (400.0 * (score / (1.0 - score)).log10())
+        .clamp(-MAX_REPORTED_CENTIPAWNS, MAX_REPORTED_CENTIPAWNS)
+        .round() as i32
+}
+
+/// Samples `size` weights from a symmetric Dirichlet distribution of concentration
+/// `alpha`, as normalised Gamma variates.
+fn sample_dirichlet(rng: &mut fastrand::Rng, alpha: f64, size: usize) -> Vec<f64> {
+    let mut samples: Vec<f64> = (0..size).map(|_| sample_gamma(rng, alpha)).collect();
+    let total: f64 = samples.iter().sum();
+    for sample in &mut samples {
+        *sample = if total > 0.0 {
+            *sample / total
+        } else {
+            1.0 / size as f64
+        };
+    }
+    samples
+}
+
+/// Samples a Gamma variate of shape `alpha` and scale 1 (Marsaglia and Tsang).
+fn sample_gamma(rng: &mut fastrand::Rng, alpha: f64) -> f64 {
+    if alpha < 1.0 {
+        // Boost the shape above 1, then scale back with a uniform power.
+        return sample_gamma(rng, alpha + 1.0) * rng.f64().powf(1.0 / alpha);
+    }
+    let d = alpha - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let x = sample_normal(rng);
+        let v = (1.0 + c * x).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+        let u = 1.0 - rng.f64();
+        if u.ln() < 0.5 * x * x + d * (1.0 - v + v.ln()) {
+            return d * v;
+        }
+    }
+}
+
+/// Samples a standard normal variate (Box-Muller).
+fn sample_normal(rng: &mut fastrand::Rng) -> f64 {
+    let radius = (-2.0 * (1.0 - rng.f64()).ln()).sqrt();
+    radius * (2.0 * std::f64::consts::PI * rng.f64()).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, time::Duration};
+
+    use super::*;
+    use crate::uci::protocol::parse_uci_move;
+
+    /// Model with uniform priors and neutral values, rewarding one action from the root.
+    struct RewardModel {
+        rewarded: Option<usize>,
+        /// Number of recurrent inferences run.
+        expansions: Cell<u32>,
+    }
+
+    impl LatentModel for RewardModel {
+        /// Actions played since the root.
+        type State = Vec<usize>;
+
+        fn initial_inference(&self, _board: &Chessboard) -> Inference<Vec<usize>> {
+            Inference {
+                state: Vec::new(),
+                policy: vec![1.0 / NUM_MOVES as f32; NUM_MOVES as usize],
+                value: 0.0,
+            }
+        }
+
+        fn recurrent_inference(
+            &self,
+            state: &Vec<usize>,
+            action: usize,
+        ) -> (Inference<Vec<usize>>, f32) {
+            self.expansions.set(self.expansions.get() + 1);
+            let reward = if state.is_empty() && Some(action) == self.rewarded {
+                1.0
+            } else {
+                0.0
+            };
+            let mut next = state.clone();
+            next.push(action);
+            // Keep the latent tree small: a handful of actions below the root.
+            let mut policy = vec![0.0; NUM_MOVES as usize];
+            policy[..4].fill(0.25);
+            let inference = Inference {
+                state: next,
+                policy,
+                value: 0.0,
+            };
+            (inference, reward)
+        }
+    }
+
+    /// Model whose latent positions have no action left, and are bad for the side to
+    /// move in them.
+    struct DeadEndModel;
+
+    impl LatentModel for DeadEndModel {
+        type State = ();
+
+        fn initial_inference(&self, _board: &Chessboard) -> Inference<()> {
+            Inference {
+                state: (),
+                policy: vec![1.0 / NUM_MOVES as f32; NUM_MOVES as usize],
+                value: 0.0,
+            }
+        }
+
+        fn recurrent_inference(&self, _state: &(), _action: usize) -> (Inference<()>, f32) {
+            let inference = Inference {
+                state: (),
+                policy: vec![0.0; NUM_MOVES as usize],
+                value: -0.5,
+            };
+            (inference, 0.0)
+        }
+    }
+
+    fn engine(rewarded: Option<&str>) -> MuZeroEngine<RewardModel> {
+        let rewarded = rewarded.map(|uci| action_index(parse_uci_move(uci).unwrap()));
+        let model = RewardModel {
+            rewarded,
+            expansions: Cell::new(0),
+        };
+        MuZeroEngine::new(model).with_seed(5)
+    }
+
+    fn simulations(count: u64) -> SearchLimits {
+        SearchLimits {
+            nodes: Some(count),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_root_visits_cover_legal_moves_only() {
+        let board = Chessboard::from_fen(START_FEN, " ");
+        let mut engine = engine(None);
+        let result = engine.think(&board, &simulations(200), &AtomicBool::new(false));
+
+        assert_eq!(result.simulations, 200);
+        let legal = board.legal_moves_vec(board.current_turn());
+        assert_eq!(result.visits.len(), legal.len());
+        assert!(result.visits.iter().all(|(move_, _)| legal.contains(move_)));
+        let total: f32 = result.visits.iter().map(|(_, share)| share).sum();
+        assert!((total - 1.0).abs() < 1e-4, "{}", total);
+        assert!(legal.contains(&result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn test_root_promotes_to_a_queen() {
+        let board = Chessboard::from_fen("k7/7P/8/8/8/8/8/K7 w - - 0 1", " ");
+        let mut engine = engine(Some("h7h8q"));
+        let result = engine.think(&board, &simulations(100), &AtomicBool::new(false));
+
+        let promotions: Vec<Move> = result
+            .visits
+            .iter()
+            .map(|(move_, _)| *move_)
+            .filter(|move_| move_.promotion_piece != 0)
+            .collect();
+        assert_eq!(promotions, vec![parse_uci_move("h7h8q").unwrap()]);
+        assert_eq!(result.best_move, Some(promotions[0]));
+    }
+
+    #[test]
+    fn test_leaves_without_actions_are_terminal() {
+        let board = Chessboard::from_fen(START_FEN, " ");
+        let mut engine = MuZeroEngine::new(DeadEndModel).with_seed(2);
+        let result = engine.think(&board, &simulations(100), &AtomicBool::new(false));
+
+        assert_eq!(result.simulations, 100);
+        assert!(result.best_move.is_some());
+        // Every move leads to a position lost for the opponent.
+        assert!(result.value > 0.4, "{}", result.value);
+    }
+
+    #[test]
+    fn test_think_honours_time_and_depth() {
+        let board = Chessboard::from_fen(START_FEN, " ");
+        let mut engine = engine(None);
+        engine.set_option(SIMULATIONS_OPTION, "1000000").unwrap();
+
+        let limits = SearchLimits {
+            movetime: Some(Duration::from_millis(80)),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let result = engine.think(&board, &limits, &AtomicBool::new(false));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(result.simulations < 1_000_000);
+        assert!(result.best_move.is_some());
+
+        // One ply deep, only the 20 root moves are ever expanded.
+        engine.model().expansions.set(0);
+        let limits = SearchLimits {
+            depth: Some(1),
+            ..simulations(200)
+        };
+        let result = engine.think(&board, &limits, &AtomicBool::new(false));
+        assert_eq!(result.simulations, 200);
+        assert!(engine.model().expansions.get() <= 20);
+    }
+
+    #[test]
+    fn test_search_follows_rewards() {
+        let board = Chessboard::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", " ");
+        let mut engine = engine(Some("a1a8"));
+        let result = engine.think(&board, &simulations(300), &AtomicBool::new(false));
+
+        let mate = parse_uci_move("a1a8").unwrap();
+        assert_eq!(result.best_move, Some(mate));
+        let (_, share) = result
+            .visits
+            .iter()
+            .find(|(move_, _)| *move_ == mate)
+            .unwrap();
+        assert!(*share > 0.5, "{}", share);
+        assert!(result.value > 0.5, "{}", result.value);
+    }
+
+    #[test]
+    fn test_temperature_and_seed() {
+        let board = Chessboard::from_fen(START_FEN, " ");
+        let config = MctsConfig {
+            temperature: 1.0,
+            ..MctsConfig::default()
+        };
+        let picks = |seed| {
+            let mut engine = engine(None).with_config(config.clone()).with_seed(seed);
+            (0..5)
+                .map(|_| {
+                    engine
+                        .think(&board, &simulations(50), &AtomicBool::new(false))
+                        .best_move
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(picks(1), picks(1));
+
+        let mut engine = engine(None).with_config(config);
+        let moves: Vec<_> = (0..20)
+            .map(|_| {
+                engine
+                    .think(&board, &simulations(50), &AtomicBool::new(false))
+                    .best_move
+            })
+            .collect();
+        assert!(moves.iter().any(|move_| *move_ != moves[0]));
+    }
+
+    #[test]
+    fn test_engine_search() {
+        let mut engine = engine(Some("a1a8"));
+        engine.set_option(SIMULATIONS_OPTION, "100").unwrap();
+        assert!(engine.set_option(SIMULATIONS_OPTION, "0").is_err());
+
+        let board = Chessboard::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", " ");
+        engine.set_position(&board, &[]);
+        let result = engine.search(
+            SearchLimits::default(),
+            &|_: &_| {},
+            &AtomicBool::new(false),
+        );
+        assert_eq!(result.best_move, Some(parse_uci_move("a1a8").unwrap()));
+        assert!(matches!(result.score, Some(Score::Centipawns(cp)) if cp > 0));
+
+        // Stopped before the first simulation, the engine still answers from the priors.
+        let result = engine.search(SearchLimits::default(), &|_: &_| {}, &AtomicBool::new(true));
+        assert!(result.best_move.is_some());
+
+        let mated = Chessboard::from_fen("R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 1 1", " ");
+        engine.set_position(&mated, &[]);
+        let result = engine.search(
+            SearchLimits::default(),
+            &|_: &_| {},
+            &AtomicBool::new(false),
+        );
+        assert_eq!(result.best_move, None);
+    }
+
+    #[test]
+    fn test_dirichlet_noise() {
+        let mut rng = fastrand::Rng::with_seed(3);
+        for alpha in [0.3, 1.0, 2.5] {
+            let sample = sample_dirichlet(&mut rng, alpha, 20);
+            assert!(sample.iter().all(|&value| value >= 0.0));
+            assert!((sample.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+        // The mean of a Gamma variate is its shape.
+        let mean = (0..20000).map(|_| sample_gamma(&mut rng, 0.3)).sum::<f64>() / 20000.0;
+        assert!((mean - 0.3).abs() < 0.03, "{}", mean);
+    }
+
+    #[test]
+    fn test_min_max_normalization() {
+        let mut stats = MinMaxStats::new();
+        assert_eq!(stats.normalize(0.4), 0.4);
+        stats.update(-0.5);
+        stats.update(1.5);
+        assert_eq!(stats.normalize(0.5), 0.5);
+        assert_eq!(stats.normalize(1.5), 1.0);
+        assert_eq!(value_to_centipawns(0.0), 0);
+        assert!(value_to_centipawns(0.5) > 0 && value_to_centipawns(-1.0) == -2000);
+    }
+
+    #[test]
+    fn test_muzero_nets_search() {
+        let vs = nn::VarStore::new(tch::Device::Cpu);
+        let nets = MuZeroNets::new(vs.root() / "muzero", 32, 32);
+        let mut engine = MuZeroEngine::new(nets).with_seed(1);
+
+        let board = Chessboard::from_fen(START_FEN, " ");
+        let result = engine.think(&board, &simulations(16), &AtomicBool::new(false));
+        assert_eq!(result.simulations, 16);
+        assert!(board
+            .legal_moves_vec(board.current_turn())
+            .contains(&result.best_move.unwrap()));
+    }
+}
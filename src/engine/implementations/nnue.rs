@@ -0,0 +1,615 @@
+use std::{fs, path::Path, sync::Arc};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    constants::{
+        self, BLACK_ID, EMPTY_ID, FILE_A_INDEX, FILE_D_INDEX, FILE_F_INDEX, FILE_G_INDEX,
+        FILE_H_INDEX, KING_ID, PAWN_ID, PIECE_VALUES, WHITE_ID,
+    },
+    engine::{
+        evaluator::{ChessEvaluator, IncrementalEval},
+        options::EngineOption,
+    },
+    game::{
+        chess_move::{self, Move},
+        chessboard::Chessboard,
+        utility::{index_to_square, square_to_index},
+    },
+};
+
+/// Name of the option loading the weights of the network.
+pub const EVAL_FILE_OPTION: &str = "EvalFile";
+
+/// Inputs of each perspective: 2 colours, 6 pieces and 64 squares.
+pub const FEATURES: usize = 768;
+/// Largest hidden layer accepted in a weight file.
+pub const MAX_HIDDEN: usize = 4096;
+/// Upper bound of the clipped ReLU, which is also the scale of the hidden layer.
+pub const QA: i32 = 255;
+/// Scale of the output weights.
+pub const QB: i32 = 64;
+/// Centipawns of one unit of network output.
+pub const SCALE: i32 = 400;
+
+/// First bytes of a weight file.
+const MAGIC: &[u8; 8] = b"ODYNNUE1";
+/// Deepest ply the search can reach, quiescence included.
+const MAX_STACK: usize = u8::MAX as usize + 1;
+/// Most pieces a position can hold, which bounds the values of the accumulators.
+const MAX_PIECES: usize = 32;
+/// Hidden layer activation of one piece in [`Network::material`].
+const MATERIAL_UNIT: i16 = 16;
+
+/// Quantized weights of an NNUE network.
+///
+/// The network sees the board from both sides. Each perspective has [`FEATURES`] inputs,
+/// one per piece, colour relative to the perspective ("ours" or "theirs") and square,
+/// mirrored vertically for Black so that both sides see their pieces from the bottom. The
+/// inputs feed a shared hidden layer (the accumulator) whose values are updated
+/// incrementally as pieces move. The accumulators of the side to evaluate for and of its
+/// opponent go through a clipped ReLU and a single output neuron:
+///
+/// ```text
+/// eval = (sum(crelu(ours) * w_ours) + sum(crelu(theirs) * w_theirs) + bias)
+///        * SCALE / (QA * QB)
+/// ```
+///
+/// # Weight file format
+///
+/// All integers are little-endian:
+///
+/// | Field            | Type  | Count          |
+/// |------------------|-------|----------------|
+/// | magic `ODYNNUE1` | bytes | 8              |
+/// | hidden size `N`  | u32   | 1              |
+/// | feature weights  | i16   | `768 * N`      |
+/// | feature biases   | i16   | `N`            |
+/// | output weights   | i16   | `2 * N`        |
+/// | output bias      | i32   | 1              |
+///
+/// Feature weights are stored feature by feature, `N` values each. The feature of a piece
+/// seen from a perspective is `(side * 6 + piece_id - 1) * 64 + square`, where `side` is 0
+/// for the pieces of the perspective and 1 for the others, and `square` counts from `a1`
+/// (0) to `h8` (63) for White and from `a8` to `h1` for Black. Feature weights and biases
+/// are scaled by [`QA`], output weights by [`QB`] and the output bias by `QA * QB`. The
+/// first `N` output weights apply to the accumulator of the side the evaluation is for.
+///
+/// Accumulators hold `i16` values, so a file is rejected unless every hidden neuron stays
+/// in that range whatever the position: its bias plus the 32 largest feature weights of
+/// the neuron, in absolute value, must not exceed `i16::MAX`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self::material()
+    }
+}
+
+impl Network {
+    /// Creates a network with every weight set to zero.
+    pub fn zeroed(hidden: usize) -> Self {
+        Self {
+            hidden,
+            feature_weights: vec![0; FEATURES * hidden],
+            feature_biases: vec![0; hidden],
+            output_weights: vec![0; 2 * hidden],
+            output_bias: 0,
+        }
+    }
+
+    /// Creates a network counting material with [`PIECE_VALUES`], used until a weight
+    /// file is loaded.
+    ///
+    /// Hidden neuron `piece_id - 1` of each perspective counts the pieces of that kind
+    /// the perspective owns.
+    pub fn material() -> Self {
+        let mut network = Self::zeroed(8);
+        for piece_id in PAWN_ID..KING_ID {
+            let neuron = piece_id as usize - 1;
+            for square in 0..64 {
+                let feature = (piece_id as usize - 1) * 64 + square;
+                network.feature_weights[feature * network.hidden + neuron] = MATERIAL_UNIT;
+            }
+            let weight = PIECE_VALUES[piece_id as usize] * QA * QB / (SCALE * MATERIAL_UNIT as i32);
+            network.output_weights[neuron] = weight as i16;
+            network.output_weights[network.hidden + neuron] = -weight as i16;
+        }
+        network
+    }
+
+    /// Returns the size of the hidden layer.
+    pub fn hidden(&self) -> usize {
+        self.hidden
+    }
+
+    /// Returns the weights of the feature of a `piece_id` of `color_id` on `square`,
+    /// seen from `perspective`.
+    fn weights(&self, perspective: u8, piece_id: u8, color_id: u8, square: u32) -> &[i16] {
+        let side = usize::from(color_id != perspective);
+        let square = if perspective == WHITE_ID {
+            square ^ 56
+        } else {
+            square
+        } as usize;
+        let feature = (side * 6 + piece_id as usize - 1) * 64 + square;
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+
+    /// Loads a network saved in the format described on [`Network`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .with_context(|| format!("cannot read network from {}", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("invalid network in {}", path.display()))
+    }
+
+    /// Writes the network to `path` in the format described on [`Network`].
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes())
+            .with_context(|| format!("cannot write network to {}", path.display()))
+    }
+
+    /// Parses a network in the format described on [`Network`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let rest = bytes
+            .strip_prefix(MAGIC.as_slice())
+            .ok_or_else(|| anyhow!("missing {} header", String::from_utf8_lossy(MAGIC)))?;
+        if rest.len() < 4 {
+            return Err(anyhow!("missing hidden layer size"));
+        }
+        let (size, rest) = rest.split_at(4);
+        let hidden = u32::from_le_bytes(size.try_into().unwrap()) as usize;
+        if hidden == 0 || hidden > MAX_HIDDEN {
+            return Err(anyhow!(
+                "hidden layer size {} is not between 1 and {}",
+                hidden,
+                MAX_HIDDEN
+            ));
+        }
+
+        let expected = 2 * (FEATURES * hidden + hidden + 2 * hidden) + 4;
+        if rest.len() != expected {
+            return Err(anyhow!(
+                "expected {} bytes of weights for {} hidden neurons, found {}",
+                expected,
+                hidden,
+                rest.len()
+            ));
+        }
+
+        let mut values = rest
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+        let mut take = |count: usize| values.by_ref().take(count).collect::<Vec<i16>>();
+        let feature_weights = take(FEATURES * hidden);
+        let feature_biases = take(hidden);
+        let output_weights = take(2 * hidden);
+        let bias = &rest[rest.len() - 4..];
+
+        let network = Self {
+            hidden,
+            feature_weights,
+            feature_biases,
+            output_weights,
+            output_bias: i32::from_le_bytes(bias.try_into().unwrap()),
+        };
+        network.check_accumulator_bounds()?;
+        Ok(network)
+    }
+
+    /// Checks that no position of at most [`MAX_PIECES`] pieces can overflow an accumulator.
+    fn check_accumulator_bounds(&self) -> Result<()> {
+        let mut weights = vec![0; FEATURES];
+        for neuron in 0..self.hidden {
+            for (feature, weight) in weights.iter_mut().enumerate() {
+                *weight =
+                    self.feature_weights[feature * self.hidden + neuron].unsigned_abs() as i32;
+            }
+            weights.sort_unstable_by(|a, b| b.cmp(a));
+
+            let bound = self.feature_biases[neuron].unsigned_abs() as i32
+                + weights[..MAX_PIECES].iter().sum::<i32>();
+            if bound > i16::MAX as i32 {
+                return Err(anyhow!(
+                    "hidden neuron {} can reach {}, beyond the range of the accumulators",
+                    neuron,
+                    bound
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Serializes the network in the format described on [`Network`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&(self.hidden as u32).to_le_bytes());
+        for value in self
+            .feature_weights
+            .iter()
+            .chain(&self.feature_biases)
+            .chain(&self.output_weights)
+        {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    /// Returns the evaluation for `color_id` of the position summed up by `accumulator`.
+    pub fn output(&self, accumulator: &Accumulator, color_id: u8) -> i32 {
+        let ours = &accumulator.values[color_id as usize];
+        let theirs = &accumulator.values[constants::opposite(color_id) as usize];
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+
+        // A full hidden layer of saturated neurons sums to about 7e10, beyond an `i32`.
+        let activation = |values: &[i16], weights: &[i16]| -> i64 {
+            values
+                .iter()
+                .zip(weights)
+                .map(|(&value, &weight)| (value as i32).clamp(0, QA) as i64 * weight as i64)
+                .sum()
+        };
+        let sum = activation(ours, our_weights)
+            + activation(theirs, their_weights)
+            + self.output_bias as i64;
+
+        (sum * SCALE as i64 / (QA * QB) as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+/// Hidden layer of both perspectives for one position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accumulator {
+    /// Values indexed by perspective colour id.
+    values: [Vec<i16>; 2],
+}
+
+impl Accumulator {
+    /// Computes the accumulator of `board` from scratch.
+    pub fn new(network: &Network, board: &Chessboard) -> Self {
+        let mut accumulator = Self {
+            values: [
+                network.feature_biases.clone(),
+                network.feature_biases.clone(),
+            ],
+        };
+        for (square, (piece_id, color_id)) in board.get_iterator_on_pieces() {
+            accumulator.add(network, piece_id, color_id, square);
+        }
+        accumulator
+    }
+
+    fn add(&mut self, network: &Network, piece_id: u8, color_id: u8, square: u32) {
+        for perspective in [BLACK_ID, WHITE_ID] {
+            let weights = network.weights(perspective, piece_id, color_id, square);
+            for (value, weight) in self.values[perspective as usize].iter_mut().zip(weights) {
+                *value += *weight;
+            }
+        }
+    }
+
+    fn remove(&mut self, network: &Network, piece_id: u8, color_id: u8, square: u32) {
+        for perspective in [BLACK_ID, WHITE_ID] {
+            let weights = network.weights(perspective, piece_id, color_id, square);
+            for (value, weight) in self.values[perspective as usize].iter_mut().zip(weights) {
+                *value -= *weight;
+            }
+        }
+    }
+
+    /// Applies `move_`, played with the `flags` returned by
+    /// [`Chessboard::make_move_unchecked`].
+    pub fn play(&mut self, network: &Network, move_: Move, flags: u16) {
+        let piece_id = chess_move::get_piece_flag(flags);
+        let color_id = chess_move::get_color_flag(flags);
+        let enemy_id = constants::opposite(color_id);
+        let (start, end) = (move_.start_index, move_.end_index);
+
+        if chess_move::get_castle_flag(flags) {
+            let (row, col) = index_to_square(end);
+            let (rook_start, rook_end) = if col == FILE_G_INDEX {
+                (FILE_H_INDEX, FILE_F_INDEX)
+            } else {
+                (FILE_A_INDEX, FILE_D_INDEX)
+            };
+            self.remove(network, piece_id, color_id, start);
+            self.add(network, piece_id, color_id, end);
+            self.remove(
+                network,
+                constants::ROOK_ID,
+                color_id,
+                square_to_index(row, rook_start),
+            );
+            self.add(
+                network,
+                constants::ROOK_ID,
+                color_id,
+                square_to_index(row, rook_end),
+            );
+            return;
+        }
+
+        let captured = chess_move::get_captured_piece_flag(flags);
+        if captured != EMPTY_ID {
+            self.remove(network, captured, enemy_id, end);
+        }
+        if chess_move::get_en_passant_flag(flags) {
+            // The captured pawn stands beside the moving pawn's start square.
+            let (start_row, _) = index_to_square(start);
+            let (_, end_col) = index_to_square(end);
+            self.remove(
+                network,
+                PAWN_ID,
+                enemy_id,
+                square_to_index(start_row, end_col),
+            );
+        }
+
+        let landing = if chess_move::get_promotion_flag(flags) {
+            move_.promotion_piece
+        } else {
+            piece_id
+        };
+        self.remove(network, piece_id, color_id, start);
+        self.add(network, landing, color_id, end);
+    }
+}
+
+/// Accumulators of the positions along the line explored by one search thread.
+struct NnueState<'a> {
+    network: &'a Network,
+    stack: Vec<Accumulator>,
+}
+
+impl IncrementalEval for NnueState<'_> {
+    fn refresh(&mut self, board: &Chessboard) {
+        let root = Accumulator::new(self.network, board);
+        if self.stack.is_empty() {
+            self.stack = vec![root; MAX_STACK];
+        } else {
+            self.stack[0] = root;
+        }
+    }
+
+    fn play(&mut self, ply: u8, move_: Option<Move>, flags: u16) {
+        let ply = ply as usize;
+        let (parents, children) = self.stack.split_at_mut(ply);
+        let child = &mut children[0];
+        child.clone_from(&parents[ply - 1]);
+        if let Some(move_) = move_ {
+            child.play(self.network, move_, flags);
+        }
+    }
+
+    fn evaluate(&self, _board: &Chessboard, ply: u8, color_id: u8) -> i32 {
+        self.network.output(&self.stack[ply as usize], color_id)
+    }
+}
+
+/// NNUE evaluator running on the CPU with integer arithmetic only.
+///
+/// Without a weight file the evaluator uses [`Network::material`]. Searches update the
+/// accumulators move by move (see [`ChessEvaluator::incremental`]); other callers pay for
+/// a full refresh on every evaluation.
+#[derive(Debug, Clone, Default)]
+pub struct NnueEvaluator {
+    network: Arc<Network>,
+}
+
+impl NnueEvaluator {
+    /// Creates an evaluator using `network`.
+    pub fn new(network: Network) -> Self {
+        Self {
+            network: Arc::new(network),
+        }
+    }
+
+    /// Returns the network in use.
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+}
+
+impl ChessEvaluator for NnueEvaluator {
+    fn evaluate(&self, board: &Chessboard, color_id: u8) -> i32 {
+        self.network
+            .output(&Accumulator::new(&self.network, board), color_id)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn IncrementalEval + '_>> {
+        Some(Box::new(NnueState {
+            network: &self.network,
+            stack: Vec::new(),
+        }))
+    }
+
+    fn options(&self) -> Vec<EngineOption> {
+        vec![EngineOption::string(EVAL_FILE_OPTION, "")]
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<bool> {
+        if !name.eq_ignore_ascii_case(EVAL_FILE_OPTION) {
+            return Ok(false);
+        }
+
+        let path = value.trim();
+        // GUIs send `<empty>` to reset a string option.
+        let network = if path.is_empty() || path == "<empty>" {
+            Network::default()
+        } else {
+            Network::from_file(path)?
+        };
+        self.network = Arc::new(network);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::START_FEN, engine::implementations::odyn_evaluator::OdynEvaluator,
+        uci::protocol::parse_uci_move,
+    };
+
+    fn random_network(hidden: usize, seed: u64) -> Network {
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let mut network = Network::zeroed(hidden);
+        for weight in network
+            .feature_weights
+            .iter_mut()
+            .chain(&mut network.feature_biases)
+            .chain(&mut network.output_weights)
+        {
+            *weight = rng.i16(-64..64);
+        }
+        network.output_bias = rng.i32(-1000..1000);
+        network
+    }
+
+    #[test]
+    fn test_material_network_counts_material() {
+        let evaluator = NnueEvaluator::default();
+        for fen in [
+            START_FEN,
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "r3k3/8/8/8/8/8/8/4KQ2 b - - 0 1",
+        ] {
+            let board = Chessboard::from_fen(fen, " ");
+            for color_id in [WHITE_ID, BLACK_ID] {
+                assert_eq!(
+                    evaluator.evaluate(&board, color_id),
+                    OdynEvaluator.evaluate(&board, color_id)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental_updates_match_a_full_refresh() {
+        let network = random_network(16, 7);
+        let evaluator = NnueEvaluator::new(network.clone());
+        let mut state = evaluator.incremental().unwrap();
+
+        // Castling on both sides, en passant, captures and promotions with and without capture.
+        let fen = "r3k2r/P5p1/8/8/5pP1/8/8/R3K2R b KQkq g3 0 1";
+        let moves = [
+            "f4g3", "e1g1", "e8c8", "a7a8q", "c8c7", "a8d8", "c7d8", "a1a2", "g3g2", "a2a3",
+            "g2f1n",
+        ];
+        let mut board = Chessboard::from_fen(fen, " ");
+        state.refresh(&board);
+
+        for (ply, move_) in moves.iter().enumerate() {
+            let move_ = parse_uci_move(move_).unwrap();
+            let legal = board.legal_moves_vec(board.current_turn());
+            assert!(legal.contains(&move_), "{} is illegal", move_);
+            let flags = board.make_move_unchecked(move_);
+            let ply = ply as u8 + 1;
+            state.play(ply, Some(move_), flags);
+
+            for color_id in [WHITE_ID, BLACK_ID] {
+                assert_eq!(
+                    state.evaluate(&board, ply, color_id),
+                    evaluator.evaluate(&board, color_id),
+                    "after {}",
+                    move_
+                );
+            }
+        }
+
+        // Null moves keep the accumulator of the parent.
+        state.play(moves.len() as u8 + 1, None, 0);
+        assert_eq!(
+            state.evaluate(&board, moves.len() as u8 + 1, WHITE_ID),
+            evaluator.evaluate(&board, WHITE_ID)
+        );
+    }
+
+    #[test]
+    fn test_evaluation_is_symmetric_between_colours() {
+        let evaluator = NnueEvaluator::new(random_network(8, 3));
+        let white = Chessboard::from_fen(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            " ",
+        );
+        let black = Chessboard::from_fen(
+            "rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 4 4",
+            " ",
+        );
+        assert_eq!(
+            evaluator.evaluate(&white, WHITE_ID),
+            evaluator.evaluate(&black, BLACK_ID)
+        );
+    }
+
+    #[test]
+    fn test_weight_files_round_trip() {
+        let network = random_network(4, 11);
+        let bytes = network.to_bytes();
+        assert_eq!(bytes.len(), 8 + 4 + 2 * (768 * 4 + 4 + 8) + 4);
+        assert_eq!(Network::from_bytes(&bytes).unwrap(), network);
+
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Network::from_bytes(b"NOTANNUE").is_err());
+        let mut huge = MAGIC.to_vec();
+        huge.extend_from_slice(&(MAX_HIDDEN as u32 + 1).to_le_bytes());
+        assert!(Network::from_bytes(&huge).is_err());
+
+        let path = std::env::temp_dir().join("odyn_nnue_round_trip.bin");
+        network.save(&path).unwrap();
+        let mut evaluator = NnueEvaluator::default();
+        assert!(evaluator
+            .set_option(EVAL_FILE_OPTION, path.to_str().unwrap())
+            .unwrap());
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(evaluator.network(), &network);
+
+        assert!(evaluator.set_option(EVAL_FILE_OPTION, "<empty>").unwrap());
+        assert_eq!(evaluator.network(), &Network::material());
+        assert!(evaluator
+            .set_option(EVAL_FILE_OPTION, "/no/such/file")
+            .is_err());
+        assert!(!evaluator.set_option("Hash", "16").unwrap());
+    }
+
+    #[test]
+    fn test_weight_files_that_can_overflow_an_accumulator_are_rejected() {
+        let mut network = Network::zeroed(2);
+        for feature in 0..MAX_PIECES {
+            network.feature_weights[feature * 2 + 1] = 1000;
+        }
+        // Only the 32 largest weights can add up, whatever their signs.
+        network.feature_weights[MAX_PIECES * 2 + 1] = -1000;
+        network.feature_biases[1] = -767;
+        assert!(Network::from_bytes(&network.to_bytes()).is_ok());
+
+        network.feature_biases[1] = -768;
+        assert!(Network::from_bytes(&network.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_output_of_the_largest_network_does_not_overflow() {
+        let mut network = Network::zeroed(MAX_HIDDEN);
+        network.feature_biases.fill(QA as i16);
+        network.output_weights.fill(i16::MAX);
+        let board = Chessboard::from_fen(START_FEN, " ");
+
+        let sum = 2 * MAX_HIDDEN as i64 * QA as i64 * i16::MAX as i64;
+        let expected = sum * SCALE as i64 / (QA * QB) as i64;
+        assert_eq!(
+            network.output(&Accumulator::new(&network, &board), WHITE_ID) as i64,
+            expected
+        );
+    }
+}
@@ -1,41 +1,405 @@
-use std::f32::INFINITY;
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    thread,
+};
+
+use anyhow::{anyhow, Result};
 
 use crate::{
-    constants::START_FEN,
-    engine::{engine::ChessEngine, evaluator::ChessEvaluator},
-    format_chessboard,
+    constants::{START_FEN, WHITE_ID},
+    engine::{
+        engine::{ChessEngine, SimpleEngine},
+        evaluator::{ChessEvaluator, EvalTrace},
+        implementations::{
+            alpha_beta::{AlphaBeta, MAX_DEPTH},
+            kpk,
+            search_params::SearchParams,
+            skill::{
+                Skill, DEFAULT_ELO, ELO_OPTION, LIMIT_STRENGTH_OPTION, MAX_ELO, MAX_SKILL_LEVEL,
+                MAX_SKILL_SEED, MIN_ELO, SKILL_LEVEL_OPTION, SKILL_MULTIPV, SKILL_SEED_OPTION,
+            },
+            tablebase::{Tablebases, TABLEBASE_PATH_OPTION},
+            transposition::{TranspositionTable, DEFAULT_HASH_MB},
+        },
+        options::{self, EngineOption},
+        search::{InfoSink, Score, SearchInfo, SearchLimits, SearchResult},
+    },
     game::{chess_move, chessboard::Chessboard},
 };
 
+/// UCI name of the transposition table size option, in megabytes.
+const HASH_OPTION: &str = "Hash";
+const MAX_HASH_MB: i64 = 4096;
+/// UCI name of the search thread count option.
+const THREADS_OPTION: &str = "Threads";
+const MAX_THREADS: i64 = 256;
+/// UCI name of the number of principal variations to search.
+const MULTIPV_OPTION: &str = "MultiPV";
+const MAX_MULTIPV: i64 = 256;
+/// UCI name of the draw avoidance option, in centipawns.
+const CONTEMPT_OPTION: &str = "Contempt";
+const MAX_CONTEMPT: i64 = 100;
+
 /// Basic chess engine implementation using a pluggable evaluator.
+///
+/// With more than one thread, helpers search the same position in parallel and share
+/// their findings through the transposition table (Lazy SMP).
 pub struct OdynEngine<E: ChessEvaluator> {
     chessboard: Chessboard,
+    /// Zobrist keys of the positions played before `chessboard`.
+    history: Vec<u64>,
     evaluator: E,
+    tt: TranspositionTable,
+    hash_mb: usize,
+    threads: usize,
+    params: SearchParams,
+    multipv: usize,
+    contempt: i32,
+    tablebases: Tablebases,
+    skill_level: i64,
+    limit_strength: bool,
+    elo: i64,
+    /// Seed of `rng`, `0` when it is seeded from the system.
+    skill_seed: u64,
+    /// Source of the random move choices of a limited strength.
+    rng: fastrand::Rng,
 }
 
 impl<E: ChessEvaluator> OdynEngine<E> {
     /// Creates a new engine initialized with the start position.
     pub fn new(evaluator: E) -> Self {
+        kpk::init();
         OdynEngine {
             chessboard: Chessboard::from_fen(START_FEN, " "),
-            evaluator: evaluator,
+            history: Vec::new(),
+            evaluator,
+            tt: TranspositionTable::new(DEFAULT_HASH_MB),
+            hash_mb: DEFAULT_HASH_MB,
+            threads: 1,
+            params: SearchParams::default(),
+            multipv: 1,
+            contempt: 0,
+            tablebases: Tablebases::new(),
+            skill_level: MAX_SKILL_LEVEL,
+            limit_strength: false,
+            elo: DEFAULT_ELO,
+            skill_seed: 0,
+            rng: fastrand::Rng::new(),
+        }
+    }
+
+    /// Returns the strength limit set by `UCI_LimitStrength`/`UCI_Elo`, or else by
+    /// `Skill Level`.
+    fn skill(&self) -> Option<Skill> {
+        if self.limit_strength {
+            Some(Skill::from_elo(self.elo))
+        } else {
+            Skill::from_level(self.skill_level)
+        }
+    }
+
+    fn reseed(&mut self) {
+        self.rng = match self.skill_seed {
+            0 => fastrand::Rng::new(),
+            seed => fastrand::Rng::with_seed(seed),
+        };
+    }
+
+    /// Replaces the best line of `result` by the one picked by `skill` among its lines.
+    fn weaken(&mut self, skill: Skill, mut result: SearchResult) -> SearchResult {
+        let index = skill.pick(&result.lines, &mut self.rng);
+        if let Some((move_, score, pv)) = result.lines.get(index).cloned() {
+            result.best_move = Some(move_);
+            result.ponder_move = pv.get(1).copied();
+            result.score = Some(score);
+            result.pv = pv;
+        }
+        result
+    }
+
+    /// Plays the best move of the tablebases when they cover the position and all of its
+    /// moves, without searching.
+    fn tablebase_move(&self, info: &dyn InfoSink) -> Option<SearchResult> {
+        let (best_move, score) = self.tablebases.root_move(&self.chessboard)?;
+        let pv = self
+            .tablebases
+            .best_line(&self.chessboard, MAX_DEPTH as usize);
+        let score = Score::from_value(score);
+        info.info(&SearchInfo {
+            depth: Some(1),
+            score: Some(score),
+            nodes: Some(1),
+            pv: pv.clone(),
+            string: Some("tablebase hit".to_string()),
+            ..Default::default()
+        });
+
+        Some(SearchResult {
+            best_move: Some(best_move),
+            ponder_move: pv.get(1).copied(),
+            score: Some(score),
+            pv: pv.clone(),
+            lines: vec![(best_move, score, pv)],
+        })
+    }
+
+    /// Fills in a legal move when the search was stopped before finding any, since the
+    /// engine must always answer with one.
+    ///
+    /// A principal variation cut short by a hash hit has no ponder move: the expected reply
+    /// is then taken from the transposition table.
+    fn with_fallback_move(&self, mut result: SearchResult) -> SearchResult {
+        if result.best_move.is_none() {
+            result.best_move = self
+                .chessboard
+                .legal_moves_vec(self.chessboard.current_turn())
+                .first()
+                .copied();
+        }
+
+        if let (Some(best_move), None) = (result.best_move, result.ponder_move) {
+            let mut board = self.chessboard.clone();
+            board.make_move_unchecked(best_move);
+            result.ponder_move = self
+                .tt
+                .probe(board.zobrist_key())
+                .and_then(|entry| entry.best_move)
+                .filter(|reply| board.legal_moves_vec(board.current_turn()).contains(reply));
+        }
+        result
+    }
+}
+
+impl<E: ChessEvaluator + Sync> ChessEngine for OdynEngine<E> {
+    fn new_game(&mut self) {
+        self.tt.clear();
+        // A seeded engine replays the same games.
+        self.reseed();
+    }
+
+    fn set_position(&mut self, start: &Chessboard, moves: &[chess_move::Move]) {
+        (self.chessboard, self.history) = start.replay(moves);
+    }
+
+    fn eval_trace(&self, board: &Chessboard) -> Option<EvalTrace> {
+        Some(self.evaluator.evaluate_with_trace(board, WHITE_ID))
+    }
+
+    fn search(
+        &mut self,
+        limits: SearchLimits,
+        info: &dyn InfoSink,
+        stop: &AtomicBool,
+    ) -> SearchResult {
+        if let Some(moves) = limits.mate {
+            let mut searcher = AlphaBeta::new(&self.evaluator, &self.tt, info, stop, limits.nodes)
+                .with_history(&self.history);
+            let result = searcher.mate_search(&self.chessboard, moves);
+            return self.with_fallback_move(result);
+        }
+
+        let skill = self.skill();
+        if self.multipv == 1 && skill.is_none() {
+            if let Some(result) = self.tablebase_move(info) {
+                return result;
+            }
+        }
+
+        let mut max_depth = limits.depth.unwrap_or(MAX_DEPTH);
+        let mut nodes = limits.nodes;
+        let mut multipv = self.multipv;
+        if let Some(skill) = skill {
+            max_depth = max_depth.min(skill.max_depth());
+            nodes = Some(nodes.map_or(skill.max_nodes(), |nodes| nodes.min(skill.max_nodes())));
+            multipv = multipv.max(SKILL_MULTIPV);
+        }
+        let contempt = self.contempt;
+        let (board, history, evaluator, tt, params, tablebases) = (
+            &self.chessboard,
+            &self.history,
+            &self.evaluator,
+            &self.tt,
+            &self.params,
+            &self.tablebases,
+        );
+        let helper_stop = AtomicBool::new(false);
+        let helper_nodes = AtomicU64::new(0);
+
+        let result = thread::scope(|scope| {
+            let helpers: Vec<_> = (1..self.threads)
+                .map(|thread_id| {
+                    let board = board.clone();
+                    let (helper_stop, helper_nodes) = (&helper_stop, &helper_nodes);
+                    scope.spawn(move || {
+                        let silent = |_: &SearchInfo| {};
+                        let mut searcher =
+                            AlphaBeta::new(evaluator, tt, &silent, helper_stop, None)
+                                .with_thread(thread_id, helper_nodes)
+                                .with_params(params.clone())
+                                .with_history(history)
+                                .with_contempt(contempt)
+                                .with_tablebases(tablebases);
+                        let result = searcher.iterative_deepening(&board, max_depth);
+                        (result, searcher.completed_iteration())
+                    })
+                })
+                .collect();
+
+            let mut searcher = AlphaBeta::new(evaluator, tt, info, stop, nodes)
+                .with_thread(0, &helper_nodes)
+                .with_params(params.clone())
+                .with_history(history)
+                .with_contempt(contempt)
+                .with_multipv(multipv)
+                .with_tablebases(tablebases);
+            let result = searcher.iterative_deepening(board, max_depth);
+            let mut best = (result, searcher.completed_iteration());
+
+            // Helpers only search as long as the main thread does.
+            helper_stop.store(true, Ordering::Relaxed);
+            for helper in helpers {
+                match helper.join() {
+                    // Helpers search a single line, so they cannot answer a MultiPV search.
+                    Ok(candidate) if multipv == 1 => best = pick_thread_result(best, candidate),
+                    // A failing helper must not take down the main thread's answer.
+                    _ => continue,
+                }
+            }
+            best.0
+        });
+
+        let result = match skill {
+            Some(skill) => self.weaken(skill, result),
+            None => result,
+        };
+        self.with_fallback_move(result)
+    }
+
+    fn analyze(
+        &mut self,
+        limits: SearchLimits,
+        lines: usize,
+        info: &dyn InfoSink,
+        stop: &AtomicBool,
+    ) -> Vec<(chess_move::Move, Score, Vec<chess_move::Move>)> {
+        let multipv = std::mem::replace(&mut self.multipv, lines.max(1));
+        let result = self.search(limits, info, stop);
+        self.multipv = multipv;
+        result.lines
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<()> {
+        if name.eq_ignore_ascii_case(HASH_OPTION) {
+            let hash_mb = options::parse_spin(name, value, 1, MAX_HASH_MB)? as usize;
+            if hash_mb != self.hash_mb {
+                self.tt = TranspositionTable::new(hash_mb);
+                self.hash_mb = hash_mb;
+            }
+            return Ok(());
+        }
+        if name.eq_ignore_ascii_case(THREADS_OPTION) {
+            self.threads = options::parse_spin(name, value, 1, MAX_THREADS)? as usize;
+            return Ok(());
+        }
+        if name.eq_ignore_ascii_case(MULTIPV_OPTION) {
+            self.multipv = options::parse_spin(name, value, 1, MAX_MULTIPV)? as usize;
+            return Ok(());
         }
+        if name.eq_ignore_ascii_case(CONTEMPT_OPTION) {
+            self.contempt = options::parse_spin(name, value, -MAX_CONTEMPT, MAX_CONTEMPT)? as i32;
+            return Ok(());
+        }
+        if name.eq_ignore_ascii_case(SKILL_LEVEL_OPTION) {
+            self.skill_level = options::parse_spin(name, value, 0, MAX_SKILL_LEVEL)?;
+            return Ok(());
+        }
+        if name.eq_ignore_ascii_case(LIMIT_STRENGTH_OPTION) {
+            self.limit_strength = options::parse_check(name, value)?;
+            return Ok(());
+        }
+        if name.eq_ignore_ascii_case(ELO_OPTION) {
+            self.elo = options::parse_spin(name, value, MIN_ELO, MAX_ELO)?;
+            return Ok(());
+        }
+        if name.eq_ignore_ascii_case(SKILL_SEED_OPTION) {
+            self.skill_seed = options::parse_spin(name, value, 0, MAX_SKILL_SEED)? as u64;
+            self.reseed();
+            return Ok(());
+        }
+        if self.params.set_option(name, value)? {
+            return Ok(());
+        }
+        if name.eq_ignore_ascii_case(TABLEBASE_PATH_OPTION) {
+            let path = value.trim();
+            // GUIs send `<empty>` to reset a string option.
+            self.tablebases = if path.is_empty() || path == "<empty>" {
+                Tablebases::new()
+            } else {
+                Tablebases::load_dir(path)?
+            };
+            return Ok(());
+        }
+        if self.evaluator.set_option(name, value)? {
+            // Scores stored with the previous evaluation are no longer comparable.
+            self.tt.clear();
+            return Ok(());
+        }
+
+        Err(anyhow!("unknown option '{}'", name))
+    }
+
+    fn options(&self) -> Vec<EngineOption> {
+        let mut options = vec![
+            EngineOption::spin(HASH_OPTION, DEFAULT_HASH_MB as i64, 1, MAX_HASH_MB),
+            EngineOption::spin(THREADS_OPTION, 1, 1, MAX_THREADS),
+            EngineOption::spin(MULTIPV_OPTION, 1, 1, MAX_MULTIPV),
+            EngineOption::spin(CONTEMPT_OPTION, 0, -MAX_CONTEMPT, MAX_CONTEMPT),
+            EngineOption::string(TABLEBASE_PATH_OPTION, ""),
+            EngineOption::spin(SKILL_LEVEL_OPTION, MAX_SKILL_LEVEL, 0, MAX_SKILL_LEVEL),
+            EngineOption::check(LIMIT_STRENGTH_OPTION, false),
+            EngineOption::spin(ELO_OPTION, DEFAULT_ELO, MIN_ELO, MAX_ELO),
+            EngineOption::spin(SKILL_SEED_OPTION, 0, 0, MAX_SKILL_SEED),
+        ];
+        options.extend(SearchParams::options());
+        options.extend(self.evaluator.options());
+        options
     }
 }
 
-impl<E: ChessEvaluator> ChessEngine for OdynEngine<E> {
-    fn position(&mut self, fen: &str, moves: Vec<crate::game::chess_move::Move>) {
+/// Result of one search thread with the depth and score of its last completed iteration.
+type ThreadResult = (SearchResult, Option<(u8, i32)>);
+
+/// Keeps the `current` pick unless `candidate` completed a deeper iteration with a
+/// better score.
+fn pick_thread_result(current: ThreadResult, candidate: ThreadResult) -> ThreadResult {
+    let better = match (current.1, candidate.1) {
+        (_, None) => false,
+        (None, Some(_)) => true,
+        (Some((depth, score)), Some((candidate_depth, candidate_score))) => {
+            candidate_depth > depth && candidate_score > score
+        }
+    };
+
+    if better && candidate.0.best_move.is_some() {
+        candidate
+    } else {
+        current
+    }
+}
+
+/// One-ply move picker kept for callers of the [`SimpleEngine`] interface.
+impl<E: ChessEvaluator> SimpleEngine for OdynEngine<E> {
+    fn position(&mut self, fen: &str, moves: Vec<chess_move::Move>) {
         self.chessboard = Chessboard::from_moves(fen, moves);
-        println!("{}", format_chessboard(&self.chessboard))
+        self.history.clear();
     }
 
-    fn current_best_move(&self) -> Option<(chess_move::Move, f32)> {
+    fn current_best_move(&self) -> Option<(chess_move::Move, i32)> {
         let current_color = self.chessboard.current_turn();
-        let mut max_value = -INFINITY;
+        let mut max_value = i32::MIN;
         let mut best_move = None;
 
         for current_move in self.chessboard.legal_moves(current_color) {
-            println!("Move {}", current_move);
             let mut cboard = self.chessboard.clone();
             cboard.make_move_unchecked(current_move);
             let value = self.evaluator.evaluate(&cboard, current_color);
@@ -49,3 +413,403 @@ impl<E: ChessEvaluator> ChessEngine for OdynEngine<E> {
         best_move.map(|m| (m, max_value))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicBool, Mutex};
+
+    use super::*;
+    use crate::engine::{
+        engine::SimpleEngineAdapter,
+        implementations::odyn_evaluator::OdynEvaluator,
+        search::{Score, SearchInfo},
+    };
+
+    #[test]
+    fn test_search_finds_back_rank_mate() {
+        let mut engine = OdynEngine::new(OdynEvaluator);
+        let board = Chessboard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", " ");
+        engine.set_position(&board, &[]);
+
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let reports = Mutex::new(Vec::new());
+        let sink = |info: &SearchInfo| reports.lock().unwrap().push(info.clone());
+        let result = engine.search(limits, &sink, &AtomicBool::new(false));
+
+        let best_move = result.best_move.unwrap();
+        assert_eq!(best_move.uci_move(), "a1a8");
+        assert_eq!(result.score, Some(Score::Mate(1)));
+        assert_eq!(result.pv.first(), Some(&best_move));
+
+        let last = reports.into_inner().unwrap().pop().expect("an info report");
+        assert_eq!(last.score, Some(Score::Mate(1)));
+        assert_eq!(last.pv, result.pv);
+    }
+
+    #[test]
+    fn test_search_returns_ponder_move_from_pv() {
+        let mut engine = OdynEngine::new(OdynEvaluator);
+        engine.set_position(&Chessboard::from_fen(START_FEN, " "), &[]);
+
+        let limits = SearchLimits {
+            depth: Some(2),
+            ..Default::default()
+        };
+        let result = engine.search(limits, &|_: &SearchInfo| {}, &AtomicBool::new(false));
+
+        assert_eq!(result.pv.len(), 2);
+        assert_eq!(result.best_move, result.pv.first().copied());
+        assert_eq!(result.ponder_move, result.pv.get(1).copied());
+    }
+
+    #[test]
+    fn test_stopped_search_still_returns_a_legal_move() {
+        let mut engine = OdynEngine::new(OdynEvaluator);
+        let board = Chessboard::from_fen(START_FEN, " ");
+        engine.set_position(&board, &[]);
+
+        let result = engine.search(
+            SearchLimits::default(),
+            &|_: &SearchInfo| {},
+            &AtomicBool::new(true),
+        );
+
+        let best_move = result.best_move.unwrap();
+        assert!(board.legal_moves(board.current_turn()).contains(&best_move));
+    }
+
+    #[test]
+    fn test_set_option_hash() {
+        let mut engine = OdynEngine::new(OdynEvaluator);
+
+        assert!(engine.set_option("hash", "1").is_ok());
+        assert!(engine.set_option("Hash", "0").is_err());
+        assert!(engine.set_option("Unknown", "1").is_err());
+    }
+
+    #[test]
+    fn test_search_finds_mate_in_two_with_each_technique_disabled() {
+        // 1. Kb6 (or 1. Kc7) forces mate on the next move.
+        let fen = "k7/8/2K5/8/8/8/8/7R w - - 0 1";
+        let toggles = [
+            "NullMove",
+            "LMR",
+            "PVS",
+            "Aspiration",
+            "ReverseFutility",
+            "Futility",
+            "Razoring",
+            "CheckExtensions",
+        ];
+
+        for toggle in [None].into_iter().chain(toggles.map(Some)) {
+            let mut engine = OdynEngine::new(OdynEvaluator);
+            if let Some(name) = toggle {
+                engine.set_option(name, "false").unwrap();
+            }
+            engine.set_position(&Chessboard::from_fen(fen, " "), &[]);
+
+            let limits = SearchLimits {
+                depth: Some(4),
+                ..Default::default()
+            };
+            let result = engine.search(limits, &|_: &SearchInfo| {}, &AtomicBool::new(false));
+
+            assert_eq!(
+                result.score,
+                Some(Score::Mate(2)),
+                "mate not found without {:?}",
+                toggle
+            );
+        }
+    }
+
+    fn solve_mate(fen: &str, moves: u32) -> (SearchResult, Vec<SearchInfo>) {
+        let mut engine = OdynEngine::new(OdynEvaluator);
+        engine.set_position(&Chessboard::from_fen(fen, " "), &[]);
+
+        let limits = SearchLimits {
+            mate: Some(moves),
+            ..Default::default()
+        };
+        let reports = Mutex::new(Vec::new());
+        let sink = |info: &SearchInfo| reports.lock().unwrap().push(info.clone());
+        let result = engine.search(limits, &sink, &AtomicBool::new(false));
+        (result, reports.into_inner().unwrap())
+    }
+
+    #[test]
+    fn test_mate_search_proves_mate_for_either_side() {
+        let (result, reports) = solve_mate("k7/8/2K5/8/8/8/8/7R w - - 0 1", 3);
+        assert_eq!(result.score, Some(Score::Mate(2)));
+        assert_eq!(reports.last().unwrap().score, Some(Score::Mate(2)));
+
+        // The same position with colors swapped.
+        let (result, _) = solve_mate("7r/8/8/8/8/2k5/8/K7 b - - 0 1", 2);
+        assert_eq!(result.score, Some(Score::Mate(2)));
+    }
+
+    #[test]
+    fn test_mate_search_reports_getting_mated() {
+        // Kb8 is forced and runs into Rh8#.
+        let (result, _) = solve_mate("k7/8/1K6/8/8/8/8/7R b - - 0 1", 1);
+
+        assert_eq!(
+            result.best_move.map(|m| m.uci_move()),
+            Some("a8b8".to_string())
+        );
+        assert_eq!(result.score, Some(Score::Mate(-1)));
+    }
+
+    #[test]
+    fn test_mate_search_refutes_missing_mate() {
+        let (result, reports) = solve_mate("k7/8/2K5/8/8/8/8/7R w - - 0 1", 1);
+
+        assert_eq!(result.score, None);
+        assert!(result.best_move.is_some());
+        assert_eq!(
+            reports.last().unwrap().string.as_deref(),
+            Some("mate in 1 refuted")
+        );
+    }
+
+    #[test]
+    fn test_mate_search_shares_the_table_with_normal_searches() {
+        let mut engine = OdynEngine::new(OdynEvaluator);
+        let board = Chessboard::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1", " ");
+        engine.set_position(&board, &[]);
+        let silent = |_: &SearchInfo| {};
+
+        let limits = SearchLimits {
+            depth: Some(4),
+            ..Default::default()
+        };
+        engine.search(limits, &silent, &AtomicBool::new(false));
+        let entry = engine.tt.probe(board.zobrist_key());
+        assert!(entry.is_some());
+
+        let limits = SearchLimits {
+            mate: Some(2),
+            ..Default::default()
+        };
+        let result = engine.search(limits, &silent, &AtomicBool::new(false));
+        assert_eq!(result.score, Some(Score::Mate(2)));
+        assert_eq!(engine.tt.probe(board.zobrist_key()), entry);
+    }
+
+    #[test]
+    fn test_analyze_returns_ranked_distinct_lines() {
+        let mut engine = OdynEngine::new(OdynEvaluator);
+        let board = Chessboard::from_fen("4k3/8/8/3q4/8/2N5/8/4K3 w - - 0 1", " ");
+        engine.set_position(&board, &[]);
+
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let reports = Mutex::new(Vec::new());
+        let sink = |info: &SearchInfo| reports.lock().unwrap().push(info.clone());
+        let lines = engine.analyze(limits, 3, &sink, &AtomicBool::new(false));
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].0.uci_move(), "c3d5");
+        assert!(lines.iter().all(|(move_, _, pv)| pv.first() == Some(move_)));
+        assert!(lines[1..].iter().all(|(move_, _, _)| *move_ != lines[0].0));
+        assert_ne!(lines[1].0, lines[2].0);
+
+        let centipawns = |score: &Score| match score {
+            Score::Centipawns(value) => *value,
+            Score::Mate(_) => panic!("no mate here"),
+        };
+        assert!(lines
+            .windows(2)
+            .all(|pair| centipawns(&pair[0].1) >= centipawns(&pair[1].1)));
+
+        let reports = reports.into_inner().unwrap();
+        let last_depth: Vec<_> = reports
+            .iter()
+            .filter(|info| info.depth == Some(3) && info.score.is_some())
+            .map(|info| info.multipv)
+            .collect();
+        assert_eq!(last_depth, [Some(1), Some(2), Some(3)]);
+
+        // The option is only overridden for the analysis.
+        let limits = SearchLimits {
+            depth: Some(1),
+            ..Default::default()
+        };
+        let result = engine.search(limits, &|_: &SearchInfo| {}, &AtomicBool::new(false));
+        assert_eq!(result.lines.len(), 1);
+    }
+
+    fn parse_moves(moves: &[&str]) -> Vec<chess_move::Move> {
+        moves
+            .iter()
+            .map(|move_| crate::uci::protocol::parse_uci_move(move_).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_losing_side_claims_threefold_repetition() {
+        let shuffle = [
+            "g1f3", "e8d8", "f3g1", "d8e8", "g1f3", "e8d8", "f3g1", "d8e8",
+        ];
+        // White is a queen down: repeating the position a third time is the best outcome.
+        let start = Chessboard::from_fen("4k3/8/8/8/8/8/q7/4K1N1 w - - 0 1", " ");
+        let moves = parse_moves(&shuffle);
+
+        for contempt in ["0", "50"] {
+            let mut engine = OdynEngine::new(OdynEvaluator);
+            engine.set_option("Contempt", contempt).unwrap();
+            engine.set_position(&start, &moves);
+
+            let limits = SearchLimits {
+                depth: Some(3),
+                ..Default::default()
+            };
+            let result = engine.search(limits, &|_: &SearchInfo| {}, &AtomicBool::new(false));
+
+            let draw = -contempt.parse::<i32>().unwrap();
+            assert_eq!(
+                result.best_move.map(|m| m.uci_move()),
+                Some("g1f3".to_string())
+            );
+            assert_eq!(result.score, Some(Score::Centipawns(draw)));
+        }
+    }
+
+    #[test]
+    fn test_winning_side_avoids_threefold_repetition() {
+        let shuffle = [
+            "g1f3", "e8d8", "f3g1", "d8e8", "g1f3", "e8d8", "f3g1", "d8e8",
+        ];
+        let start = Chessboard::from_fen("4k3/8/8/8/8/8/Q7/4K1N1 w - - 0 1", " ");
+        let moves = parse_moves(&shuffle);
+
+        let mut engine = OdynEngine::new(OdynEvaluator);
+        engine.set_position(&start, &moves);
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let result = engine.search(limits, &|_: &SearchInfo| {}, &AtomicBool::new(false));
+
+        assert_ne!(
+            result.best_move.map(|m| m.uci_move()),
+            Some("g1f3".to_string())
+        );
+        assert!(matches!(result.score, Some(Score::Centipawns(score)) if score > 0));
+    }
+
+    #[test]
+    fn test_multi_threaded_search_finds_mate() {
+        let mut engine = OdynEngine::new(OdynEvaluator);
+        engine.set_option("Threads", "4").unwrap();
+        assert!(engine.set_option("Threads", "0").is_err());
+
+        let board = Chessboard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", " ");
+        engine.set_position(&board, &[]);
+
+        let limits = SearchLimits {
+            depth: Some(4),
+            ..Default::default()
+        };
+        let result = engine.search(limits, &|_: &SearchInfo| {}, &AtomicBool::new(false));
+
+        assert_eq!(
+            result.best_move.map(|m| m.uci_move()),
+            Some("a1a8".to_string())
+        );
+        assert_eq!(result.score, Some(Score::Mate(1)));
+    }
+
+    #[test]
+    fn test_pick_thread_result_prefers_deeper_better_iterations() {
+        let result = |uci: &str| SearchResult {
+            best_move: Some(crate::uci::protocol::parse_uci_move(uci).unwrap()),
+            ..Default::default()
+        };
+
+        let main = (result("e2e4"), Some((6, 30)));
+        let shallower = (result("d2d4"), Some((5, 90)));
+        let worse = (result("d2d4"), Some((7, 10)));
+        let better = (result("g1f3"), Some((7, 50)));
+
+        assert_eq!(pick_thread_result(main.clone(), shallower).0, main.0);
+        assert_eq!(pick_thread_result(main.clone(), worse).0, main.0);
+        assert_eq!(pick_thread_result(main, better.clone()).0, better.0);
+    }
+
+    #[test]
+    fn test_simple_engine_adapter_plays_one_ply_move() {
+        let mut engine = SimpleEngineAdapter::new(OdynEngine::new(OdynEvaluator));
+        // White can win the queen Black just left hanging on d5.
+        let start = Chessboard::from_fen("4k3/3q4/8/8/8/2N5/8/4K3 b - - 0 1", " ");
+        engine.set_position(&start, &parse_moves(&["d7d5"]));
+
+        let result = engine.search(
+            SearchLimits::default(),
+            &|_: &SearchInfo| {},
+            &AtomicBool::new(false),
+        );
+
+        assert_eq!(
+            result.best_move.map(|m| m.uci_move()),
+            Some("c3d5".to_string())
+        );
+        assert_eq!(result.score, Some(Score::Centipawns(300)));
+    }
+
+    #[test]
+    fn test_limited_strength_is_capped_and_replayed_by_seed() {
+        let play_game = |options: &[(&str, &str)]| {
+            let mut engine = OdynEngine::new(OdynEvaluator);
+            for (name, value) in options {
+                engine.set_option(name, value).unwrap();
+            }
+            engine.new_game();
+
+            let mut board = Chessboard::from_fen(START_FEN, " ");
+            let mut moves = Vec::new();
+            let max_depth = Mutex::new(0);
+            for _ in 0..8 {
+                engine.set_position(&board, &[]);
+                let sink = |info: &SearchInfo| {
+                    let mut max_depth = max_depth.lock().unwrap();
+                    *max_depth = (*max_depth).max(info.depth.unwrap_or(0));
+                };
+                let result = engine.search(SearchLimits::default(), &sink, &AtomicBool::new(false));
+                let move_ = result.best_move.unwrap();
+                assert!(board.legal_moves(board.current_turn()).contains(&move_));
+                board.make_move_unchecked(move_);
+                moves.push(move_);
+            }
+            (moves, max_depth.into_inner().unwrap())
+        };
+
+        let (moves, max_depth) = play_game(&[("Skill Level", "3"), ("Skill Seed", "11")]);
+        assert_eq!(max_depth, 2);
+        assert_eq!(
+            play_game(&[("Skill Level", "3"), ("Skill Seed", "11")]).0,
+            moves
+        );
+
+        let (_, max_depth) = play_game(&[
+            ("UCI_LimitStrength", "true"),
+            ("UCI_Elo", "1000"),
+            ("Skill Seed", "5"),
+        ]);
+        assert_eq!(max_depth, 1);
+        assert!(engine_rejects("UCI_Elo", "900"));
+        assert!(engine_rejects("Skill Level", "21"));
+    }
+
+    fn engine_rejects(name: &str, value: &str) -> bool {
+        OdynEngine::new(OdynEvaluator)
+            .set_option(name, value)
+            .is_err()
+    }
+}
@@ -1,17 +1,19 @@
 use crate::{
     constants::{KING_ID, PIECE_VALUES},
-    engine::evaluator::ChessEvaluator,
+    engine::evaluator::{ChessEvaluator, EvalTrace, PhaseScore},
+    game::chessboard::Chessboard,
 };
 
 /// Material-only evaluator used by the default Odyn engine.
+#[derive(Debug, Clone, Copy, Default)]
 pub struct OdynEvaluator;
 
 impl ChessEvaluator for OdynEvaluator {
-    fn evaluate(&self, board: &crate::game::chessboard::Chessboard, color_id: u8) -> f32 {
+    fn evaluate(&self, board: &crate::game::chessboard::Chessboard, color_id: u8) -> i32 {
         // Simple evaluator that maximizes the number of points you have in pieces value
         // It's a good starting point for a simple evaluator
         // You can use any other heuristic you want
-        let mut points = 0.0;
+        let mut points = 0;
 
         for (_, (piece_id, piece_color)) in board.get_iterator_on_pieces() {
             if piece_id == KING_ID {
@@ -24,10 +26,25 @@ impl ChessEvaluator for OdynEvaluator {
                 continue;
             }
 
-            let sign = if color_id == piece_color { 1 } else { -1 } as f32;
+            let sign = if color_id == piece_color { 1 } else { -1 };
             points += sign * piece_value.unwrap();
         }
 
         points
     }
+
+    fn evaluate_with_trace(&self, board: &Chessboard, color_id: u8) -> EvalTrace {
+        let mut material = [0; 2];
+        for (_, (piece_id, piece_color)) in board.get_iterator_on_pieces() {
+            if piece_id != KING_ID {
+                material[piece_color as usize] += PIECE_VALUES[piece_id as usize];
+            }
+        }
+
+        let mut trace = EvalTrace::single("material", board, color_id, 0);
+        trace.terms[0].black = PhaseScore::flat(material[0]);
+        trace.terms[0].white = PhaseScore::flat(material[1]);
+        trace.score = self.evaluate(board, color_id);
+        trace
+    }
 }
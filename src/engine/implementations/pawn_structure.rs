@@ -0,0 +1,493 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    constants::{BLACK_ID, PAWN_ID, WHITE_ID},
+    engine::{
+        evaluator::{ChessEvaluator, EvalTrace},
+        implementations::{
+            eval_params::{EvalParams, Term, TermScore, TermSink, TermTracer},
+            pst_evaluator::{taper, PstEvaluator},
+        },
+    },
+    game::{
+        chessboard::Chessboard,
+        utility::{east_one, file_fill, iter_bits, north_fill, south_fill, west_one},
+    },
+};
+
+/// Number of entries of the pawn hash table.
+pub const PAWN_HASH_ENTRIES: usize = 1 << 14;
+
+/// Pawns of one side matching each pawn structure term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PawnTerms {
+    /// Pawns without enemy pawns in front of them on their own and adjacent files.
+    pub passed: u64,
+    /// Pawns on a file free of enemy pawns with at least as many supporters as sentries.
+    pub candidates: u64,
+    /// Pawns without friendly pawns on the adjacent files.
+    pub isolated: u64,
+    /// Pawns with a friendly pawn in front of them on the same file.
+    pub doubled: u64,
+    /// Pawns that cannot be supported and whose advance is controlled by an enemy pawn.
+    pub backward: u64,
+    /// Pawns protected by a friendly pawn.
+    pub connected: u64,
+    /// Pawns with a friendly pawn beside them on the same rank.
+    pub phalanx: u64,
+    /// Number of groups of adjacent files holding pawns.
+    pub islands: u32,
+}
+
+impl PawnTerms {
+    /// Classifies the pawns `own` of `color_id` against the enemy pawns `enemy`.
+    pub fn new(own: u64, enemy: u64, color_id: u8) -> Self {
+        if color_id == WHITE_ID {
+            return Self::for_white(own, enemy);
+        }
+
+        // Flipping the board vertically turns Black's pawns into pawns moving up.
+        let terms = Self::for_white(own.swap_bytes(), enemy.swap_bytes());
+        Self {
+            passed: terms.passed.swap_bytes(),
+            candidates: terms.candidates.swap_bytes(),
+            isolated: terms.isolated.swap_bytes(),
+            doubled: terms.doubled.swap_bytes(),
+            backward: terms.backward.swap_bytes(),
+            connected: terms.connected.swap_bytes(),
+            phalanx: terms.phalanx.swap_bytes(),
+            islands: terms.islands,
+        }
+    }
+
+    fn for_white(own: u64, enemy: u64) -> Self {
+        let own_attacks = adjacent(own >> 8);
+        let enemy_attacks = adjacent(enemy << 8);
+        let mut terms = Self {
+            connected: own & own_attacks,
+            phalanx: own & adjacent(own),
+            islands: islands(own),
+            ..Self::default()
+        };
+
+        for square in iter_bits(own) {
+            let pawn = 1 << square;
+            let front = front_span(pawn);
+            let adjacent_front = adjacent(front);
+            let supporters = own & adjacent(pawn | rear_span(pawn));
+
+            let doubled = own & front != 0;
+            let passed = !doubled && enemy & (front | adjacent_front) == 0;
+            let isolated = own & file_fill(adjacent(pawn)) == 0;
+
+            if passed {
+                terms.passed |= pawn;
+            } else if !doubled
+                && enemy & front == 0
+                && supporters.count_ones() >= (enemy & adjacent_front).count_ones()
+            {
+                terms.candidates |= pawn;
+            }
+            if doubled {
+                terms.doubled |= pawn;
+            }
+            if isolated {
+                terms.isolated |= pawn;
+            } else if !passed && supporters == 0 && enemy_attacks & (pawn >> 8) != 0 {
+                terms.backward |= pawn;
+            }
+        }
+
+        terms
+    }
+
+    /// Adds the terms of `color_id`, passed pawns excepted, to `sink`.
+    fn add_to(&self, color_id: u8, sink: &mut impl TermSink) {
+        let mut add = |term: Term, count: u32| sink.add(color_id, term.index(0), count as i32);
+
+        add(Term::Isolated, self.isolated.count_ones());
+        add(Term::Doubled, self.doubled.count_ones());
+        add(Term::Backward, self.backward.count_ones());
+        add(Term::Connected, self.connected.count_ones());
+        add(Term::Phalanx, self.phalanx.count_ones());
+        add(Term::Island, self.islands);
+        for square in iter_bits(self.candidates) {
+            sink.add(
+                color_id,
+                Term::Candidate.index(relative_rank(square, color_id)),
+                1,
+            );
+        }
+    }
+}
+
+/// Cached evaluation of a pawn structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PawnEntry {
+    /// Middlegame score from White's point of view, passed pawns excepted.
+    pub midgame: i32,
+    /// Endgame score from White's point of view, passed pawns excepted.
+    pub endgame: i32,
+    /// Passed pawns of both colours, scored separately since blockers are not pawns.
+    pub passed: u64,
+}
+
+impl PawnEntry {
+    /// Evaluates the pawns of `board` with `params`.
+    pub fn new(board: &Chessboard, params: &EvalParams) -> Self {
+        let mut score = TermScore::new(params);
+        let passed = pawn_terms(board, &mut score);
+
+        Self {
+            midgame: score.midgame,
+            endgame: score.endgame,
+            passed,
+        }
+    }
+}
+
+/// One slot of the pawn hash table.
+///
+/// Like in the transposition table, the key is stored XOR-ed with the data so that a slot
+/// torn by concurrent writes fails the key check.
+#[derive(Default)]
+struct PawnSlot {
+    key: AtomicU64,
+    score: AtomicU64,
+    passed: AtomicU64,
+}
+
+/// Lock-free cache of pawn structure evaluations indexed by
+/// [`Chessboard::pawn_key`].
+pub struct PawnHashTable {
+    slots: Vec<PawnSlot>,
+}
+
+impl PawnHashTable {
+    /// Creates a table holding `len` entries.
+    pub fn new(len: usize) -> Self {
+        Self {
+            slots: (0..len.max(1)).map(|_| PawnSlot::default()).collect(),
+        }
+    }
+
+    /// Returns the entry stored for `key`, if any.
+    ///
+    /// An empty slot reads as the entry of a board without pawns, whose key is 0, which is
+    /// also what evaluating such a board gives.
+    pub fn probe(&self, key: u64) -> Option<PawnEntry> {
+        let slot = &self.slots[self.index(key)];
+        let score = slot.score.load(Ordering::Relaxed);
+        let passed = slot.passed.load(Ordering::Relaxed);
+        if slot.key.load(Ordering::Relaxed) ^ score ^ passed != key {
+            return None;
+        }
+
+        Some(PawnEntry {
+            midgame: score as u32 as i32,
+            endgame: (score >> 32) as u32 as i32,
+            passed,
+        })
+    }
+
+    /// Stores `entry` for `key`, replacing whatever the slot held.
+    pub fn store(&self, key: u64, entry: PawnEntry) {
+        let slot = &self.slots[self.index(key)];
+        let score = entry.midgame as u32 as u64 | (entry.endgame as u32 as u64) << 32;
+        slot.key
+            .store(key ^ score ^ entry.passed, Ordering::Relaxed);
+        slot.score.store(score, Ordering::Relaxed);
+        slot.passed.store(entry.passed, Ordering::Relaxed);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.slots.len() as u64) as usize
+    }
+}
+
+/// Evaluator of the pawn structure, tapered like [`PstEvaluator`].
+///
+/// Terms depending only on pawns are cached in a [`PawnHashTable`]. Passed pawns are
+/// scored on every call since their bonus depends on pieces standing in their path.
+pub struct PawnStructure {
+    params: Arc<EvalParams>,
+    table: PawnHashTable,
+}
+
+impl Default for PawnStructure {
+    fn default() -> Self {
+        Self::new(Arc::default(), PAWN_HASH_ENTRIES)
+    }
+}
+
+impl Clone for PawnStructure {
+    /// Clones start with an empty cache.
+    fn clone(&self) -> Self {
+        Self::new(self.params.clone(), self.table.slots.len())
+    }
+}
+
+impl fmt::Debug for PawnStructure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PawnStructure")
+            .field("entries", &self.table.slots.len())
+            .finish()
+    }
+}
+
+impl PawnStructure {
+    /// Creates an evaluator weighting terms with `params` and caching up to `entries`
+    /// pawn structures.
+    pub fn new(params: Arc<EvalParams>, entries: usize) -> Self {
+        Self {
+            params,
+            table: PawnHashTable::new(entries),
+        }
+    }
+
+    /// Adds the pawn structure terms of `board` to `sink`, without using the cache.
+    pub fn terms(board: &Chessboard, sink: &mut impl TermSink) {
+        let passed = pawn_terms(board, sink);
+        passed_terms(board, passed, sink);
+    }
+
+    /// Returns the cached evaluation of the pawns of `board`, computing it if needed.
+    pub fn entry(&self, board: &Chessboard) -> PawnEntry {
+        let key = board.pawn_key();
+        if let Some(entry) = self.table.probe(key) {
+            return entry;
+        }
+
+        let entry = PawnEntry::new(board, &self.params);
+        self.table.store(key, entry);
+        entry
+    }
+
+    /// Returns the middlegame and endgame scores from White's point of view.
+    pub fn midgame_endgame(&self, board: &Chessboard) -> (i32, i32) {
+        let entry = self.entry(board);
+        let mut score = TermScore::new(&self.params);
+        score.midgame = entry.midgame;
+        score.endgame = entry.endgame;
+        passed_terms(board, entry.passed, &mut score);
+
+        (score.midgame, score.endgame)
+    }
+}
+
+impl ChessEvaluator for PawnStructure {
+    fn evaluate(&self, board: &Chessboard, color_id: u8) -> i32 {
+        let (midgame, endgame) = self.midgame_endgame(board);
+        let score = taper(midgame, endgame, PstEvaluator::phase(board));
+
+        if color_id == WHITE_ID {
+            score
+        } else {
+            -score
+        }
+    }
+
+    fn evaluate_with_trace(&self, board: &Chessboard, color_id: u8) -> EvalTrace {
+        let mut tracer = TermTracer::new(&self.params);
+        Self::terms(board, &mut tracer);
+        tracer.into_trace(board, self.evaluate(board, color_id))
+    }
+}
+
+/// Adds the terms of the pawns of both colours, passed pawns excepted, to `sink` and
+/// returns the passed pawns.
+fn pawn_terms(board: &Chessboard, sink: &mut impl TermSink) -> u64 {
+    let white = board.piece_board(PAWN_ID, WHITE_ID);
+    let black = board.piece_board(PAWN_ID, BLACK_ID);
+    let white_terms = PawnTerms::new(white, black, WHITE_ID);
+    let black_terms = PawnTerms::new(black, white, BLACK_ID);
+    white_terms.add_to(WHITE_ID, sink);
+    black_terms.add_to(BLACK_ID, sink);
+
+    white_terms.passed | black_terms.passed
+}
+
+/// Adds the `passed` pawns of both colours to `sink`, as blocked when any piece stands in
+/// their path.
+fn passed_terms(board: &Chessboard, passed: u64, sink: &mut impl TermSink) {
+    let white = board.piece_board(PAWN_ID, WHITE_ID);
+    let occupancy = board.occupancy();
+
+    for square in iter_bits(passed) {
+        let pawn = 1 << square;
+        let (color_id, path) = if white & pawn != 0 {
+            (WHITE_ID, front_span(pawn))
+        } else {
+            (BLACK_ID, front_span(pawn.swap_bytes()).swap_bytes())
+        };
+
+        let term = if path & occupancy != 0 {
+            Term::PassedBlocked
+        } else {
+            Term::Passed
+        };
+        sink.add(color_id, term.index(relative_rank(square, color_id)), 1);
+    }
+}
+
+/// Returns the rank of `square` counted from the side of `color_id`, from 0 to 7.
+fn relative_rank(square: u32, color_id: u8) -> usize {
+    let row = (square >> 3) as usize;
+    if color_id == WHITE_ID {
+        7 - row
+    } else {
+        row
+    }
+}
+
+fn adjacent(board: u64) -> u64 {
+    west_one(board) | east_one(board)
+}
+
+/// Squares north of `board` (towards rank 8), excluding `board` itself.
+fn front_span(board: u64) -> u64 {
+    north_fill(board >> 8)
+}
+
+/// Squares south of `board` (towards rank 1), excluding `board` itself.
+fn rear_span(board: u64) -> u64 {
+    south_fill(board << 8)
+}
+
+fn islands(pawns: u64) -> u32 {
+    let mut files = pawns;
+    files |= files >> 32;
+    files |= files >> 16;
+    files |= files >> 8;
+    let files = files & 0xff;
+    // Count the files holding pawns whose western neighbour holds none.
+    (files & !(files << 1)).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::utility::{square_to_index, string_to_square};
+
+    /// Returns the bitboard of the given squares, such as `["e4", "d5"]`.
+    fn squares(names: &[&str]) -> u64 {
+        names
+            .iter()
+            .map(|name| {
+                let (row, col) = string_to_square(name).unwrap();
+                1 << square_to_index(row, col)
+            })
+            .fold(0, |board, square| board | square)
+    }
+
+    fn terms(fen: &str, color_id: u8) -> PawnTerms {
+        let board = Chessboard::from_fen(fen, " ");
+        let own = board.piece_board(PAWN_ID, color_id);
+        let enemy = board.piece_board(PAWN_ID, 1 - color_id);
+        PawnTerms::new(own, enemy, color_id)
+    }
+
+    #[test]
+    fn test_passed_pawns() {
+        let fen = "4k3/1p6/8/2P5/8/5p2/P7/4K3 w - - 0 1";
+        assert_eq!(terms(fen, WHITE_ID).passed, 0);
+        assert_eq!(terms(fen, BLACK_ID).passed, squares(&["f3"]));
+
+        let fen = "4k3/8/8/2P5/p7/8/7P/4K3 w - - 0 1";
+        assert_eq!(terms(fen, WHITE_ID).passed, squares(&["c5", "h2"]));
+        assert_eq!(terms(fen, BLACK_ID).passed, squares(&["a4"]));
+    }
+
+    #[test]
+    fn test_passed_pawn_bonus_grows_with_rank_and_shrinks_when_blocked() {
+        let evaluator = PawnStructure::default();
+        let score = |fen: &str| evaluator.midgame_endgame(&Chessboard::from_fen(fen, " "));
+
+        let (_, far) = score("4k3/8/8/8/8/P7/8/4K3 w - - 0 1");
+        let (_, close) = score("4k3/8/P7/8/8/8/8/4K3 w - - 0 1");
+        let (_, blocked) = score("n3k3/8/P7/8/8/8/8/4K3 w - - 0 1");
+        assert!(close > far);
+        assert!(blocked < close && blocked > 0);
+
+        // Black passers count against White, mirrored.
+        let (_, black) = score("4k3/8/8/8/8/p7/8/4K3 w - - 0 1");
+        assert_eq!(black, -close);
+    }
+
+    #[test]
+    fn test_isolated_and_doubled_pawns() {
+        let fen = "4k3/p1p5/8/8/8/2P5/P1P5/4K3 w - - 0 1";
+        let white = terms(fen, WHITE_ID);
+        assert_eq!(white.isolated, squares(&["a2", "c2", "c3"]));
+        assert_eq!(white.doubled, squares(&["c2"]));
+        assert_eq!(terms(fen, BLACK_ID).doubled, 0);
+    }
+
+    #[test]
+    fn test_backward_pawns() {
+        // d3 cannot be supported any more and e5 controls d4.
+        let fen = "4k3/8/8/2P1p3/8/3P4/8/4K3 w - - 0 1";
+        assert_eq!(terms(fen, WHITE_ID).backward, squares(&["d3"]));
+
+        // Without the black pawn the advance is safe.
+        let fen = "4k3/8/8/2P5/8/3P4/8/4K3 w - - 0 1";
+        assert_eq!(terms(fen, WHITE_ID).backward, 0);
+    }
+
+    #[test]
+    fn test_connected_pawns_and_phalanxes() {
+        let fen = "4k3/5pp1/4p3/8/8/2P5/1P1P4/4K3 w - - 0 1";
+        let white = terms(fen, WHITE_ID);
+        assert_eq!(white.connected, squares(&["c3"]));
+        assert_eq!(white.phalanx, 0);
+
+        let black = terms(fen, BLACK_ID);
+        assert_eq!(black.connected, squares(&["e6"]));
+        assert_eq!(black.phalanx, squares(&["f7", "g7"]));
+    }
+
+    #[test]
+    fn test_pawn_islands() {
+        let fen = "4k3/pp1p1ppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1";
+        assert_eq!(terms(fen, WHITE_ID).islands, 1);
+        assert_eq!(terms(fen, BLACK_ID).islands, 3);
+    }
+
+    #[test]
+    fn test_candidate_passers() {
+        // b4 and c4 outnumber the lone sentry on b6.
+        let fen = "4k3/8/1p6/8/1PP5/8/8/4K3 w - - 0 1";
+        let white = terms(fen, WHITE_ID);
+        assert_eq!(white.candidates, squares(&["c4"]));
+        assert_eq!(white.passed, 0);
+
+        // With a second sentry the majority is gone.
+        let fen = "4k3/8/1p1p4/8/1PP5/8/8/4K3 w - - 0 1";
+        assert_eq!(terms(fen, WHITE_ID).candidates, 0);
+    }
+
+    #[test]
+    fn test_pawn_hash_returns_cached_entries() {
+        let evaluator = PawnStructure::new(Arc::default(), 64);
+        let board = Chessboard::from_fen("4k3/pp6/8/3P4/8/8/4PP2/4K3 w - - 0 1", " ");
+        let entry = evaluator.entry(&board);
+
+        assert_eq!(entry, PawnEntry::new(&board, &EvalParams::default()));
+        assert_eq!(evaluator.table.probe(board.pawn_key()), Some(entry));
+        assert_eq!(evaluator.table.probe(board.pawn_key() ^ 1), None);
+
+        // Moving pieces other than pawns keeps the pawn entry.
+        let moved = Chessboard::from_fen("3k4/pp6/8/3P4/8/8/4PP2/3K4 b - - 0 1", " ");
+        assert_eq!(evaluator.table.probe(moved.pawn_key()), Some(entry));
+        assert_eq!(
+            evaluator.evaluate(&board, WHITE_ID),
+            -evaluator.evaluate(&board, BLACK_ID)
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use crate::{
+    constants::WHITE_ID,
+    engine::{
+        evaluator::{ChessEvaluator, EvalTrace},
+        implementations::eval_params::{EvalParams, Term, TermScore, TermSink, TermTracer},
+    },
+    game::chessboard::Chessboard,
+};
+
+/// Game phase with every minor and major piece still on the board.
+pub const MAX_PHASE: i32 = 24;
+/// Contribution of each piece to the game phase, indexed by piece id.
+pub const PHASE_WEIGHTS: [i32; 7] = [0, 0, 1, 1, 2, 4, 0];
+
+/// Tapered evaluator based on piece values and piece-square tables.
+///
+/// Every value exists in a middlegame and an endgame flavour. The two scores are
+/// interpolated by the game phase, computed from the remaining knights, bishops, rooks and
+/// queens. Tables are written from White's point of view with `a8` first, like a diagram,
+/// and mirrored vertically for Black so that the evaluation is symmetric.
+///
+/// Values are read from [`Term::Material`] and the piece-square table terms of
+/// [`EvalParams`].
+#[derive(Debug, Clone, Default)]
+pub struct PstEvaluator {
+    params: Arc<EvalParams>,
+}
+
+/// Interpolates between a middlegame and an endgame score according to `phase`.
+pub fn taper(midgame: i32, endgame: i32, phase: i32) -> i32 {
+    (midgame * phase + endgame * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+impl PstEvaluator {
+    /// Creates an evaluator weighting pieces with `params`.
+    pub fn new(params: Arc<EvalParams>) -> Self {
+        Self { params }
+    }
+
+    /// Returns the game phase of `board`, from 0 (pawn endgame) to [`MAX_PHASE`].
+    pub fn phase(board: &Chessboard) -> i32 {
+        let phase: i32 = board
+            .get_iterator_on_pieces()
+            .map(|(_, (piece_id, _))| PHASE_WEIGHTS[piece_id as usize])
+            .sum();
+        phase.min(MAX_PHASE)
+    }
+
+    /// Adds the material and piece-square terms of `board` to `sink`.
+    pub fn terms(board: &Chessboard, sink: &mut impl TermSink) {
+        for (square, (piece_id, color_id)) in board.get_iterator_on_pieces() {
+            let square = if color_id == WHITE_ID {
+                square as usize
+            } else {
+                square as usize ^ 56
+            };
+
+            sink.add(color_id, Term::Material.index(piece_id as usize - 1), 1);
+            sink.add(color_id, Term::table(piece_id).index(square), 1);
+        }
+    }
+
+    /// Returns the middlegame and endgame scores of `board` from White's point of view.
+    pub fn midgame_endgame(&self, board: &Chessboard) -> (i32, i32) {
+        let mut score = TermScore::new(&self.params);
+        Self::terms(board, &mut score);
+        (score.midgame, score.endgame)
+    }
+}
+
+impl ChessEvaluator for PstEvaluator {
+    fn evaluate(&self, board: &Chessboard, color_id: u8) -> i32 {
+        let (midgame, endgame) = self.midgame_endgame(board);
+        let score = taper(midgame, endgame, Self::phase(board));
+
+        if color_id == WHITE_ID {
+            score
+        } else {
+            -score
+        }
+    }
+
+    fn evaluate_with_trace(&self, board: &Chessboard, color_id: u8) -> EvalTrace {
+        let mut tracer = TermTracer::new(&self.params);
+        Self::terms(board, &mut tracer);
+        tracer.into_trace(board, self.evaluate(board, color_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::{BLACK_ID, START_FEN},
+        engine::implementations::eval_params::Weight,
+    };
+
+    fn evaluate(fen: &str, color_id: u8) -> i32 {
+        PstEvaluator::default().evaluate(&Chessboard::from_fen(fen, " "), color_id)
+    }
+
+    #[test]
+    fn test_evaluation_is_symmetric_between_colours() {
+        assert_eq!(evaluate(START_FEN, WHITE_ID), 0);
+        assert_eq!(evaluate(START_FEN, BLACK_ID), 0);
+
+        // The same position with colours swapped and the board flipped.
+        let white = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        let black = "rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 4 4";
+        assert_eq!(evaluate(white, WHITE_ID), evaluate(black, BLACK_ID));
+        assert_eq!(evaluate(white, WHITE_ID), -evaluate(white, BLACK_ID));
+    }
+
+    #[test]
+    fn test_phase_tapers_towards_the_endgame() {
+        assert_eq!(
+            PstEvaluator::phase(&Chessboard::from_fen(START_FEN, " ")),
+            MAX_PHASE
+        );
+        let kings = "8/8/8/4k3/8/8/8/4K3 w - - 0 1";
+        assert_eq!(PstEvaluator::phase(&Chessboard::from_fen(kings, " ")), 0);
+
+        // Without pieces only the endgame tables count: a central king is better.
+        let central = "7k/8/8/8/3K4/8/8/8 w - - 0 1";
+        let corner = "7k/8/8/8/8/8/8/K7 w - - 0 1";
+        assert!(evaluate(central, WHITE_ID) > evaluate(corner, WHITE_ID));
+    }
+
+    #[test]
+    fn test_evaluation_follows_the_parameters() {
+        let board = Chessboard::from_fen("7k/8/8/8/3K4/8/8/8 w - - 0 1", " ");
+        let mut params = EvalParams::default();
+        for square in 0..64 {
+            params.set(Term::KingTable, square, Weight::new(10, 10));
+        }
+
+        // Both kings stand on squares worth the same.
+        assert_eq!(
+            PstEvaluator::new(Arc::new(params)).evaluate(&board, WHITE_ID),
+            0
+        );
+        assert_ne!(PstEvaluator::default().evaluate(&board, WHITE_ID), 0);
+    }
+}
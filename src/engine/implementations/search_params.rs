@@ -0,0 +1,199 @@
+use anyhow::Result;
+
+use crate::engine::options::{self, EngineOption};
+
+/// Switches and tuning values of the selective search techniques.
+///
+/// Every technique can be turned off on its own through a UCI `check` option, which makes
+/// it possible to measure each of them in SPRT matches. Margins are in centipawns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchParams {
+    /// Null-move pruning, skipped when the side to move only has pawns.
+    pub null_move: bool,
+    /// Base depth reduction of the null-move search.
+    pub null_move_reduction: u8,
+    /// Late move reductions.
+    pub lmr: bool,
+    /// Constant term of the reduction, in hundredths of a ply.
+    pub lmr_base: u32,
+    /// Divisor of `ln(depth) * ln(move number)`, in hundredths.
+    pub lmr_divisor: u32,
+    /// Principal variation search (null windows after the first move).
+    pub pvs: bool,
+    /// Aspiration windows around the previous iteration's score.
+    pub aspiration: bool,
+    /// Initial half-width of the aspiration window.
+    pub aspiration_window: u32,
+    /// Reverse futility (static null move) pruning.
+    pub reverse_futility: bool,
+    /// Margin per remaining ply of reverse futility pruning.
+    pub reverse_futility_margin: u32,
+    /// Futility pruning of quiet moves near the leaves.
+    pub futility: bool,
+    /// Margin per remaining ply of futility pruning.
+    pub futility_margin: u32,
+    /// Razoring into the quiescence search near the leaves.
+    pub razoring: bool,
+    /// Margin per remaining ply of razoring.
+    pub razor_margin: u32,
+    /// Search moves giving check one ply deeper.
+    pub check_extensions: bool,
+    /// Maximum number of plies a line may be extended by.
+    pub check_extension_limit: u8,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self {
+            null_move: true,
+            null_move_reduction: 2,
+            lmr: true,
+            lmr_base: 75,
+            lmr_divisor: 225,
+            pvs: true,
+            aspiration: true,
+            aspiration_window: 25,
+            reverse_futility: true,
+            reverse_futility_margin: 80,
+            futility: true,
+            futility_margin: 100,
+            razoring: true,
+            razor_margin: 300,
+            check_extensions: true,
+            check_extension_limit: 16,
+        }
+    }
+}
+
+impl SearchParams {
+    /// Parameters without any selective technique, as needed to prove mates.
+    pub fn exhaustive() -> Self {
+        Self {
+            null_move: false,
+            lmr: false,
+            pvs: false,
+            aspiration: false,
+            reverse_futility: false,
+            futility: false,
+            razoring: false,
+            check_extensions: false,
+            ..Self::default()
+        }
+    }
+
+    /// Lists the UCI options controlling the parameters, with their default values.
+    pub fn options() -> Vec<EngineOption> {
+        let default = Self::default();
+        vec![
+            EngineOption::check("NullMove", default.null_move),
+            EngineOption::spin(
+                "NullMoveReduction",
+                default.null_move_reduction as i64,
+                1,
+                6,
+            ),
+            EngineOption::check("LMR", default.lmr),
+            EngineOption::spin("LMRBase", default.lmr_base as i64, 0, 300),
+            EngineOption::spin("LMRDivisor", default.lmr_divisor as i64, 100, 600),
+            EngineOption::check("PVS", default.pvs),
+            EngineOption::check("Aspiration", default.aspiration),
+            EngineOption::spin("AspirationWindow", default.aspiration_window as i64, 5, 500),
+            EngineOption::check("ReverseFutility", default.reverse_futility),
+            EngineOption::spin(
+                "ReverseFutilityMargin",
+                default.reverse_futility_margin as i64,
+                0,
+                500,
+            ),
+            EngineOption::check("Futility", default.futility),
+            EngineOption::spin("FutilityMargin", default.futility_margin as i64, 0, 1000),
+            EngineOption::check("Razoring", default.razoring),
+            EngineOption::spin("RazorMargin", default.razor_margin as i64, 0, 1000),
+            EngineOption::check("CheckExtensions", default.check_extensions),
+            EngineOption::spin(
+                "CheckExtensionLimit",
+                default.check_extension_limit as i64,
+                0,
+                32,
+            ),
+        ]
+    }
+
+    /// Applies the option `name` if it is one of [`SearchParams::options`].
+    ///
+    /// Returns `Ok(false)` when `name` is not a search parameter.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<bool> {
+        let spin = |min, max| options::parse_spin(name, value, min, max);
+        let check = || options::parse_check(name, value);
+
+        match name.to_ascii_lowercase().as_str() {
+            "nullmove" => self.null_move = check()?,
+            "nullmovereduction" => self.null_move_reduction = spin(1, 6)? as u8,
+            "lmr" => self.lmr = check()?,
+            "lmrbase" => self.lmr_base = spin(0, 300)? as u32,
+            "lmrdivisor" => self.lmr_divisor = spin(100, 600)? as u32,
+            "pvs" => self.pvs = check()?,
+            "aspiration" => self.aspiration = check()?,
+            "aspirationwindow" => self.aspiration_window = spin(5, 500)? as u32,
+            "reversefutility" => self.reverse_futility = check()?,
+            "reversefutilitymargin" => self.reverse_futility_margin = spin(0, 500)? as u32,
+            "futility" => self.futility = check()?,
+            "futilitymargin" => self.futility_margin = spin(0, 1000)? as u32,
+            "razoring" => self.razoring = check()?,
+            "razormargin" => self.razor_margin = spin(0, 1000)? as u32,
+            "checkextensions" => self.check_extensions = check()?,
+            "checkextensionlimit" => self.check_extension_limit = spin(0, 32)? as u8,
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Builds the late move reduction table indexed by `[depth][move index]`.
+    pub fn lmr_table(&self) -> Box<[[u8; 64]; 64]> {
+        let mut table = Box::new([[0; 64]; 64]);
+        let base = self.lmr_base as f64 / 100.0;
+        let divisor = self.lmr_divisor as f64 / 100.0;
+
+        for (depth, row) in table.iter_mut().enumerate().skip(1) {
+            for (index, reduction) in row.iter_mut().enumerate().skip(1) {
+                let value = base + (depth as f64).ln() * (index as f64).ln() / divisor;
+                *reduction = value.max(0.0) as u8;
+            }
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_option_is_settable() {
+        let mut params = SearchParams::default();
+
+        for option in SearchParams::options() {
+            let value = match option.kind {
+                options::OptionKind::Check { default } => (!default).to_string(),
+                options::OptionKind::Spin { min, .. } => min.to_string(),
+                _ => unreachable!("search parameters are checks and spins"),
+            };
+            assert!(params.set_option(&option.name, &value).unwrap());
+        }
+
+        assert!(!params.null_move && !params.pvs && !params.check_extensions);
+        assert_eq!(params.aspiration_window, 5);
+        assert!(!params.set_option("Hash", "1").unwrap());
+        assert!(params.set_option("LMRBase", "-1").is_err());
+    }
+
+    #[test]
+    fn test_lmr_table_grows_with_depth_and_move_number() {
+        let table = SearchParams::default().lmr_table();
+
+        assert_eq!(table[1][1], 0);
+        assert!(table[10][30] > table[3][4]);
+        assert!(table[63][63] >= table[63][10]);
+    }
+}
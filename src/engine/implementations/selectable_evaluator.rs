@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    engine::{
+        evaluator::{ChessEvaluator, EvalTrace, IncrementalEval},
+        implementations::{
+            activity::ActivityEvaluator,
+            eval_params::EvalParams,
+            nnue::{NnueEvaluator, EVAL_FILE_OPTION},
+            odyn_evaluator::OdynEvaluator,
+            pawn_structure::{PawnStructure, PAWN_HASH_ENTRIES},
+            pst_evaluator::PstEvaluator,
+        },
+        options::EngineOption,
+    },
+    game::chessboard::Chessboard,
+};
+
+/// Name of the option choosing the evaluator.
+pub const EVALUATOR_OPTION: &str = "Evaluator";
+/// Name of the option loading the parameters of the tapered evaluator.
+pub const PARAMS_FILE_OPTION: &str = "ParamsFile";
+
+/// Evaluators that can be chosen with the `Evaluator` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvaluatorKind {
+    /// [`OdynEvaluator`], counting material only.
+    #[default]
+    Material,
+    /// [`PstEvaluator`], with tapered piece-square tables, plus [`PawnStructure`] and
+    /// [`ActivityEvaluator`].
+    Tapered,
+    /// [`NnueEvaluator`], with the network loaded by the `EvalFile` option.
+    Nnue,
+}
+
+impl EvaluatorKind {
+    /// Every kind, in the order listed by the `Evaluator` option.
+    pub const ALL: [EvaluatorKind; 3] = [
+        EvaluatorKind::Material,
+        EvaluatorKind::Tapered,
+        EvaluatorKind::Nnue,
+    ];
+
+    /// Returns the value of the `Evaluator` option selecting this kind.
+    pub fn name(self) -> &'static str {
+        match self {
+            EvaluatorKind::Material => "Material",
+            EvaluatorKind::Tapered => "Tapered",
+            EvaluatorKind::Nnue => "NNUE",
+        }
+    }
+
+    /// Parses an option value, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Evaluator switching between the built-in evaluators at runtime through UCI options.
+#[derive(Debug, Clone, Default)]
+pub struct SelectableEvaluator {
+    kind: EvaluatorKind,
+    material: OdynEvaluator,
+    params: Arc<EvalParams>,
+    tapered: PstEvaluator,
+    pawns: PawnStructure,
+    activity: ActivityEvaluator,
+    nnue: NnueEvaluator,
+}
+
+impl SelectableEvaluator {
+    /// Creates an evaluator using `kind`, with the default parameters.
+    pub fn new(kind: EvaluatorKind) -> Self {
+        Self {
+            kind,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the evaluator in use.
+    pub fn kind(&self) -> EvaluatorKind {
+        self.kind
+    }
+
+    /// Returns the parameters of the tapered evaluator.
+    pub fn params(&self) -> &EvalParams {
+        &self.params
+    }
+
+    /// Replaces the parameters of the tapered evaluator, emptying the pawn hash table.
+    pub fn set_params(&mut self, params: EvalParams) {
+        self.params = Arc::new(params);
+        self.tapered = PstEvaluator::new(self.params.clone());
+        self.pawns = PawnStructure::new(self.params.clone(), PAWN_HASH_ENTRIES);
+        self.activity = ActivityEvaluator::new(self.params.clone());
+    }
+}
+
+impl ChessEvaluator for SelectableEvaluator {
+    fn evaluate(&self, board: &Chessboard, color_id: u8) -> i32 {
+        match self.kind {
+            EvaluatorKind::Material => self.material.evaluate(board, color_id),
+            EvaluatorKind::Tapered => {
+                self.tapered.evaluate(board, color_id)
+                    + self.pawns.evaluate(board, color_id)
+                    + self.activity.evaluate(board, color_id)
+            }
+            EvaluatorKind::Nnue => self.nnue.evaluate(board, color_id),
+        }
+    }
+
+    fn evaluate_with_trace(&self, board: &Chessboard, color_id: u8) -> EvalTrace {
+        match self.kind {
+            EvaluatorKind::Material => self.material.evaluate_with_trace(board, color_id),
+            EvaluatorKind::Tapered => {
+                let mut trace = self.tapered.evaluate_with_trace(board, color_id);
+                trace
+                    .terms
+                    .extend(self.pawns.evaluate_with_trace(board, color_id).terms);
+                trace
+                    .terms
+                    .extend(self.activity.evaluate_with_trace(board, color_id).terms);
+                trace.score = self.evaluate(board, color_id);
+                trace
+            }
+            EvaluatorKind::Nnue => self.nnue.evaluate_with_trace(board, color_id),
+        }
+    }
+
+    fn incremental(&self) -> Option<Box<dyn IncrementalEval + '_>> {
+        match self.kind {
+            EvaluatorKind::Nnue => self.nnue.incremental(),
+            _ => None,
+        }
+    }
+
+    fn options(&self) -> Vec<EngineOption> {
+        let kinds: Vec<&str> = EvaluatorKind::ALL.iter().map(|kind| kind.name()).collect();
+        vec![
+            EngineOption::combo(EVALUATOR_OPTION, EvaluatorKind::default().name(), &kinds),
+            EngineOption::string(PARAMS_FILE_OPTION, ""),
+            EngineOption::string(EVAL_FILE_OPTION, ""),
+        ]
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<bool> {
+        if name.eq_ignore_ascii_case(EVALUATOR_OPTION) {
+            self.kind = EvaluatorKind::from_name(value)
+                .ok_or_else(|| anyhow!("option {}: unknown evaluator '{}'", name, value))?;
+            return Ok(true);
+        }
+        if name.eq_ignore_ascii_case(PARAMS_FILE_OPTION) {
+            let path = value.trim();
+            // GUIs send `<empty>` to reset a string option.
+            let params = if path.is_empty() || path == "<empty>" {
+                EvalParams::default()
+            } else {
+                EvalParams::from_file(path)?
+            };
+            self.set_params(params);
+            return Ok(true);
+        }
+
+        self.nnue.set_option(name, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::implementations::eval_params::{Term, Weight};
+
+    #[test]
+    fn test_options_select_evaluator_and_parameters() {
+        let board = Chessboard::from_fen("7k/8/8/8/3K4/8/8/8 w - - 0 1", " ");
+        let mut evaluator = SelectableEvaluator::default();
+        assert_eq!(evaluator.evaluate(&board, 1), 0);
+
+        assert!(evaluator.set_option("evaluator", "tapered").unwrap());
+        assert_eq!(evaluator.kind(), EvaluatorKind::Tapered);
+        assert_eq!(
+            evaluator.evaluate(&board, 1),
+            PstEvaluator::default().evaluate(&board, 1)
+                + ActivityEvaluator::default().evaluate(&board, 1)
+        );
+
+        let mut params = EvalParams::default();
+        for square in 0..64 {
+            params.set(Term::KingTable, square, Weight::new(10, 10));
+        }
+        let path = std::env::temp_dir().join("odyn_selectable_params.txt");
+        params.save(&path).unwrap();
+        assert!(evaluator
+            .set_option(PARAMS_FILE_OPTION, path.to_str().unwrap())
+            .unwrap());
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(evaluator.params(), &params);
+        assert_eq!(evaluator.evaluate(&board, 1), 0);
+
+        assert!(evaluator.set_option(EVALUATOR_OPTION, "NNUE").unwrap());
+        assert_eq!(evaluator.kind(), EvaluatorKind::Nnue);
+        assert!(evaluator.incremental().is_some());
+        assert!(evaluator.set_option(EVALUATOR_OPTION, "Neural").is_err());
+        assert!(evaluator
+            .set_option(PARAMS_FILE_OPTION, "/no/such/file")
+            .is_err());
+        assert!(!evaluator.set_option("Hash", "16").unwrap());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{
+    constants::{PAWN_ID, PIECE_VALUES},
+    engine::search::Score,
+    game::chess_move::Move,
+};
+
+/// UCI name of the strength option, from 0 (weakest) to [`MAX_SKILL_LEVEL`] (full
+/// strength).
+pub const SKILL_LEVEL_OPTION: &str = "Skill Level";
+pub const MAX_SKILL_LEVEL: i64 = 20;
+/// UCI name of the switch making the engine play at the rating of [`ELO_OPTION`].
+pub const LIMIT_STRENGTH_OPTION: &str = "UCI_LimitStrength";
+/// UCI name of the target rating used when [`LIMIT_STRENGTH_OPTION`] is set.
+pub const ELO_OPTION: &str = "UCI_Elo";
+/// Ratings mapped to skill levels 0 and [`MAX_SKILL_LEVEL`].
+pub const MIN_ELO: i64 = 1000;
+pub const MAX_ELO: i64 = 2800;
+pub const DEFAULT_ELO: i64 = 1500;
+/// UCI name of the seed of the random move choices, `0` seeding from the system.
+pub const SKILL_SEED_OPTION: &str = "Skill Seed";
+pub const MAX_SKILL_SEED: i64 = u32::MAX as i64;
+
+/// Number of lines searched to choose among when the strength is limited.
+pub const SKILL_MULTIPV: usize = 4;
+/// Nodes searched at level 0, doubled every two levels.
+const MIN_NODES: f64 = 1000.0;
+/// Chance of a deliberate inaccuracy at level 0, decreasing linearly to none at full
+/// strength.
+const MAX_INACCURACY_CHANCE: f64 = 0.15;
+/// Largest loss, in centipawns, of a deliberate inaccuracy at full strength, and its
+/// increase per level below.
+const BASE_INACCURACY: i32 = 50;
+const INACCURACY_PER_LEVEL: f64 = 20.0;
+
+/// Strength limit derived from the `Skill Level` or `UCI_Elo` options.
+///
+/// A limited engine searches shallower and fewer nodes, looks at [`SKILL_MULTIPV`] lines
+/// and then picks one of them with [`Skill::pick`], so that weaker levels play worse
+/// moves more often.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Skill {
+    /// Fractional level in `0..MAX_SKILL_LEVEL`.
+    level: f64,
+}
+
+impl Skill {
+    /// Returns the limit of a `Skill Level`, or `None` at full strength.
+    pub fn from_level(level: i64) -> Option<Self> {
+        (level < MAX_SKILL_LEVEL).then(|| Self {
+            level: level.max(0) as f64,
+        })
+    }
+
+    /// Returns the limit playing at about `elo`, interpolating the level linearly between
+    /// [`MIN_ELO`] and [`MAX_ELO`].
+    pub fn from_elo(elo: i64) -> Self {
+        let elo = elo.clamp(MIN_ELO, MAX_ELO);
+        let level = (elo - MIN_ELO) as f64 * MAX_SKILL_LEVEL as f64 / (MAX_ELO - MIN_ELO) as f64;
+        // The top rating still plays a little below full strength.
+        Self {
+            level: level.min(MAX_SKILL_LEVEL as f64 - 0.1),
+        }
+    }
+
+    /// Returns the fractional skill level.
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Returns the deepest iteration to search, from 1 at level 0 to 10.
+    pub fn max_depth(&self) -> u8 {
+        1 + (self.level / 2.0) as u8
+    }
+
+    /// Returns the nodes to search, from 1000 at level 0 to about a million.
+    pub fn max_nodes(&self) -> u64 {
+        (MIN_NODES * 2f64.powf(self.level / 2.0)) as u64
+    }
+
+    /// Returns the largest loss in centipawns of a deliberate inaccuracy.
+    pub fn max_inaccuracy(&self) -> i32 {
+        BASE_INACCURACY + ((MAX_SKILL_LEVEL as f64 - self.level) * INACCURACY_PER_LEVEL) as i32
+    }
+
+    /// Picks the index of the line to play among `lines`, ranked best first.
+    ///
+    /// Every line gets a bonus growing with the weakness of the level: part of its loss
+    /// against the best line is forgiven and a random amount up to a pawn (or the spread
+    /// of the scores when smaller) is added, as in Stockfish. Besides, the engine
+    /// occasionally plays a random line losing at most [`Skill::max_inaccuracy`].
+    pub fn pick(&self, lines: &[(Move, Score, Vec<Move>)], rng: &mut fastrand::Rng) -> usize {
+        let Some((_, top, _)) = lines.first() else {
+            return 0;
+        };
+        let values: Vec<i32> = lines.iter().map(|(_, score, _)| score.to_value()).collect();
+        let top = top.to_value();
+        let missing = 1.0 - self.level / MAX_SKILL_LEVEL as f64;
+
+        if rng.f64() < MAX_INACCURACY_CHANCE * missing {
+            let playable = values
+                .iter()
+                .take_while(|&&value| top - value <= self.max_inaccuracy())
+                .count();
+            return rng.usize(..playable);
+        }
+
+        let weakness = 120.0 - 2.0 * self.level;
+        let delta = (top - values[values.len() - 1]).min(PIECE_VALUES[PAWN_ID as usize]) as f64;
+        let mut best = (0, f64::MIN);
+        for (index, &value) in values.iter().enumerate() {
+            let push = (weakness * (top - value) as f64 + delta * weakness * rng.f64()) / 128.0;
+            let value = value as f64 + push;
+            if value > best.1 {
+                best = (index, value);
+            }
+        }
+        best.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(values: &[i32]) -> Vec<(Move, Score, Vec<Move>)> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| {
+                let move_ = Move::new_no_promotion(index as u32, 63);
+                (move_, Score::Centipawns(value), vec![move_])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_limits_grow_with_elo() {
+        assert_eq!(Skill::from_level(MAX_SKILL_LEVEL), None);
+        let weakest = Skill::from_level(0).unwrap();
+        assert_eq!(Skill::from_elo(MIN_ELO - 500), weakest);
+        assert_eq!(weakest.max_depth(), 1);
+        assert_eq!(weakest.max_nodes(), 1000);
+
+        let skills: Vec<Skill> = (MIN_ELO..=MAX_ELO)
+            .step_by(100)
+            .map(Skill::from_elo)
+            .collect();
+        assert!(skills.windows(2).all(|pair| {
+            pair[0].level() < pair[1].level()
+                && pair[0].max_depth() <= pair[1].max_depth()
+                && pair[0].max_nodes() < pair[1].max_nodes()
+                && pair[0].max_inaccuracy() > pair[1].max_inaccuracy()
+        }));
+        assert!(skills.last().unwrap().level() < MAX_SKILL_LEVEL as f64);
+    }
+
+    #[test]
+    fn test_weaker_levels_pick_worse_moves_more_often() {
+        let lines = lines(&[50, 20, -40, -600]);
+        let picks = |level: i64, seed: u64| -> Vec<usize> {
+            let skill = Skill::from_level(level).unwrap();
+            let mut rng = fastrand::Rng::with_seed(seed);
+            (0..1000).map(|_| skill.pick(&lines, &mut rng)).collect()
+        };
+
+        // The same seed replays the same choices.
+        assert_eq!(picks(5, 7), picks(5, 7));
+
+        let best_picks = |level| picks(level, 1).iter().filter(|&&index| index == 0).count();
+        let (weak, strong) = (best_picks(0), best_picks(19));
+        assert!(
+            weak < strong,
+            "{} best picks at level 0, {} at 19",
+            weak,
+            strong
+        );
+        // The hanging piece is out of reach of the deliberate inaccuracies of level 19.
+        assert!(!picks(19, 1).contains(&3));
+    }
+}
@@ -0,0 +1,956 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    constants::{self, BISHOP_ID, KING_ID, KNIGHT_ID, PAWN_ID, QUEEN_ID, ROOK_ID, WHITE_ID},
+    engine::search::{MATE_BOUND, MATE_VALUE},
+    game::{
+        chess_move::Move,
+        chessboard::Chessboard,
+        move_generator::{
+            generate_bishop_moves, generate_king_moves, generate_knight_moves,
+            generate_queen_moves, generate_rook_moves,
+        },
+        utility::{index_to_square, iter_bits, square_to_index},
+    },
+};
+
+/// Name of the option giving the directory the tablebases are loaded from.
+pub const TABLEBASE_PATH_OPTION: &str = "TablebasePath";
+/// Extension of the tablebase files.
+pub const EXTENSION: &str = "odtb";
+/// Endings generated by `tbgen` when none is given.
+pub const DEFAULT_MATERIALS: [&str; 5] = ["KQvK", "KRvK", "KBNvK", "KRvKB", "KQvKR"];
+/// Most pieces, kings included, in a generated ending.
+pub const MAX_PIECES: usize = 4;
+
+/// First bytes of a tablebase file.
+const MAGIC: &[u8; 8] = b"ODYNTB01";
+/// Squares the first White king is brought to by symmetry in stored tables: `a1`, `b1`,
+/// `c1`, `d1`, `b2`, `c2`, `d2`, `c3`, `d3` and `d4`.
+const TRIANGLE: [(u32, u32); 10] = [
+    (7, 0),
+    (7, 1),
+    (7, 2),
+    (7, 3),
+    (6, 1),
+    (6, 2),
+    (6, 3),
+    (5, 2),
+    (5, 3),
+    (4, 3),
+];
+/// Counter of the positions that can never be lost: draws by capture or stalemate, and
+/// illegal positions.
+const ESCAPE: u8 = u8::MAX;
+/// Byte starting a run of identical entries in tablebase files, above every value.
+const RUN: u8 = u8::MAX;
+/// Shortest run of identical entries worth encoding as a run.
+const MIN_RUN: usize = 4;
+
+/// Pieces of a pawnless ending, White first and by decreasing value within each side.
+///
+/// Endings are named like `KRvKB`: the White pieces, `v`, then the Black pieces.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Material {
+    /// `(piece_id, color_id)` pairs.
+    pieces: Vec<(u8, u8)>,
+}
+
+impl Material {
+    fn new(mut pieces: Vec<(u8, u8)>) -> Self {
+        pieces.sort_by_key(|&(piece_id, color_id)| (color_id != WHITE_ID, u8::MAX - piece_id));
+        Self { pieces }
+    }
+
+    /// Returns the material on `board`, or `None` if it has pawns or more than
+    /// [`MAX_PIECES`] pieces.
+    pub fn from_board(board: &Chessboard) -> Option<Self> {
+        let pieces: Vec<(u8, u8)> = board
+            .get_iterator_on_pieces()
+            .map(|(_, piece)| piece)
+            .collect();
+        if pieces.len() > MAX_PIECES || pieces.iter().any(|&(piece_id, _)| piece_id == PAWN_ID) {
+            return None;
+        }
+        Some(Self::new(pieces))
+    }
+
+    /// Returns the `(piece_id, color_id)` pairs, in index order.
+    pub fn pieces(&self) -> &[(u8, u8)] {
+        &self.pieces
+    }
+
+    /// Returns the same ending with colours swapped.
+    pub fn swapped(&self) -> Self {
+        Self::new(
+            self.pieces
+                .iter()
+                .map(|&(piece_id, color_id)| (piece_id, constants::opposite(color_id)))
+                .collect(),
+        )
+    }
+
+    /// Returns whether neither side can ever mate: bare kings, or a single minor piece.
+    pub fn is_dead_draw(&self) -> bool {
+        let extra: Vec<u8> = self
+            .pieces
+            .iter()
+            .map(|&(piece_id, _)| piece_id)
+            .filter(|&piece_id| piece_id != KING_ID)
+            .collect();
+        match extra[..] {
+            [] => true,
+            [piece_id] => piece_id == BISHOP_ID || piece_id == KNIGHT_ID,
+            _ => false,
+        }
+    }
+
+    /// Returns the ending left after capturing the piece at `index`.
+    fn without(&self, index: usize) -> Self {
+        let mut pieces = self.pieces.clone();
+        pieces.remove(index);
+        Self::new(pieces)
+    }
+}
+
+impl Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for color_id in [WHITE_ID, constants::BLACK_ID] {
+            if color_id != WHITE_ID {
+                write!(f, "v")?;
+            }
+            for &(piece_id, _) in self.pieces.iter().filter(|piece| piece.1 == color_id) {
+                write!(f, "{}", piece_letter(piece_id))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Material {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (white, black) = s
+            .trim()
+            .split_once(['v', 'V'])
+            .ok_or_else(|| anyhow!("ending '{}' should look like KRvK", s))?;
+
+        let mut pieces = Vec::new();
+        for (side, color_id) in [(white, WHITE_ID), (black, constants::BLACK_ID)] {
+            for letter in side.chars() {
+                let piece_id = match letter.to_ascii_uppercase() {
+                    'K' => KING_ID,
+                    'Q' => QUEEN_ID,
+                    'R' => ROOK_ID,
+                    'B' => BISHOP_ID,
+                    'N' => KNIGHT_ID,
+                    _ => return Err(anyhow!("unsupported piece '{}' in ending '{}'", letter, s)),
+                };
+                pieces.push((piece_id, color_id));
+            }
+            let kings = side
+                .chars()
+                .filter(|letter| letter.eq_ignore_ascii_case(&'K'))
+                .count();
+            if kings != 1 {
+                return Err(anyhow!("each side of ending '{}' needs one king", s));
+            }
+        }
+        if pieces.len() > MAX_PIECES {
+            return Err(anyhow!(
+                "ending '{}' has more than {} pieces",
+                s,
+                MAX_PIECES
+            ));
+        }
+
+        Ok(Self::new(pieces))
+    }
+}
+
+fn piece_letter(piece_id: u8) -> char {
+    match piece_id {
+        KING_ID => 'K',
+        QUEEN_ID => 'Q',
+        ROOK_ID => 'R',
+        BISHOP_ID => 'B',
+        _ => 'N',
+    }
+}
+
+/// Squares attacked by a `piece_id` on `square`.
+fn attacks(piece_id: u8, square: u32, occupancy: u64) -> u64 {
+    let board = 1 << square;
+    match piece_id {
+        KING_ID => generate_king_moves(board) & !board,
+        QUEEN_ID => generate_queen_moves(board, occupancy),
+        ROOK_ID => generate_rook_moves(board, occupancy),
+        BISHOP_ID => generate_bishop_moves(board, occupancy),
+        _ => generate_knight_moves(board),
+    }
+}
+
+/// Placement of the pieces of a [`Material`], in the same order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Placement {
+    white_to_move: bool,
+    squares: [u32; MAX_PIECES],
+}
+
+impl Placement {
+    fn occupancy(&self, count: usize) -> u64 {
+        self.squares[..count]
+            .iter()
+            .fold(0, |occupancy, square| occupancy | 1 << square)
+    }
+
+    /// Whether a piece of `color_id` attacks `square`.
+    fn attacked(&self, pieces: &[(u8, u8)], square: u32, color_id: u8) -> bool {
+        let occupancy = self.occupancy(pieces.len());
+        pieces
+            .iter()
+            .zip(self.squares)
+            .any(|(&(piece_id, piece_color), from)| {
+                piece_color == color_id && attacks(piece_id, from, occupancy) & 1 << square != 0
+            })
+    }
+
+    fn king(&self, pieces: &[(u8, u8)], color_id: u8) -> u32 {
+        let index = pieces
+            .iter()
+            .position(|&piece| piece == (KING_ID, color_id))
+            .unwrap();
+        self.squares[index]
+    }
+
+    fn side_to_move(&self) -> u8 {
+        if self.white_to_move {
+            WHITE_ID
+        } else {
+            constants::BLACK_ID
+        }
+    }
+
+    /// Whether the pieces stand on distinct squares and the side that just moved is not
+    /// in check.
+    fn is_legal(&self, pieces: &[(u8, u8)]) -> bool {
+        let occupancy = self.occupancy(pieces.len());
+        if occupancy.count_ones() as usize != pieces.len() {
+            return false;
+        }
+        let mover = constants::opposite(self.side_to_move());
+        !self.attacked(pieces, self.king(pieces, mover), self.side_to_move())
+    }
+}
+
+/// Value of a position in a table: 0 for draws, `plies + 1` otherwise, where the side to
+/// move mates in an odd number of plies and is mated in an even one.
+fn to_score(value: u8) -> i32 {
+    let plies = value as i32 - 1;
+    match value {
+        0 => 0,
+        _ if plies % 2 == 1 => MATE_VALUE - plies,
+        _ => -MATE_VALUE + plies,
+    }
+}
+
+/// Distance-to-mate table of one ending, generated in memory over every placement.
+struct FullTable {
+    values: Vec<u8>,
+}
+
+impl FullTable {
+    fn index(placement: &Placement, count: usize) -> usize {
+        let squares = placement.squares[..count]
+            .iter()
+            .rev()
+            .fold(0, |index, &square| index * 64 + square as usize);
+        usize::from(placement.white_to_move) + 2 * squares
+    }
+
+    fn placement(index: usize, count: usize) -> Placement {
+        let mut squares = [0; MAX_PIECES];
+        let mut rest = index / 2;
+        for square in squares.iter_mut().take(count) {
+            *square = (rest % 64) as u32;
+            rest /= 64;
+        }
+        Placement {
+            white_to_move: index % 2 == 1,
+            squares,
+        }
+    }
+}
+
+/// Generates the distance-to-mate table of `material` and of every ending reachable from
+/// it by captures.
+///
+/// The generator works backwards from the checkmates. Positions where the side to move is
+/// mated are lost in 0 plies. A position is won in `n + 1` plies once one of its moves
+/// reaches a position lost in `n` plies, and lost in `n + 1` plies once all of its moves
+/// reach positions won in at most `n` plies. Captures lead to smaller endings, generated
+/// first. Positions left unresolved are draws.
+pub fn generate(material: &Material) -> Result<Table> {
+    if material
+        .pieces
+        .iter()
+        .any(|&(piece_id, _)| piece_id == PAWN_ID)
+        || material.pieces.len() > MAX_PIECES
+    {
+        return Err(anyhow!("cannot generate ending {}", material));
+    }
+
+    let mut tables = HashMap::new();
+    generate_full(material, &mut tables);
+    Ok(Table::from_full(material.clone(), &tables[material]))
+}
+
+fn generate_full(material: &Material, tables: &mut HashMap<Material, FullTable>) {
+    if tables.contains_key(material) {
+        return;
+    }
+    let pieces = material.pieces();
+    let count = pieces.len();
+    for (index, &(piece_id, _)) in pieces.iter().enumerate() {
+        if piece_id != KING_ID {
+            generate_full(&material.without(index), tables);
+        }
+    }
+
+    let size = 2 << (6 * count);
+    let mut values = vec![0u8; size];
+    let mut counters = vec![ESCAPE; size];
+    let mut capture_losses = vec![0u8; size];
+    // Positions to resolve at each ply, queued ahead of time by captures.
+    let mut resolved: Vec<Vec<usize>> = vec![Vec::new(); usize::from(RUN)];
+
+    for index in 0..size {
+        let placement = FullTable::placement(index, count);
+        if !placement.is_legal(pieces) {
+            continue;
+        }
+
+        let color_id = placement.side_to_move();
+        let occupancy = placement.occupancy(count);
+        let (mut quiet, mut escape, mut legal) = (0, false, false);
+        let mut capture_win = u8::MAX;
+        let mut capture_loss = 0;
+
+        for (mover, &(piece_id, piece_color)) in pieces.iter().enumerate() {
+            if piece_color != color_id {
+                continue;
+            }
+            let own = placement.squares[..count]
+                .iter()
+                .zip(pieces)
+                .filter(|(_, piece)| piece.1 == color_id)
+                .fold(0u64, |own, (square, _)| own | 1 << square);
+            for target in iter_bits(attacks(piece_id, placement.squares[mover], occupancy) & !own) {
+                let mut next = placement;
+                next.white_to_move = !placement.white_to_move;
+                next.squares[mover] = target;
+
+                let captured =
+                    (0..count).find(|&other| other != mover && placement.squares[other] == target);
+                let Some(captured) = captured else {
+                    if next.is_legal(pieces) {
+                        quiet += 1;
+                        legal = true;
+                    }
+                    continue;
+                };
+
+                let smaller = material.without(captured);
+                let mut squares = [0; MAX_PIECES];
+                let mut remaining: Vec<(u8, u8, u32)> = (0..count)
+                    .filter(|&other| other != captured)
+                    .map(|other| (pieces[other].0, pieces[other].1, next.squares[other]))
+                    .collect();
+                for (slot, piece) in squares.iter_mut().zip(smaller.pieces()) {
+                    let found = remaining
+                        .iter()
+                        .position(|&(piece_id, color_id, _)| (piece_id, color_id) == *piece)
+                        .unwrap();
+                    *slot = remaining.remove(found).2;
+                }
+                let next = Placement {
+                    white_to_move: next.white_to_move,
+                    squares,
+                };
+                if !next.is_legal(smaller.pieces()) {
+                    continue;
+                }
+                legal = true;
+
+                let value = tables[&smaller].values[FullTable::index(&next, count - 1)];
+                match value {
+                    0 => escape = true,
+                    // The opponent is mated in `value - 1` plies: the capture mates in `value`.
+                    _ if value % 2 == 1 => capture_win = capture_win.min(value),
+                    _ => capture_loss = capture_loss.max(value),
+                }
+            }
+        }
+
+        if !legal {
+            let king = placement.king(pieces, color_id);
+            if placement.attacked(pieces, king, constants::opposite(color_id)) {
+                resolved[0].push(index);
+            }
+            continue;
+        }
+
+        capture_losses[index] = capture_loss;
+        if capture_win != u8::MAX {
+            resolved[capture_win as usize].push(index);
+        } else if !escape {
+            counters[index] = quiet;
+            if quiet == 0 {
+                resolved[capture_loss as usize].push(index);
+            }
+        }
+    }
+
+    for plies in 0..resolved.len() {
+        // Positions can be queued several times, or resolved faster since.
+        let current: Vec<usize> = std::mem::take(&mut resolved[plies])
+            .into_iter()
+            .filter(|&index| {
+                let fresh = values[index] == 0;
+                if fresh {
+                    values[index] = plies as u8 + 1;
+                }
+                fresh
+            })
+            .collect();
+        if !current.is_empty() {
+            assert!(
+                plies + 1 < resolved.len(),
+                "mates of {} are too long",
+                material
+            );
+        }
+
+        for &index in &current {
+            let placement = FullTable::placement(index, count);
+            // The opponent of the side to move made the last move.
+            let mover_color = constants::opposite(placement.side_to_move());
+            let occupancy = placement.occupancy(count);
+
+            for (mover, &(piece_id, piece_color)) in pieces.iter().enumerate() {
+                if piece_color != mover_color {
+                    continue;
+                }
+                for origin in
+                    iter_bits(attacks(piece_id, placement.squares[mover], occupancy) & !occupancy)
+                {
+                    let mut previous = placement;
+                    previous.white_to_move = !placement.white_to_move;
+                    previous.squares[mover] = origin;
+                    if !previous.is_legal(pieces) {
+                        continue;
+                    }
+
+                    let previous = FullTable::index(&previous, count);
+                    if values[previous] != 0 {
+                        continue;
+                    }
+                    if plies % 2 == 0 {
+                        // A move reaches a position lost for the opponent.
+                        resolved[plies + 1].push(previous);
+                    } else if counters[previous] != ESCAPE {
+                        counters[previous] -= 1;
+                        if counters[previous] == 0 {
+                            let lost = (plies + 1).max(capture_losses[previous] as usize);
+                            resolved[lost].push(previous);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    tables.insert(material.clone(), FullTable { values });
+}
+
+/// Distance-to-mate table of one ending, as stored on disk.
+///
+/// Only the placements with the first White king on one of the 10 squares of the
+/// `a1`-`d1`-`d4` triangle are kept: the others are reached by mirroring the board, which
+/// does not change the distance to mate without pawns.
+///
+/// # File format
+///
+/// Files start with the 8 bytes `ODYNTB01`, the length of the name of the ending in one
+/// byte, the name (for example `KRvK`) and the number of entries as a little-endian u32.
+/// The entries follow, one byte each, except that runs of identical entries are encoded
+/// as the byte 255, the entry and the LEB128 length of the run. Entries are indexed by `white_to_move + 2 * (t + 10 * s)`, where `t` is the
+/// index of the first White king in the triangle and `s` the other squares in base 64,
+/// the second piece being the least significant digit. Pieces are ordered White first,
+/// by decreasing value. Values are 0 for draws and illegal positions, and `plies + 1`
+/// otherwise, where the side to move mates in an odd number of plies and is mated in an
+/// even one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    material: Material,
+    values: Vec<u8>,
+}
+
+impl Table {
+    fn entries(count: usize) -> usize {
+        (2 * TRIANGLE.len()) << (6 * (count - 1))
+    }
+
+    fn from_full(material: Material, full: &FullTable) -> Self {
+        let count = material.pieces.len();
+        let mut values = vec![0; Self::entries(count)];
+        let mut previous = 0;
+        for (index, value) in values.iter_mut().enumerate() {
+            let placement = Self::placement(index, count);
+            // Illegal positions are never probed: extend the current run instead.
+            if placement.is_legal(material.pieces()) {
+                previous = full.values[FullTable::index(&placement, count)];
+            }
+            *value = previous;
+        }
+        Self { material, values }
+    }
+
+    fn placement(index: usize, count: usize) -> Placement {
+        let mut squares = [0; MAX_PIECES];
+        let (row, col) = TRIANGLE[index / 2 % TRIANGLE.len()];
+        squares[0] = square_to_index(row, col);
+        let mut rest = index / 2 / TRIANGLE.len();
+        for square in squares.iter_mut().take(count).skip(1) {
+            *square = (rest % 64) as u32;
+            rest /= 64;
+        }
+        Placement {
+            white_to_move: index % 2 == 1,
+            squares,
+        }
+    }
+
+    /// Returns the ending of the table.
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Returns the value of `placement`, mirrored to bring the first White king into the
+    /// triangle.
+    fn value(&self, placement: &Placement) -> u8 {
+        let count = self.material.pieces.len();
+        for symmetry in 0..8 {
+            let squares = placement.squares.map(|square| mirror(square, symmetry));
+            let (row, col) = index_to_square(squares[0]);
+            let Some(triangle) = TRIANGLE.iter().position(|&corner| corner == (row, col)) else {
+                continue;
+            };
+            let rest = squares[1..count]
+                .iter()
+                .rev()
+                .fold(0, |index, &square| index * 64 + square as usize);
+            let index = usize::from(placement.white_to_move) + 2 * (triangle + 10 * rest);
+            return self.values[index];
+        }
+        unreachable!("every square mirrors into the triangle")
+    }
+
+    /// Returns the longest forced mate in the table, in plies.
+    pub fn max_dtm(&self) -> u32 {
+        let wins = self
+            .values
+            .iter()
+            .filter(|&&value| value % 2 == 0 && value > 0);
+        wins.max().map_or(0, |&value| value as u32 - 1)
+    }
+
+    /// Returns the number of positions won and lost by the side to move.
+    pub fn counts(&self) -> (usize, usize) {
+        let won = self
+            .values
+            .iter()
+            .filter(|&&value| value % 2 == 0 && value > 0);
+        let lost = self.values.iter().filter(|&&value| value % 2 == 1);
+        (won.count(), lost.count())
+    }
+
+    /// Reads a table in the format described on [`Table`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes =
+            fs::read(path).with_context(|| format!("cannot read tablebase {}", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("invalid tablebase {}", path.display()))
+    }
+
+    /// Writes the table to `path` in the format described on [`Table`].
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes())
+            .with_context(|| format!("cannot write tablebase {}", path.display()))
+    }
+
+    /// Parses a table in the format described on [`Table`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let rest = bytes
+            .strip_prefix(MAGIC.as_slice())
+            .ok_or_else(|| anyhow!("missing {} header", String::from_utf8_lossy(MAGIC)))?;
+        let (&length, rest) = rest
+            .split_first()
+            .ok_or_else(|| anyhow!("missing ending name"))?;
+        if rest.len() < length as usize + 4 {
+            return Err(anyhow!("truncated header"));
+        }
+        let (name, rest) = rest.split_at(length as usize);
+        let material: Material = std::str::from_utf8(name)?.parse()?;
+        let (entries, mut rest) = rest.split_at(4);
+        let entries = u32::from_le_bytes(entries.try_into().unwrap()) as usize;
+        if entries != Self::entries(material.pieces.len()) {
+            return Err(anyhow!("{} entries for ending {}", entries, material));
+        }
+
+        let mut values = Vec::with_capacity(entries);
+        while let Some((&value, mut tail)) = rest.split_first() {
+            let (value, run) = if value == RUN {
+                let (&value, run_tail) =
+                    tail.split_first().ok_or_else(|| anyhow!("truncated run"))?;
+                let (run, run_tail) = read_leb128(run_tail)?;
+                tail = run_tail;
+                (value, run)
+            } else {
+                (value, 1)
+            };
+            if values.len() + run > entries {
+                return Err(anyhow!("more than {} entries", entries));
+            }
+            values.resize(values.len() + run, value);
+            rest = tail;
+        }
+        if values.len() != entries {
+            return Err(anyhow!(
+                "expected {} entries, found {}",
+                entries,
+                values.len()
+            ));
+        }
+
+        Ok(Self { material, values })
+    }
+
+    /// Serializes the table in the format described on [`Table`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.material.to_string();
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&(self.values.len() as u32).to_le_bytes());
+
+        for run in self.values.chunk_by(|a, b| a == b) {
+            if run.len() < MIN_RUN {
+                bytes.extend_from_slice(run);
+                continue;
+            }
+            bytes.extend_from_slice(&[RUN, run[0]]);
+            let mut length = run.len();
+            while length >= 0x80 {
+                bytes.push((length & 0x7f) as u8 | 0x80);
+                length >>= 7;
+            }
+            bytes.push(length as u8);
+        }
+        bytes
+    }
+}
+
+fn read_leb128(bytes: &[u8]) -> Result<(usize, &[u8])> {
+    let mut value = 0usize;
+    for (index, &byte) in bytes.iter().enumerate().take(5) {
+        value |= ((byte & 0x7f) as usize) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok((value, &bytes[index + 1..]));
+        }
+    }
+    Err(anyhow!("invalid run length"))
+}
+
+/// Applies one of the 8 symmetries of the board to `square`: bit 0 mirrors files, bit 1
+/// ranks and bit 2 the `a1`-`h8` diagonal.
+fn mirror(square: u32, symmetry: u32) -> u32 {
+    let (mut row, mut col) = index_to_square(square);
+    if symmetry & 1 != 0 {
+        col = 7 - col;
+    }
+    if symmetry & 2 != 0 {
+        row = 7 - row;
+    }
+    if symmetry & 4 != 0 {
+        (row, col) = (7 - col, 7 - row);
+    }
+    square_to_index(row, col)
+}
+
+/// Distance-to-mate tablebases loaded from disk.
+#[derive(Debug, Clone, Default)]
+pub struct Tablebases {
+    tables: HashMap<Material, Table>,
+}
+
+impl Tablebases {
+    /// Creates an empty set of tablebases.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every `.odtb` file of `directory`.
+    pub fn load_dir(directory: impl AsRef<Path>) -> Result<Self> {
+        let directory = directory.as_ref();
+        let mut tablebases = Self::new();
+        let entries = fs::read_dir(directory)
+            .with_context(|| format!("cannot list tablebases in {}", directory.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                tablebases.insert(Table::from_file(&path)?);
+            }
+        }
+        Ok(tablebases)
+    }
+
+    /// Adds `table`, replacing the table of the same ending.
+    pub fn insert(&mut self, table: Table) {
+        self.tables.insert(table.material.clone(), table);
+    }
+
+    /// Returns the number of endings available.
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Returns whether no ending is available.
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Returns the exact score of `board` for the side to move, or `None` if its ending is
+    /// not available.
+    ///
+    /// Scores follow the search's convention: `MATE_VALUE - n` when the side to move mates
+    /// in `n` plies, `-MATE_VALUE + n` when it is mated in `n` plies, and 0 for draws.
+    /// Positions with castling rights are not covered, and the 50-move rule is ignored.
+    pub fn probe_dtm(&self, board: &Chessboard) -> Option<i32> {
+        if self.tables.is_empty()
+            || board.occupancy().count_ones() as usize > MAX_PIECES
+            || board.bitboard().flags & 0b1111 != 0
+        {
+            return None;
+        }
+        let material = Material::from_board(board)?;
+        if material.is_dead_draw() {
+            return Some(0);
+        }
+
+        let (table, swap) = match self.tables.get(&material) {
+            Some(table) => (table, false),
+            None => (self.tables.get(&material.swapped())?, true),
+        };
+
+        let mut remaining: Vec<(u32, (u8, u8))> = board.get_iterator_on_pieces().collect();
+        let mut squares = [0; MAX_PIECES];
+        for (slot, &(piece_id, color_id)) in squares.iter_mut().zip(table.material.pieces()) {
+            let color_id = if swap {
+                constants::opposite(color_id)
+            } else {
+                color_id
+            };
+            let found = remaining
+                .iter()
+                .position(|&(_, piece)| piece == (piece_id, color_id))?;
+            *slot = remaining.remove(found).0;
+        }
+        let white_to_move = (board.current_turn() == WHITE_ID) != swap;
+
+        Some(to_score(table.value(&Placement {
+            white_to_move,
+            squares,
+        })))
+    }
+
+    /// Returns the best move on `board` with its score, or `None` if the endings of some
+    /// moves are not available.
+    ///
+    /// Winning sides mate as fast as possible and losing sides resist as long as possible.
+    pub fn root_move(&self, board: &Chessboard) -> Option<(Move, i32)> {
+        let mut best: Option<(Move, i32)> = None;
+        for move_ in board.legal_moves_vec(board.current_turn()) {
+            let mut next = board.clone();
+            next.make_move_unchecked(move_);
+            let child = self.probe_dtm(&next)?;
+            // The child is one ply further from the root.
+            let score = -match child {
+                _ if child >= MATE_BOUND => child - 1,
+                _ if child <= -MATE_BOUND => child + 1,
+                _ => child,
+            };
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((move_, score));
+            }
+        }
+        best
+    }
+
+    /// Returns the line of best moves from `board`, up to mate or `max_plies` moves.
+    /// Drawn lines stop after their first move.
+    pub fn best_line(&self, board: &Chessboard, max_plies: usize) -> Vec<Move> {
+        let mut line = Vec::new();
+        let mut board = board.clone();
+        while line.len() < max_plies {
+            let Some((move_, score)) = self.root_move(&board) else {
+                break;
+            };
+            line.push(move_);
+            if score.abs() < MATE_BOUND {
+                break;
+            }
+            board.make_move_unchecked(move_);
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tablebases(materials: &[&str]) -> Tablebases {
+        let mut tablebases = Tablebases::new();
+        for material in materials {
+            tablebases.insert(generate(&material.parse().unwrap()).unwrap());
+        }
+        tablebases
+    }
+
+    #[test]
+    fn test_material_names() {
+        let material: Material = "KRvKB".parse().unwrap();
+        assert_eq!(material.to_string(), "KRvKB");
+        assert_eq!(material.swapped().to_string(), "KBvKR");
+        assert_eq!("knbvk".parse::<Material>().unwrap().to_string(), "KBNvK");
+        assert!("KRK".parse::<Material>().is_err());
+        assert!("KPvK".parse::<Material>().is_err());
+        assert!("KRRvKR".parse::<Material>().is_err());
+        assert!("RvK".parse::<Material>().is_err());
+
+        let board = Chessboard::from_fen("8/8/8/3k4/8/8/8/R3K3 w - - 0 1", " ");
+        assert_eq!(Material::from_board(&board).unwrap().to_string(), "KRvK");
+        let board = Chessboard::from_fen(constants::START_FEN, " ");
+        assert_eq!(Material::from_board(&board), None);
+    }
+
+    #[test]
+    fn test_longest_mates() {
+        let queen = generate(&"KQvK".parse().unwrap()).unwrap();
+        let rook = generate(&"KRvK".parse().unwrap()).unwrap();
+        // Mates in 10 and 16 moves.
+        assert_eq!(queen.max_dtm(), 19);
+        assert_eq!(rook.max_dtm(), 31);
+
+        let bytes = rook.to_bytes();
+        assert!(bytes.len() < rook.values.len());
+        assert_eq!(Table::from_bytes(&bytes).unwrap(), rook);
+        assert!(Table::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Table::from_bytes(b"ODYNTB00").is_err());
+    }
+
+    #[test]
+    #[ignore = "generates the 4-man endings, run in release mode"]
+    fn test_four_man_longest_mates() {
+        for (ending, moves) in [("KBNvK", 33), ("KRvKB", 29), ("KQvKR", 35)] {
+            let table = generate(&ending.parse().unwrap()).unwrap();
+            assert_eq!(table.max_dtm(), 2 * moves - 1, "{}", ending);
+        }
+    }
+
+    #[test]
+    fn test_probe_agrees_with_move_generation() {
+        let tablebases = tablebases(&["KRvK"]);
+
+        // Mate in one, with colours both ways.
+        let board = Chessboard::from_fen("3k4/8/3K4/8/8/8/8/7R w - - 0 1", " ");
+        assert_eq!(tablebases.probe_dtm(&board), Some(MATE_VALUE - 1));
+        let board = Chessboard::from_fen("7r/8/8/8/8/3k4/8/3K4 b - - 0 1", " ");
+        assert_eq!(tablebases.probe_dtm(&board), Some(MATE_VALUE - 1));
+        let board = Chessboard::from_fen("3k3R/8/3K4/8/8/8/8/8 b - - 0 1", " ");
+        assert_eq!(tablebases.probe_dtm(&board), Some(-MATE_VALUE));
+        // The rook hangs.
+        let board = Chessboard::from_fen("8/8/8/8/8/8/1k6/R6K b - - 0 1", " ");
+        assert_eq!(tablebases.probe_dtm(&board), Some(0));
+        assert_eq!(
+            tablebases.probe_dtm(&Chessboard::from_fen("8/8/8/3k4/8/8/8/R3K3 w Q - 0 1", " ")),
+            None
+        );
+
+        // Every position is scored one ply beyond its best move.
+        let mut rng = fastrand::Rng::with_seed(9);
+        let mut checked = 0;
+        while checked < 300 {
+            let [white_king, rook, black_king] = [rng.u32(0..64), rng.u32(0..64), rng.u32(0..64)];
+            let placement = Placement {
+                white_to_move: rng.bool(),
+                squares: [white_king, rook, black_king, 0],
+            };
+            let material: Material = "KRvK".parse().unwrap();
+            let distance = {
+                let (a, b) = (index_to_square(white_king), index_to_square(black_king));
+                a.0.abs_diff(b.0).max(a.1.abs_diff(b.1))
+            };
+            if distance <= 1 || !placement.is_legal(material.pieces()) {
+                continue;
+            }
+
+            let mut rows = vec![String::new(); 8];
+            for (row, text) in rows.iter_mut().enumerate() {
+                let mut empty = 0;
+                for col in 0..8 {
+                    let square = square_to_index(row as u32, col);
+                    let letter = [(white_king, 'K'), (rook, 'R'), (black_king, 'k')]
+                        .into_iter()
+                        .find(|&(piece, _)| piece == square);
+                    match letter {
+                        Some((_, letter)) => {
+                            if empty > 0 {
+                                text.push_str(&empty.to_string());
+                            }
+                            empty = 0;
+                            text.push(letter);
+                        }
+                        None => empty += 1,
+                    }
+                }
+                if empty > 0 {
+                    text.push_str(&empty.to_string());
+                }
+            }
+            let turn = if placement.white_to_move { "w" } else { "b" };
+            let board = Chessboard::from_fen(&format!("{} {} - - 0 1", rows.join("/"), turn), " ");
+
+            let score = tablebases.probe_dtm(&board).unwrap();
+            match tablebases.root_move(&board) {
+                Some((_, best)) => assert_eq!(score, best, "{}", board.to_fen(" ")),
+                None => assert!(score == 0 || score == -MATE_VALUE),
+            }
+            checked += 1;
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::game::chess_move::Move;
+
+/// Default transposition table size in megabytes.
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// How a stored score relates to the true score of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The search failed high: the true score is at least the stored one.
+    Lower,
+    /// The search failed low: the true score is at most the stored one.
+    Upper,
+}
+
+/// Result of a previous search of one position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TtEntry {
+    /// Full Zobrist key, used to detect index collisions.
+    pub key: u64,
+    /// Best (or refutation) move found, if any.
+    pub best_move: Option<Move>,
+    /// Score relative to the node, with mate scores stored as distance from the node.
+    pub score: i32,
+    /// Remaining depth the position was searched to.
+    pub depth: u8,
+    /// Kind of bound `score` represents.
+    pub bound: Bound,
+}
+
+/// MSB
+/// 6 bits | 2 bits | 8 bits | 32 bits     | 1 bit     | 3 bits    | 6 bits | 6 bits
+/// --------------------------------------------------------------------------------
+/// unused | bound  | depth  | score (i32) | has move  | promotion | end    | start
+const SCORE_SHIFT: u32 = 16;
+const DEPTH_SHIFT: u32 = 48;
+const BOUND_SHIFT: u32 = 56;
+const HAS_MOVE_BIT: u64 = 1 << 15;
+
+/// One slot of the table.
+///
+/// The key is stored XOR-ed with the data so that a slot torn by two threads writing at
+/// the same time fails the key check instead of returning a corrupted entry.
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Hash table of searched positions indexed by Zobrist key.
+///
+/// The table is lock-free and can be shared by several search threads.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+impl TranspositionTable {
+    /// Creates a table using roughly `size_mb` megabytes.
+    pub fn new(size_mb: usize) -> Self {
+        let slot_size = std::mem::size_of::<Slot>();
+        let len = (size_mb.max(1) * 1024 * 1024 / slot_size).max(1);
+        Self {
+            slots: (0..len).map(|_| Slot::default()).collect(),
+        }
+    }
+
+    /// Returns the entry stored for `key`, if any.
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        let slot = &self.slots[self.index(key)];
+        let data = slot.data.load(Ordering::Relaxed);
+        let stored_key = slot.key.load(Ordering::Relaxed) ^ data;
+
+        if data == 0 || stored_key != key {
+            return None;
+        }
+        Some(unpack(key, data))
+    }
+
+    /// Stores `entry`, keeping a deeper result for the same position.
+    pub fn store(&self, entry: TtEntry) {
+        let slot = &self.slots[self.index(entry.key)];
+
+        let previous = slot.data.load(Ordering::Relaxed);
+        let previous_key = slot.key.load(Ordering::Relaxed) ^ previous;
+        if previous != 0
+            && previous_key == entry.key
+            && unpack(previous_key, previous).depth > entry.depth
+        {
+            return;
+        }
+
+        let data = pack(&entry);
+        slot.key.store(entry.key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    /// Removes every entry.
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the occupancy of the table in permille, sampled on its first entries.
+    pub fn hashfull(&self) -> u16 {
+        let sample = self.slots.len().min(1000);
+        let used = self.slots[..sample]
+            .iter()
+            .filter(|slot| slot.data.load(Ordering::Relaxed) != 0)
+            .count();
+        (used * 1000 / sample) as u16
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.slots.len() as u64) as usize
+    }
+}
+
+fn pack(entry: &TtEntry) -> u64 {
+    let move_bits = match entry.best_move {
+        Some(move_) => {
+            HAS_MOVE_BIT
+                | move_.start_index as u64
+                | (move_.end_index as u64) << 6
+                | (move_.promotion_piece as u64) << 12
+        }
+        None => 0,
+    };
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+
+    // The bound is never zero, so neither is the packed data of a stored entry.
+    move_bits
+        | (entry.score as u32 as u64) << SCORE_SHIFT
+        | (entry.depth as u64) << DEPTH_SHIFT
+        | bound << BOUND_SHIFT
+}
+
+fn unpack(key: u64, data: u64) -> TtEntry {
+    let best_move = (data & HAS_MOVE_BIT != 0).then(|| {
+        Move::new(
+            (data & 0x3f) as u32,
+            (data >> 6 & 0x3f) as u32,
+            (data >> 12 & 0b111) as u8,
+        )
+    });
+    let bound = match data >> BOUND_SHIFT & 0b11 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+
+    TtEntry {
+        key,
+        best_move,
+        score: (data >> SCORE_SHIFT) as u32 as i32,
+        depth: (data >> DEPTH_SHIFT) as u8,
+        bound,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_probe_round_trip() {
+        let tt = TranspositionTable::new(1);
+        let entry = TtEntry {
+            key: 0x1234_5678_9abc_def0,
+            best_move: Some(Move::new(12, 4, 5)),
+            score: -325,
+            depth: 7,
+            bound: Bound::Lower,
+        };
+        tt.store(entry);
+
+        assert_eq!(tt.probe(entry.key), Some(entry));
+        assert_eq!(tt.probe(entry.key ^ 1), None);
+
+        // A shallower result for the same position does not replace a deeper one.
+        tt.store(TtEntry {
+            depth: 3,
+            best_move: None,
+            ..entry
+        });
+        assert_eq!(tt.probe(entry.key), Some(entry));
+
+        tt.clear();
+        assert_eq!(tt.probe(entry.key), None);
+        assert_eq!(tt.hashfull(), 0);
+    }
+}
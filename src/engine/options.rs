@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Result};
+
+/// Type and constraints of an engine option, following the UCI `option` types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionKind {
+    /// Boolean switch.
+    Check { default: bool },
+    /// Integer in `min..=max`.
+    Spin { default: i64, min: i64, max: i64 },
+    /// One value among `vars`.
+    Combo { default: String, vars: Vec<String> },
+    /// Action without a value.
+    Button,
+    /// Free text (for example a file path).
+    String { default: String },
+}
+
+/// Option advertised by an engine and changed through
+/// [`ChessEngine::set_option`](crate::engine::engine::ChessEngine::set_option).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOption {
+    /// Name shown to the user, matched case-insensitively.
+    pub name: String,
+    /// Type, default value and constraints.
+    pub kind: OptionKind,
+}
+
+impl EngineOption {
+    /// Creates an integer option.
+    pub fn spin(name: &str, default: i64, min: i64, max: i64) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: OptionKind::Spin { default, min, max },
+        }
+    }
+
+    /// Creates a boolean option.
+    pub fn check(name: &str, default: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: OptionKind::Check { default },
+        }
+    }
+
+    /// Creates a combo option choosing among `vars`.
+    pub fn combo(name: &str, default: &str, vars: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: OptionKind::Combo {
+                default: default.to_string(),
+                vars: vars.iter().map(|var| var.to_string()).collect(),
+            },
+        }
+    }
+
+    /// Creates a free text option.
+    pub fn string(name: &str, default: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: OptionKind::String {
+                default: default.to_string(),
+            },
+        }
+    }
+
+    /// Creates a button option.
+    pub fn button(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: OptionKind::Button,
+        }
+    }
+}
+
+/// Parses the value of a spin option, rejecting values outside `min..=max`.
+pub fn parse_spin(name: &str, value: &str, min: i64, max: i64) -> Result<i64> {
+    let parsed = value
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| anyhow!("option {}: invalid integer '{}'", name, value))?;
+
+    if !(min..=max).contains(&parsed) {
+        return Err(anyhow!(
+            "option {}: {} is outside {}..={}",
+            name,
+            parsed,
+            min,
+            max
+        ));
+    }
+
+    Ok(parsed)
+}
+
+/// Parses the value of a check option (`true` or `false`).
+pub fn parse_check(name: &str, value: &str) -> Result<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(anyhow!(
+            "option {}: expected true or false, got '{}'",
+            name,
+            value
+        )),
+    }
+}
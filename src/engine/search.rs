@@ -0,0 +1,244 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, RecvTimeoutError, Sender},
+    Arc,
+};
+use std::thread;
+use std::time::Duration;
+
+use crate::{constants::WHITE_ID, game::chess_move::Move};
+
+/// Time kept in reserve so that `bestmove` reaches the GUI before the flag falls.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+/// Number of moves assumed to remain when the GUI does not send `movestogo`.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Limits of a single search, mirroring the parameters of the UCI `go` command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// Remaining clock time for white.
+    pub wtime: Option<Duration>,
+    /// Remaining clock time for black.
+    pub btime: Option<Duration>,
+    /// White increment per move.
+    pub winc: Option<Duration>,
+    /// Black increment per move.
+    pub binc: Option<Duration>,
+    /// Moves left until the next time control.
+    pub movestogo: Option<u32>,
+    /// Maximum search depth in plies.
+    pub depth: Option<u8>,
+    /// Maximum number of nodes to search.
+    pub nodes: Option<u64>,
+    /// Search for a mate in that many moves.
+    pub mate: Option<u32>,
+    /// Exact time to spend on this move.
+    pub movetime: Option<Duration>,
+    /// Search until explicitly stopped.
+    pub infinite: bool,
+    /// Search in pondering mode (until `ponderhit` or `stop`).
+    pub ponder: bool,
+}
+
+impl SearchLimits {
+    /// Returns whether the search must wait for `stop` (or `ponderhit`) before answering.
+    pub fn is_unbounded(&self) -> bool {
+        self.infinite || self.ponder
+    }
+
+    /// Returns how long `color_id` may think on this move, if the search is timed.
+    pub fn time_budget(&self, color_id: u8) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+
+        if let Some(movetime) = self.movetime {
+            return Some(
+                movetime
+                    .saturating_sub(MOVE_OVERHEAD)
+                    .max(Duration::from_millis(1)),
+            );
+        }
+
+        let (time, inc) = if color_id == WHITE_ID {
+            (self.wtime?, self.winc.unwrap_or_default())
+        } else {
+            (self.btime?, self.binc.unwrap_or_default())
+        };
+
+        let moves_to_go = self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let budget = time / moves_to_go + inc * 3 / 4;
+        let hard_cap = time.saturating_sub(MOVE_OVERHEAD);
+
+        Some(budget.min(hard_cap).max(Duration::from_millis(1)))
+    }
+}
+
+/// Search value of delivering mate right now; a mate `n` plies away is worth
+/// `MATE_VALUE - n`.
+pub const MATE_VALUE: i32 = 32_000;
+/// Longest distance to mate, in plies, a search value can encode.
+pub const MAX_MATE_PLY: i32 = 256;
+/// Values at least that large in magnitude are mate scores. Evaluations stay below it.
+pub const MATE_BOUND: i32 = MATE_VALUE - MAX_MATE_PLY;
+
+/// Score reported to the GUI, from the point of view of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    /// Evaluation in centipawns.
+    Centipawns(i32),
+    /// Forced mate in that many moves; negative when the side to move gets mated.
+    Mate(i32),
+}
+
+impl Score {
+    /// Converts a search value in centipawns, where the mate range encodes the distance
+    /// to mate in plies, into the score reported to the GUI.
+    pub fn from_value(value: i32) -> Score {
+        if value >= MATE_BOUND {
+            let plies = MATE_VALUE - value;
+            Score::Mate((plies + 1) / 2)
+        } else if value <= -MATE_BOUND {
+            let plies = MATE_VALUE + value;
+            Score::Mate(-(plies + 1) / 2)
+        } else {
+            Score::Centipawns(value)
+        }
+    }
+
+    /// Converts the score back into a search value, the inverse of [`Score::from_value`]
+    /// up to the rounding of mate distances to whole moves.
+    pub fn to_value(self) -> i32 {
+        match self {
+            Score::Centipawns(value) => value,
+            Score::Mate(moves) if moves > 0 => MATE_VALUE - (2 * moves - 1),
+            Score::Mate(moves) => -MATE_VALUE - 2 * moves,
+        }
+    }
+}
+
+/// Progress report emitted by an engine while it searches.
+///
+/// Every field is optional so that the same structure covers per-iteration reports and
+/// lightweight `currmove` updates.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchInfo {
+    /// Nominal depth of the iteration.
+    pub depth: Option<u8>,
+    /// Deepest ply reached, quiescence search included.
+    pub seldepth: Option<u8>,
+    /// One-based rank of the reported line when several are searched (MultiPV).
+    pub multipv: Option<u32>,
+    /// Score of the principal variation.
+    pub score: Option<Score>,
+    /// Nodes searched since the search started.
+    pub nodes: Option<u64>,
+    /// Nodes searched per second.
+    pub nps: Option<u64>,
+    /// Time elapsed since the search started.
+    pub time: Option<Duration>,
+    /// Transposition table occupancy in permille.
+    pub hashfull: Option<u16>,
+    /// Root move currently being searched.
+    pub currmove: Option<Move>,
+    /// One-based index of [`SearchInfo::currmove`] in the root move list.
+    pub currmovenumber: Option<u32>,
+    /// Principal variation, starting with the best move.
+    pub pv: Vec<Move>,
+    /// Free text meant for the user.
+    pub string: Option<String>,
+}
+
+/// Receives the progress reports of a running search.
+pub trait InfoSink {
+    /// Called by the engine every time it has something to report.
+    fn info(&self, info: &SearchInfo);
+}
+
+impl<F: Fn(&SearchInfo)> InfoSink for F {
+    fn info(&self, info: &SearchInfo) {
+        self(info)
+    }
+}
+
+/// Outcome of a search.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchResult {
+    /// Move to play, or `None` when the position has no legal move.
+    pub best_move: Option<Move>,
+    /// Expected reply to [`SearchResult::best_move`], used for pondering.
+    pub ponder_move: Option<Move>,
+    /// Score of the best move from the point of view of the side to move.
+    pub score: Option<Score>,
+    /// Principal variation, starting with the best move.
+    pub pv: Vec<Move>,
+    /// Best root moves with their score and principal variation, best first. Holds
+    /// several lines when MultiPV is enabled.
+    pub lines: Vec<(Move, Score, Vec<Move>)>,
+}
+
+/// Raises a stop flag once a time budget has elapsed.
+///
+/// Dropping the timer cancels it: its thread wakes up and exits at once instead of
+/// sleeping until the budget is spent.
+pub struct StopTimer {
+    /// Dropped to wake the timer thread up.
+    _cancel: Sender<()>,
+}
+
+impl StopTimer {
+    /// Starts a timer raising `stop` after `budget`.
+    pub fn start(stop: Arc<AtomicBool>, budget: Duration) -> Self {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        thread::spawn(move || {
+            if cancelled.recv_timeout(budget) == Err(RecvTimeoutError::Timeout) {
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+        Self { _cancel: cancel }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_timer_fires_unless_dropped() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let _timer = StopTimer::start(Arc::clone(&stop), Duration::from_millis(10));
+        thread::sleep(Duration::from_millis(100));
+        assert!(stop.load(Ordering::Relaxed));
+
+        let stop = Arc::new(AtomicBool::new(false));
+        drop(StopTimer::start(
+            Arc::clone(&stop),
+            Duration::from_millis(10),
+        ));
+        thread::sleep(Duration::from_millis(100));
+        assert!(!stop.load(Ordering::Relaxed));
+        // The cancelled thread has let go of the flag.
+        assert_eq!(Arc::strong_count(&stop), 1);
+    }
+
+    #[test]
+    fn test_score_from_value() {
+        assert_eq!(Score::from_value(125), Score::Centipawns(125));
+        assert_eq!(Score::from_value(-50), Score::Centipawns(-50));
+        assert_eq!(
+            Score::from_value(MATE_BOUND - 1),
+            Score::Centipawns(MATE_BOUND - 1)
+        );
+        // Mate delivered on our first move (1 ply) is a mate in 1.
+        assert_eq!(Score::from_value(MATE_VALUE - 1), Score::Mate(1));
+        assert_eq!(Score::from_value(MATE_VALUE - 3), Score::Mate(2));
+        // Getting mated after our move and the opponent's reply.
+        assert_eq!(Score::from_value(-MATE_VALUE + 2), Score::Mate(-1));
+        // Already checkmated.
+        assert_eq!(Score::from_value(-MATE_VALUE), Score::Mate(0));
+
+        for value in [125, -50, MATE_VALUE - 3, -MATE_VALUE + 2, -MATE_VALUE] {
+            assert_eq!(Score::from_value(value).to_value(), value);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Match statistics: Elo difference with error bars and sequential probability ratio
+//! test.
+//!
+//! Both work on the score of the first engine of a match. The SPRT uses the normal
+//! approximation of the generalized SPRT over logistic Elo, as the usual testing
+//! frameworks do: the log-likelihood ratio of the two hypotheses is
+//! `n * (s1 - s0) * (2s - s0 - s1) / (2 var)` where `s` is the mean score of the `n`
+//! games, `var` its per-game variance and `s0`, `s1` the scores expected at `elo0` and
+//! `elo1`.
+
+/// Quantile of the normal distribution bounding a 95% confidence interval.
+const Z_95: f64 = 1.959964;
+
+/// Games won, drawn and lost by the first engine of a match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchScore {
+    /// Records a game in which the first engine scored `points` (1, 0.5 or 0).
+    pub fn add(&mut self, points: f64) {
+        if points > 0.5 {
+            self.wins += 1;
+        } else if points < 0.5 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+
+    /// Returns the number of games played.
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Returns the mean score per game, 0.5 before the first game.
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + 0.5 * self.draws as f64) / self.games() as f64
+    }
+
+    /// Returns the variance of the score of a single game.
+    fn variance(&self) -> f64 {
+        let games = self.games() as f64;
+        let score = self.score();
+        (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games
+    }
+
+    /// Returns the Elo difference to the second engine and the margin of its 95%
+    /// confidence interval.
+    ///
+    /// `None` until both a win and a loss, or some draws, make the difference finite.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let low = elo_from_score((score - Z_95 * deviation).max(f64::EPSILON));
+        let high = elo_from_score((score + Z_95 * deviation).min(1.0 - f64::EPSILON));
+        Some((elo_from_score(score), (high - low) / 2.0))
+    }
+}
+
+/// Returns the Elo difference at which a player is expected to score `score`.
+pub fn elo_from_score(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+/// Returns the expected score of a player `elo` points stronger than its opponent.
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Conclusion of a sequential probability ratio test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtStatus {
+    /// More games are needed.
+    Continue,
+    /// The first engine is not stronger by `elo1`: H0 (`elo0`) is accepted.
+    AcceptH0,
+    /// The first engine is stronger by `elo1`: H1 is accepted.
+    AcceptH1,
+}
+
+/// Sequential probability ratio test of H0: "the Elo difference is `elo0`" against H1:
+/// "it is `elo1`", with false positive rate `alpha` and false negative rate `beta`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// Creates a test of `elo0` against `elo1` at the usual 5% error rates.
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Self {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    /// Returns the log-likelihood ratio of H1 against H0 after `score`.
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        if score.games() == 0 {
+            return 0.0;
+        }
+        let variance = score.variance();
+        if variance <= 0.0 {
+            return 0.0;
+        }
+        let (s0, s1) = (score_from_elo(self.elo0), score_from_elo(self.elo1));
+        score.games() as f64 * (s1 - s0) * (2.0 * score.score() - s0 - s1) / (2.0 * variance)
+    }
+
+    /// Returns the log-likelihood ratios below which H0 and above which H1 is accepted.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Returns the conclusion of the test after `score`.
+    pub fn status(&self, score: &MatchScore) -> SprtStatus {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtStatus::AcceptH1
+        } else if llr <= lower {
+            SprtStatus::AcceptH0
+        } else {
+            SprtStatus::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(wins: u32, draws: u32, losses: u32) -> MatchScore {
+        MatchScore {
+            wins,
+            draws,
+            losses,
+        }
+    }
+
+    #[test]
+    fn test_elo_and_margin() {
+        assert_eq!(score(0, 0, 0).elo(), None);
+        assert_eq!(score(3, 0, 0).elo(), None);
+
+        let (elo, margin) = score(50, 0, 50).elo().unwrap();
+        assert!(elo.abs() < 1e-9);
+        // A coin flip over 100 games: 0.5 +- 0.098, about 69 Elo.
+        assert!((margin - 69.0).abs() < 1.0, "{}", margin);
+
+        let (elo, _) = score(60, 20, 20).elo().unwrap();
+        assert!((elo - elo_from_score(0.7)).abs() < 1e-9);
+        assert!((score_from_elo(elo) - 0.7).abs() < 1e-9);
+
+        let (_, tighter) = score(600, 200, 200).elo().unwrap();
+        let (_, wider) = score(60, 20, 20).elo().unwrap();
+        assert!(tighter < wider);
+    }
+
+    #[test]
+    fn test_sprt_concludes_on_clear_results() {
+        let sprt = Sprt::new(0.0, 10.0);
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 1e-3 && (upper - 2.944).abs() < 1e-3);
+
+        assert_eq!(sprt.llr(&score(0, 0, 0)), 0.0);
+        assert_eq!(sprt.status(&score(10, 20, 10)), SprtStatus::Continue);
+        assert_eq!(sprt.status(&score(600, 800, 400)), SprtStatus::AcceptH1);
+        assert_eq!(sprt.status(&score(400, 800, 600)), SprtStatus::AcceptH0);
+
+        // Winning more raises the ratio.
+        assert!(sprt.llr(&score(110, 100, 90)) > sprt.llr(&score(100, 100, 100)));
+    }
+}
@@ -0,0 +1,631 @@
+//! Matches between two UCI engines running as local processes.
+//!
+//! A [`Match`] plays its games in pairs: both engines play each opening once with White
+//! and once with Black. Clocks are kept by the runner, which forfeits an engine
+//! overstepping its time, and games may be stopped early by the [`Adjudication`] rules.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{atomic::AtomicBool, Arc},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    constants::{opposite, BLACK_ID, START_FEN, WHITE_ID},
+    engine::{
+        engine::ChessEngine,
+        search::{Score, SearchLimits, StopTimer},
+        sprt::MatchScore,
+    },
+    game::{
+        chess_move::Move,
+        chessboard::Chessboard,
+        pgn,
+        player::{self, GameRecord, GameResult, Termination},
+    },
+    uci::client::UciClient,
+};
+
+/// Time an engine may overstep its clock, to absorb the latency of the pipes.
+const TIME_MARGIN: Duration = Duration::from_millis(100);
+
+/// Clock of each side: `base` for the game plus `increment` after every move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+/// Parses `base+increment` in seconds, like `10+0.1`, or `base` alone.
+impl FromStr for TimeControl {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let seconds = |text: &str| -> Result<Duration> {
+            let value: f64 = text
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("invalid time '{}' in time control '{}'", text, s))?;
+            Duration::try_from_secs_f64(value)
+                .map_err(|_| anyhow!("invalid time '{}' in time control '{}'", text, s))
+        };
+        let (base, increment) = match s.split_once('+') {
+            Some((base, increment)) => (seconds(base)?, seconds(increment)?),
+            None => (seconds(s)?, Duration::ZERO),
+        };
+        if base.is_zero() {
+            return Err(anyhow!("time control '{}' leaves no time to play", s));
+        }
+        Ok(Self { base, increment })
+    }
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}+{}",
+            self.base.as_secs_f64(),
+            self.increment.as_secs_f64()
+        )
+    }
+}
+
+/// How to launch and set up an engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// Name written in the PGN and the reports.
+    pub name: String,
+    pub command: PathBuf,
+    pub args: Vec<String>,
+    /// UCI options set after the handshake, as `(name, value)` pairs.
+    pub options: Vec<(String, String)>,
+}
+
+impl EngineConfig {
+    /// Creates the configuration of `command`, named after its file name.
+    pub fn new(command: impl Into<PathBuf>) -> Self {
+        let command = command.into();
+        let name = command
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| command.display().to_string());
+        Self {
+            name,
+            command,
+            args: Vec::new(),
+            options: Vec::new(),
+        }
+    }
+
+    /// Replaces the name of the engine.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Adds a command-line argument.
+    pub fn with_arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Adds a UCI option to set before the first game.
+    pub fn with_option(mut self, name: &str, value: &str) -> Self {
+        self.options.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Rules stopping a game before its natural end. Scores are in centipawns, as reported
+/// by the engines from the point of view of the side to move.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Adjudication {
+    /// A side whose engine reports a score at or below `-resign_score` for
+    /// `resign_moves` moves in a row loses.
+    pub resign_score: Option<i32>,
+    pub resign_moves: u32,
+    /// The game is drawn once both engines report scores within `draw_score` of zero for
+    /// `draw_moves` moves each in a row, from move `draw_after` on.
+    pub draw_score: Option<i32>,
+    pub draw_moves: u32,
+    pub draw_after: u32,
+    /// The game is drawn after that many moves by each side.
+    pub max_moves: Option<u32>,
+}
+
+/// Per-game state of the [`Adjudication`] rules.
+#[derive(Debug, Default)]
+struct Arbiter {
+    /// Consecutive moves with a losing score, indexed by color id.
+    losing: [u32; 2],
+    /// Consecutive plies with a drawish score.
+    drawish: u32,
+}
+
+impl Arbiter {
+    /// Records the score reported with the move of `color_id` at full move `move_number`,
+    /// and returns the result it leads to, if any.
+    fn record(
+        &mut self,
+        rules: &Adjudication,
+        color_id: u8,
+        move_number: u32,
+        score: Option<Score>,
+    ) -> Option<(GameResult, Termination)> {
+        let value = score.map(Score::to_value);
+        let side = color_id as usize;
+
+        match (rules.resign_score, value) {
+            (Some(resign), Some(value)) if value <= -resign => self.losing[side] += 1,
+            _ => self.losing[side] = 0,
+        }
+        if rules.resign_score.is_some() && self.losing[side] >= rules.resign_moves.max(1) {
+            return Some((
+                GameResult::win_for(opposite(color_id)),
+                Termination::Adjudication("resign".to_string()),
+            ));
+        }
+
+        match (rules.draw_score, value) {
+            (Some(draw), Some(value)) if value.abs() <= draw && move_number >= rules.draw_after => {
+                self.drawish += 1
+            }
+            _ => self.drawish = 0,
+        }
+        if rules.draw_score.is_some() && self.drawish >= 2 * rules.draw_moves.max(1) {
+            return Some((
+                GameResult::Draw,
+                Termination::Adjudication("draw".to_string()),
+            ));
+        }
+
+        if rules
+            .max_moves
+            .is_some_and(|max| move_number >= max && color_id != WHITE_ID)
+        {
+            return Some((
+                GameResult::Draw,
+                Termination::Adjudication("move limit".to_string()),
+            ));
+        }
+        None
+    }
+}
+
+/// Starting position of a pair of games: a position and the moves already played from
+/// it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opening {
+    pub fen: String,
+    pub moves: Vec<Move>,
+}
+
+impl Default for Opening {
+    fn default() -> Self {
+        Self {
+            fen: START_FEN.to_string(),
+            moves: Vec::new(),
+        }
+    }
+}
+
+/// Loads openings from a `.pgn` file, keeping the moves of every game, or from an EPD
+/// file holding one position per line.
+pub fn load_openings(path: &Path) -> Result<Vec<Opening>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("cannot read openings {}", path.display()))?;
+    let is_pgn = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pgn"));
+    let openings = if is_pgn {
+        pgn::read_games(&text)
+            .map(|game| {
+                let game = game?;
+                Ok(Opening {
+                    fen: game.start_fen,
+                    moves: game.moves,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        text.lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(parse_epd)
+            .collect::<Result<Vec<_>>>()?
+    };
+    if openings.is_empty() {
+        return Err(anyhow!("no opening in {}", path.display()));
+    }
+    Ok(openings)
+}
+
+/// Reads the position of an EPD line, whose move counters are optional.
+fn parse_epd(line: &str) -> Result<Opening> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 4 {
+        return Err(anyhow!("expected a position in '{}'", line));
+    }
+    let counters_given = fields.len() >= 6
+        && fields[4].parse::<u32>().is_ok()
+        && fields[5].trim_end_matches(';').parse::<u32>().is_ok();
+    let fen = if counters_given {
+        format!(
+            "{} {}",
+            fields[..5].join(" "),
+            fields[5].trim_end_matches(';')
+        )
+    } else {
+        format!("{} 0 1", fields[..4].join(" "))
+    };
+    Ok(Opening {
+        fen,
+        moves: Vec::new(),
+    })
+}
+
+/// Launches an engine and sets its options.
+fn start_engine(config: &EngineConfig) -> Result<UciClient> {
+    let mut client = UciClient::spawn(&config.command, &config.args)?;
+    for (name, value) in &config.options {
+        client
+            .set_option(name, value)
+            .with_context(|| format!("cannot configure {}", config.name))?;
+    }
+    client.sync()?;
+    Ok(client)
+}
+
+/// Game played in a [`Match`].
+#[derive(Debug, Clone)]
+pub struct MatchGame {
+    /// Number of the game, from 1.
+    pub round: u32,
+    pub white: String,
+    pub black: String,
+    pub record: GameRecord,
+    /// Points scored by the first engine of the match.
+    pub points: f64,
+}
+
+impl MatchGame {
+    /// Formats the game in PGN.
+    pub fn to_pgn(&self) -> String {
+        let tags: Vec<(String, String)> = [
+            ("Event", "Engine match".to_string()),
+            ("Site", "?".to_string()),
+            ("Round", self.round.to_string()),
+            ("White", self.white.clone()),
+            ("Black", self.black.clone()),
+            ("Result", self.record.result.to_string()),
+            ("Termination", termination_tag(&self.record.termination)),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+        pgn::format_game(
+            &tags,
+            &self.record.start_fen,
+            &self.record.moves,
+            &self.record.result.to_string(),
+        )
+    }
+}
+
+/// Returns the text of the `Termination` tag, after the PGN standard where it has one.
+fn termination_tag(termination: &Termination) -> String {
+    match termination {
+        Termination::TimeForfeit => "time forfeit".to_string(),
+        Termination::Adjudication(_) => "adjudication".to_string(),
+        Termination::IllegalMove(_) | Termination::Forfeit(_) => "rules infraction".to_string(),
+        _ => "normal".to_string(),
+    }
+}
+
+/// Match between two engines.
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub engines: [EngineConfig; 2],
+    pub time_control: TimeControl,
+    pub adjudication: Adjudication,
+    /// Openings played in turn, the standard position when empty.
+    pub openings: Vec<Opening>,
+    /// Games to play, the last pair possibly incomplete.
+    pub games: u32,
+}
+
+impl Match {
+    /// Creates a match of `games` games between `first` and `second` from the standard
+    /// position, without adjudication.
+    pub fn new(
+        first: EngineConfig,
+        second: EngineConfig,
+        time_control: TimeControl,
+        games: u32,
+    ) -> Self {
+        Self {
+            engines: [first, second],
+            time_control,
+            adjudication: Adjudication::default(),
+            openings: Vec::new(),
+            games,
+        }
+    }
+
+    /// Replaces the adjudication rules.
+    pub fn with_adjudication(mut self, adjudication: Adjudication) -> Self {
+        self.adjudication = adjudication;
+        self
+    }
+
+    /// Replaces the openings.
+    pub fn with_openings(mut self, openings: Vec<Opening>) -> Self {
+        self.openings = openings;
+        self
+    }
+
+    /// Plays the match, calling `on_game` after every game with the game and the score
+    /// so far. The match stops early when `on_game` returns `false`.
+    ///
+    /// An engine that crashes or hangs loses the game and is restarted for the next one;
+    /// failing to start an engine aborts the match.
+    pub fn run(
+        &self,
+        mut on_game: impl FnMut(&MatchGame, &MatchScore) -> bool,
+    ) -> Result<MatchScore> {
+        let default_opening = [Opening::default()];
+        let openings = if self.openings.is_empty() {
+            &default_opening[..]
+        } else {
+            &self.openings[..]
+        };
+
+        let mut clients: [Option<UciClient>; 2] = [None, None];
+        let mut score = MatchScore::default();
+        for game in 0..self.games {
+            for (client, config) in clients.iter_mut().zip(&self.engines) {
+                if client.is_none() {
+                    *client = Some(start_engine(config)?);
+                }
+            }
+
+            let opening = &openings[(game / 2) as usize % openings.len()];
+            // The first engine plays White in even games.
+            let first_white = game % 2 == 0;
+            let (white, black) = if first_white { (0, 1) } else { (1, 0) };
+            let [first, second] = &mut clients;
+            let (first, second) = (first.as_mut().unwrap(), second.as_mut().unwrap());
+            let (white_client, black_client) = if first_white {
+                (first, second)
+            } else {
+                (second, first)
+            };
+            let (record, failed) = self.play(white_client, black_client, opening);
+
+            for color_id in failed {
+                let side = if color_id == WHITE_ID { white } else { black };
+                clients[side] = None;
+            }
+
+            let white_score = record.result.white_score();
+            let points = if first_white {
+                white_score
+            } else {
+                1.0 - white_score
+            };
+            score.add(points);
+            let played = MatchGame {
+                round: game + 1,
+                white: self.engines[white].name.clone(),
+                black: self.engines[black].name.clone(),
+                record,
+                points,
+            };
+            if !on_game(&played, &score) {
+                break;
+            }
+        }
+        Ok(score)
+    }
+
+    /// Plays one game and returns it with the color ids of the engines to restart.
+    fn play(
+        &self,
+        white: &mut UciClient,
+        black: &mut UciClient,
+        opening: &Opening,
+    ) -> (GameRecord, Vec<u8>) {
+        let start = Chessboard::from_fen(&opening.fen, " ");
+        let mut board = start.clone();
+        let mut history = Vec::new();
+        let mut moves = Vec::new();
+        let mut failed = Vec::new();
+        let mut arbiter = Arbiter::default();
+        let mut clocks = [self.time_control.base; 2];
+        let mut move_number: u32 = opening
+            .fen
+            .split(' ')
+            .nth(5)
+            .and_then(|field| field.parse().ok())
+            .unwrap_or(1);
+
+        let mut end = None;
+        for &move_ in &opening.moves {
+            if !board.legal_moves_vec(board.current_turn()).contains(&move_) {
+                break;
+            }
+            if board.current_turn() != WHITE_ID {
+                move_number += 1;
+            }
+            history.push(board.zobrist_key());
+            board.make_move_unchecked(move_);
+            moves.push(move_);
+        }
+
+        white.new_game();
+        black.new_game();
+
+        let (result, termination) = loop {
+            if let Some(end) = end.take() {
+                break end;
+            }
+            if let Some(end) = player::game_end(&board, &history) {
+                break end;
+            }
+
+            let color_id = board.current_turn();
+            let side = color_id as usize;
+            let engine = if color_id == WHITE_ID {
+                &mut *white
+            } else {
+                &mut *black
+            };
+            let loss = GameResult::win_for(opposite(color_id));
+
+            // The engine manages its own time; the runner only stops it once its clock
+            // has run out.
+            let allowed = clocks[side] + TIME_MARGIN;
+            let stop = Arc::new(AtomicBool::new(false));
+            let timer = StopTimer::start(Arc::clone(&stop), allowed);
+            let limits = SearchLimits {
+                wtime: Some(clocks[WHITE_ID as usize]),
+                btime: Some(clocks[BLACK_ID as usize]),
+                winc: Some(self.time_control.increment),
+                binc: Some(self.time_control.increment),
+                ..Default::default()
+            };
+            engine.set_position(&start, &moves);
+            let started = Instant::now();
+            let result = engine.search(limits, &|_: &_| {}, &stop);
+            let elapsed = started.elapsed();
+            drop(timer);
+
+            let Some(move_) = result.best_move else {
+                // A hung engine may still answer later and confuse the next game.
+                failed.push(color_id);
+                let termination = if elapsed > allowed {
+                    Termination::TimeForfeit
+                } else if engine.is_running() {
+                    Termination::Forfeit("no move".to_string())
+                } else {
+                    Termination::Forfeit("engine exited".to_string())
+                };
+                break (loss, termination);
+            };
+            if elapsed > allowed {
+                break (loss, Termination::TimeForfeit);
+            }
+            let score = result.score;
+            clocks[side] = clocks[side].saturating_sub(elapsed) + self.time_control.increment;
+
+            if !board.legal_moves_vec(color_id).contains(&move_) {
+                break (loss, Termination::IllegalMove(move_));
+            }
+            history.push(board.zobrist_key());
+            board.make_move_unchecked(move_);
+            moves.push(move_);
+
+            if player::game_end(&board, &history).is_none() {
+                end = arbiter.record(&self.adjudication, color_id, move_number, score);
+            }
+            if color_id != WHITE_ID {
+                move_number += 1;
+            }
+        };
+
+        let record = GameRecord {
+            start_fen: opening.fen.clone(),
+            moves,
+            result,
+            termination,
+        };
+        (record, failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_control() {
+        let tc: TimeControl = "10+0.1".parse().unwrap();
+        assert_eq!(tc.base, Duration::from_secs(10));
+        assert_eq!(tc.increment, Duration::from_millis(100));
+        assert_eq!(tc.to_string(), "10+0.1");
+        assert_eq!(
+            "0.5".parse::<TimeControl>().unwrap().increment,
+            Duration::ZERO
+        );
+        assert!("0+1".parse::<TimeControl>().is_err());
+        assert!("ten".parse::<TimeControl>().is_err());
+        assert!("-1+0".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn test_parse_epd() {
+        let opening = parse_epd("4k3/8/8/8/8/8/4P3/4K3 w - - bm e4; id \"pawn\";").unwrap();
+        assert_eq!(opening.fen, "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        let opening = parse_epd(START_FEN).unwrap();
+        assert_eq!(opening.fen, START_FEN);
+        assert!(parse_epd("4k3/8 w").is_err());
+    }
+
+    #[test]
+    fn test_adjudication() {
+        let rules = Adjudication {
+            resign_score: Some(600),
+            resign_moves: 2,
+            draw_score: Some(10),
+            draw_moves: 2,
+            draw_after: 20,
+            max_moves: Some(60),
+        };
+
+        let mut arbiter = Arbiter::default();
+        let lost = Some(Score::Centipawns(-700));
+        assert_eq!(arbiter.record(&rules, WHITE_ID, 10, lost), None);
+        assert_eq!(
+            arbiter.record(&rules, BLACK_ID, 10, Some(Score::Centipawns(700))),
+            None
+        );
+        assert_eq!(
+            arbiter.record(&rules, WHITE_ID, 11, Some(Score::Mate(-4))),
+            Some((
+                GameResult::BlackWins,
+                Termination::Adjudication("resign".to_string())
+            ))
+        );
+
+        // Drawish scores only count from move 20 on, and must hold for both sides.
+        let mut arbiter = Arbiter::default();
+        let even = Some(Score::Centipawns(3));
+        for (color_id, move_number) in [(WHITE_ID, 19), (BLACK_ID, 19), (WHITE_ID, 20)] {
+            assert_eq!(arbiter.record(&rules, color_id, move_number, even), None);
+        }
+        assert_eq!(arbiter.record(&rules, BLACK_ID, 20, even), None);
+        assert_eq!(arbiter.record(&rules, WHITE_ID, 21, even), None);
+        assert_eq!(
+            arbiter.record(&rules, BLACK_ID, 21, even),
+            Some((
+                GameResult::Draw,
+                Termination::Adjudication("draw".to_string())
+            ))
+        );
+
+        let mut arbiter = Arbiter::default();
+        assert_eq!(arbiter.record(&rules, WHITE_ID, 60, None), None);
+        assert_eq!(
+            arbiter.record(&rules, BLACK_ID, 60, None),
+            Some((
+                GameResult::Draw,
+                Termination::Adjudication("move limit".to_string())
+            ))
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! Texel tuning of the parameters of the tapered evaluation.
+//!
+//! Every term of the tapered evaluation is linear in [`EvalParams`]: a position is reduced
+//! once to the counts of each parameter (its features) and the game phase, after which
+//! evaluating it under new weights is a dot product. The tuner fits the weights so that
+//! `1 / (1 + 10^(-K * eval / 400))` predicts the result of the game the position was taken
+//! from, minimising the mean squared error with Adam.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    engine::implementations::{
+        activity::ActivityEvaluator,
+        eval_params::{EvalParams, Weight, PARAM_COUNT},
+        pawn_structure::PawnStructure,
+        pst_evaluator::{PstEvaluator, MAX_PHASE},
+    },
+    game::chessboard::Chessboard,
+};
+
+/// Scaling constant tried first, before [`Tuner::optimize_k`] fits it to the dataset.
+pub const DEFAULT_K: f64 = 1.0;
+
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+
+/// Position labelled with the result of its game.
+#[derive(Debug)]
+pub struct LabelledPosition {
+    /// The position.
+    pub board: Chessboard,
+    /// Result from White's point of view: 1 for a win, 0.5 for a draw, 0 for a loss.
+    pub result: f64,
+}
+
+/// Parses one line of a dataset.
+///
+/// Lines start with the board, side to move, castling rights and en passant square of a
+/// FEN, optionally followed by the two move counters. The result comes either from a
+/// `c9 "1-0";` EPD operation or from a last field holding `1-0`, `0-1`, `1/2-1/2` or a
+/// number between 0 and 1, possibly in brackets (`[0.5]`).
+pub fn parse_labelled_position(line: &str) -> Result<LabelledPosition> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 5 {
+        return Err(anyhow!("expected a position and a result in '{}'", line));
+    }
+
+    let (result, rest) = match fields.iter().position(|&field| field == "c9") {
+        Some(index) => {
+            let value = fields
+                .get(index + 1)
+                .ok_or_else(|| anyhow!("missing value after c9 in '{}'", line))?;
+            (*value, &fields[4..index])
+        }
+        None => (fields[fields.len() - 1], &fields[4..fields.len() - 1]),
+    };
+    let result = parse_result(result)?;
+
+    let [board, side, castling, en_passant] = [fields[0], fields[1], fields[2], fields[3]];
+    let ranks: Vec<&str> = board.split('/').collect();
+    if ranks.len() != 8 || !["w", "b"].contains(&side) {
+        return Err(anyhow!("invalid position in '{}'", line));
+    }
+    for rank in ranks {
+        let squares: u32 = rank.chars().map(|c| c.to_digit(10).unwrap_or(1)).sum();
+        if squares != 8
+            || !rank
+                .chars()
+                .all(|c| c.is_ascii_digit() || "pnbrqkPNBRQK".contains(c))
+        {
+            return Err(anyhow!("invalid rank '{}' in '{}'", rank, line));
+        }
+    }
+
+    // Move counters are optional in EPD; any other field is an operation we ignore.
+    let counters = match rest {
+        [halfmove, fullmove, ..]
+            if halfmove.parse::<u32>().is_ok() && fullmove.parse::<u32>().is_ok() =>
+        {
+            format!("{} {}", halfmove, fullmove)
+        }
+        _ => "0 1".to_string(),
+    };
+    let fen = format!(
+        "{} {} {} {} {}",
+        board, side, castling, en_passant, counters
+    );
+
+    Ok(LabelledPosition {
+        board: Chessboard::from_fen(&fen, " "),
+        result,
+    })
+}
+
+fn parse_result(field: &str) -> Result<f64> {
+    let value = field.trim_matches(|c| matches!(c, '"' | ';' | '[' | ']' | '(' | ')'));
+    let result = match value {
+        "1-0" => 1.0,
+        "0-1" => 0.0,
+        "1/2-1/2" | "1/2" | "=" => 0.5,
+        _ => value
+            .parse::<f64>()
+            .map_err(|_| anyhow!("invalid result '{}'", field))?,
+    };
+
+    if !(0.0..=1.0).contains(&result) {
+        return Err(anyhow!("result '{}' is not between 0 and 1", field));
+    }
+    Ok(result)
+}
+
+/// Loads every position of the dataset at `path`, one per line.
+///
+/// Empty lines and lines starting with `#` are skipped.
+pub fn load_dataset(path: impl AsRef<Path>) -> Result<Vec<LabelledPosition>> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("cannot read dataset {}", path.display()))?;
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(number, line)| {
+            parse_labelled_position(line)
+                .with_context(|| format!("{}:{}", path.display(), number + 1))
+        })
+        .collect()
+}
+
+/// Returns the counts of each parameter in the tapered evaluation of `board`, from
+/// White's point of view, sorted by parameter index and without zero counts.
+pub fn features(board: &Chessboard) -> Vec<(usize, i32)> {
+    let mut terms = Vec::new();
+    PstEvaluator::terms(board, &mut terms);
+    PawnStructure::terms(board, &mut terms);
+    ActivityEvaluator::terms(board, &mut terms);
+
+    let mut merged = BTreeMap::new();
+    for (index, count) in terms {
+        *merged.entry(index).or_insert(0) += count;
+    }
+    merged
+        .into_iter()
+        .filter(|&(_, count)| count != 0)
+        .collect()
+}
+
+/// Features of one position, ready to be evaluated under any weights.
+struct Sample {
+    features: Vec<(usize, i32)>,
+    /// Share of the middlegame score, from 0 to 1.
+    phase: f64,
+    result: f64,
+}
+
+/// Fits [`EvalParams`] to a dataset of labelled positions.
+pub struct Tuner {
+    samples: Vec<Sample>,
+    /// Middlegame and endgame value of each parameter.
+    weights: Vec<[f64; 2]>,
+    k: f64,
+    /// First and second moment estimates of Adam.
+    moments: Vec<[[f64; 2]; 2]>,
+    steps: i32,
+}
+
+impl Tuner {
+    /// Creates a tuner starting from `params`.
+    pub fn new(positions: &[LabelledPosition], params: &EvalParams) -> Self {
+        let samples = positions
+            .iter()
+            .map(|position| Sample {
+                features: features(&position.board),
+                phase: PstEvaluator::phase(&position.board) as f64 / MAX_PHASE as f64,
+                result: position.result,
+            })
+            .collect();
+        let weights = params
+            .weights()
+            .iter()
+            .map(|weight| [weight.midgame as f64, weight.endgame as f64])
+            .collect();
+
+        Self {
+            samples,
+            weights,
+            k: DEFAULT_K,
+            moments: vec![[[0.0; 2]; 2]; PARAM_COUNT],
+            steps: 0,
+        }
+    }
+
+    /// Returns the scaling constant of the sigmoid.
+    pub fn k(&self) -> f64 {
+        self.k
+    }
+
+    fn evaluate(&self, sample: &Sample) -> f64 {
+        let (midgame, endgame) =
+            sample
+                .features
+                .iter()
+                .fold((0.0, 0.0), |(midgame, endgame), &(index, count)| {
+                    let [weight_mg, weight_eg] = self.weights[index];
+                    (
+                        midgame + weight_mg * count as f64,
+                        endgame + weight_eg * count as f64,
+                    )
+                });
+        midgame * sample.phase + endgame * (1.0 - sample.phase)
+    }
+
+    fn sigmoid(&self, k: f64, score: f64) -> f64 {
+        1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+    }
+
+    fn error_with_k(&self, k: f64) -> f64 {
+        let total: f64 = self
+            .samples
+            .iter()
+            .map(|sample| (sample.result - self.sigmoid(k, self.evaluate(sample))).powi(2))
+            .sum();
+        total / self.samples.len().max(1) as f64
+    }
+
+    /// Returns the mean squared error between the predicted and actual results.
+    pub fn error(&self) -> f64 {
+        self.error_with_k(self.k)
+    }
+
+    /// Picks the scaling constant minimising the error of the current weights, so that
+    /// tuning only adjusts the weights relative to each other, and returns it.
+    pub fn optimize_k(&mut self) -> f64 {
+        let (mut low, mut high) = (0.0, 10.0);
+        for _ in 0..100 {
+            let left = low + (high - low) / 3.0;
+            let right = high - (high - low) / 3.0;
+            if self.error_with_k(left) < self.error_with_k(right) {
+                high = right;
+            } else {
+                low = left;
+            }
+        }
+
+        self.k = (low + high) / 2.0;
+        self.k
+    }
+
+    /// Runs one epoch of Adam over the whole dataset, moving weights by about
+    /// `learning_rate` centipawns.
+    pub fn step(&mut self, learning_rate: f64) {
+        let mut gradient = vec![[0.0; 2]; PARAM_COUNT];
+        let scale = self.k * std::f64::consts::LN_10 / 400.0;
+
+        for sample in &self.samples {
+            let predicted = self.sigmoid(self.k, self.evaluate(sample));
+            let slope = -2.0 * (sample.result - predicted) * predicted * (1.0 - predicted) * scale;
+            for &(index, count) in &sample.features {
+                let count = count as f64;
+                gradient[index][0] += slope * count * sample.phase;
+                gradient[index][1] += slope * count * (1.0 - sample.phase);
+            }
+        }
+
+        self.steps += 1;
+        let samples = self.samples.len().max(1) as f64;
+        let correction1 = 1.0 - BETA1.powi(self.steps);
+        let correction2 = 1.0 - BETA2.powi(self.steps);
+        for ((weight, moments), gradient) in
+            self.weights.iter_mut().zip(&mut self.moments).zip(gradient)
+        {
+            for phase in 0..2 {
+                let gradient = gradient[phase] / samples;
+                let [first, second] = &mut moments[phase];
+                *first = BETA1 * *first + (1.0 - BETA1) * gradient;
+                *second = BETA2 * *second + (1.0 - BETA2) * gradient * gradient;
+                weight[phase] -= learning_rate * (*first / correction1)
+                    / ((*second / correction2).sqrt() + EPSILON);
+            }
+        }
+    }
+
+    /// Returns the current weights, rounded to centipawns.
+    pub fn params(&self) -> EvalParams {
+        let mut params = EvalParams::default();
+        for (weight, &[midgame, endgame]) in params.weights_mut().iter_mut().zip(&self.weights) {
+            *weight = Weight::new(midgame.round() as i32, endgame.round() as i32);
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::WHITE_ID,
+        engine::{
+            evaluator::ChessEvaluator,
+            implementations::selectable_evaluator::{EvaluatorKind, SelectableEvaluator},
+        },
+    };
+
+    const POSITIONS: [&str; 6] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - c9 \"1/2-1/2\";",
+        "4k3/8/8/8/8/8/4P3/4K3 w - - c9 \"1-0\";",
+        "4k3/4p3/8/8/8/8/8/4K3 w - - c9 \"0-1\";",
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4 [0.5]",
+        "6k1/5ppp/8/8/8/8/5PPP/3QR1K1 w - - 0 1 1-0",
+        "3qr1k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1 0.0",
+    ];
+
+    #[test]
+    fn test_dataset_lines_are_parsed() {
+        let position = parse_labelled_position(POSITIONS[1]).unwrap();
+        assert_eq!(position.result, 1.0);
+        assert_eq!(
+            position.board.to_fen(" "),
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"
+        );
+
+        assert_eq!(parse_labelled_position(POSITIONS[0]).unwrap().result, 0.5);
+        assert_eq!(parse_labelled_position(POSITIONS[3]).unwrap().result, 0.5);
+        assert_eq!(parse_labelled_position(POSITIONS[5]).unwrap().result, 0.0);
+        let position =
+            parse_labelled_position("4k3/8/8/8/8/8/4P3/4K3 b - - hmvc 3; c9 \"0-1\";").unwrap();
+        assert_eq!(position.result, 0.0);
+
+        assert!(parse_labelled_position("4k3/8/8 w - - c9 \"1-0\";").is_err());
+        assert!(parse_labelled_position("4k3/8/8/8/8/8/4P3/4K3 w - - c9 \"2-0\";").is_err());
+        assert!(parse_labelled_position("4k3/8/8/8/8/8/4P3/4K3 w - - 1.5").is_err());
+        assert!(parse_labelled_position("4k3/8/8/8/8/8/4P3/4K3 w -").is_err());
+    }
+
+    #[test]
+    fn test_features_reproduce_the_tapered_evaluation() {
+        let evaluator = SelectableEvaluator::new(EvaluatorKind::Tapered);
+        let positions: Vec<LabelledPosition> = POSITIONS
+            .iter()
+            .map(|line| parse_labelled_position(line).unwrap())
+            .collect();
+        let tuner = Tuner::new(&positions, &EvalParams::default());
+
+        for (position, sample) in positions.iter().zip(&tuner.samples) {
+            let expected = evaluator.evaluate(&position.board, WHITE_ID) as f64;
+            // The evaluator rounds each component separately.
+            assert!((tuner.evaluate(sample) - expected).abs() <= 3.0);
+        }
+    }
+
+    #[test]
+    fn test_tuning_reduces_the_error() {
+        let path = std::env::temp_dir().join("odyn_tuning_dataset.epd");
+        fs::write(&path, format!("# results\n\n{}\n", POSITIONS.join("\n"))).unwrap();
+        let positions = load_dataset(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(positions.len(), POSITIONS.len());
+
+        let mut tuner = Tuner::new(&positions, &EvalParams::default());
+        tuner.optimize_k();
+        let before = tuner.error();
+        for _ in 0..50 {
+            tuner.step(5.0);
+        }
+        assert!(tuner.error() < before);
+
+        // Rounding the weights back to centipawns keeps the improvement.
+        let tuned = Tuner::new(&positions, &tuner.params());
+        assert!(tuned.error_with_k(tuner.k()) < before);
+    }
+}
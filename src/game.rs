@@ -16,7 +16,11 @@ pub mod mailbox;
 pub mod move_generator;
 /// Perft utilities for move-generation validation.
 pub mod perft;
-/// Player related helpers and types.
+/// PGN game reading and standard algebraic notation.
+pub mod pgn;
+/// Players choosing moves and a driver playing complete games between them.
 pub mod player;
 /// Conversion and bit manipulation utility functions.
 pub mod utility;
+/// Zobrist keys used to hash positions.
+pub mod zobrist;
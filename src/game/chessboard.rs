@@ -24,6 +24,7 @@ use crate::{
             generate_xray_bishop_attacks, generate_xray_rook_attacks,
         },
         utility::bitboard_to_vec,
+        zobrist::ZOBRIST,
     },
 };
 
@@ -33,8 +34,11 @@ pub struct Chessboard {
     bitboard: bitboard::Bitboard,
     mailbox: mailbox::MailboxBoard,
 
-    white_moves: u32,
-    black_moves: u32,
+    /// Number of the current full move, starting at 1 and incremented after Black moves.
+    fullmove_number: u32,
+
+    /// Plies since the last capture or pawn move, for the fifty-move rule.
+    halfmove_clock: u32,
 }
 
 impl Clone for Chessboard {
@@ -45,8 +49,8 @@ impl Clone for Chessboard {
         Self {
             bitboard,
             mailbox,
-            white_moves: self.white_moves,
-            black_moves: self.black_moves,
+            fullmove_number: self.fullmove_number,
+            halfmove_clock: self.halfmove_clock,
         }
     }
 }
@@ -56,7 +60,7 @@ impl Chessboard {
     pub fn from_fen(fen: &str, separator: &str) -> Chessboard {
         let fen_parts: Vec<&str> = fen.split(separator).collect();
 
-        let [_s_board, _s_turn, _s_castle, _s_enpassant, s_bmoves, s_wmoves] = &fen_parts[..]
+        let [_s_board, _s_turn, _s_castle, _s_enpassant, s_halfmove, s_fullmove] = &fen_parts[..]
         else {
             panic!("Invalid fen, invalid number of parts")
         };
@@ -64,8 +68,8 @@ impl Chessboard {
         Chessboard {
             bitboard: Bitboard::from_fen(fen, separator),
             mailbox: MailboxBoard::from_fen(fen, separator),
-            white_moves: s_wmoves.parse().unwrap(),
-            black_moves: s_bmoves.parse().unwrap(),
+            fullmove_number: s_fullmove.parse().unwrap(),
+            halfmove_clock: s_halfmove.parse().unwrap(),
         }
     }
 
@@ -94,7 +98,7 @@ impl Chessboard {
     /// Serializes the board back to FEN.
     pub fn to_fen(&self, separator: &str) -> String {
         let mut bitboard_fen = self.bitboard.to_fen();
-        let move_counts = format!("{} {}", self.black_moves, self.white_moves);
+        let move_counts = format!("{} {}", self.halfmove_clock, self.fullmove_number);
         bitboard_fen.push(move_counts);
 
         bitboard_fen.join(separator)
@@ -102,8 +106,18 @@ impl Chessboard {
 
     /// Applies a move without validating legality and returns packed move flags.
     pub fn make_move_unchecked(&mut self, move_: Move) -> u16 {
+        if self.current_turn() == BLACK_ID {
+            self.fullmove_number += 1;
+        }
         let flags = self.mailbox.move_piece(&move_);
         self.bitboard.move_piece(&move_, flags);
+
+        let is_capture = chess_move::get_captured_piece_flag(flags) != EMPTY_ID;
+        if is_capture || chess_move::get_piece_flag(flags) == PAWN_ID {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
         flags
     }
 
@@ -116,6 +130,18 @@ impl Chessboard {
         cboard
     }
 
+    /// Plays `moves` from this position, returning the board reached along with the
+    /// Zobrist keys of the positions before it, oldest first.
+    pub fn replay(&self, moves: &[Move]) -> (Chessboard, Vec<u64>) {
+        let mut board = self.clone();
+        let mut history = Vec::with_capacity(moves.len());
+        for move_ in moves {
+            history.push(board.zobrist_key());
+            board.make_move_unchecked(*move_);
+        }
+        (board, history)
+    }
+
     /// Computes legal moves for `color_id`.
     pub fn legal_moves(&self, color_id: u8) -> HashSet<chess_move::Move> {
         self.legal_moves_vec(color_id).into_iter().collect()
@@ -406,11 +432,91 @@ impl Chessboard {
         self.bitboard.is_in_check(color_id, opponent_attacks)
     }
 
+    /// Returns whether the king of `color_id` is attacked in the current position.
+    pub fn is_king_attacked(&self, color_id: u8) -> bool {
+        let opponent_attacks = self
+            .bitboard
+            .generate_pieces_attacks(constants::opposite(color_id), &ALL_PIECES_ID);
+        self.is_in_check(color_id, opponent_attacks)
+    }
+
+    /// Passes the turn to the opponent without moving (a "null move").
+    ///
+    /// Only meant for search heuristics: the resulting position may not be reachable. The
+    /// halfmove clock is reset so that repetitions are not detected across the null move.
+    pub fn make_null_move(&mut self) {
+        self.bitboard.en_passant = 0;
+        self.bitboard.flags ^= 1 << bitboard::TURN_F_INDEX;
+        self.halfmove_clock = 0;
+    }
+
+    /// Returns the number of plies since the last capture or pawn move.
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    /// Returns whether `color_id` has pieces other than pawns and its king.
+    pub fn has_non_pawn_material(&self, color_id: u8) -> bool {
+        let pieces = self.bitboard.knight_board
+            | self.bitboard.bishop_board
+            | self.bitboard.rook_board
+            | self.bitboard.queen_board;
+        pieces & self.bitboard.get_color_board(color_id) != 0
+    }
+
+    /// Returns the underlying bitboards, for attack generation.
+    pub fn bitboard(&self) -> &Bitboard {
+        &self.bitboard
+    }
+
+    /// Returns the squares occupied by the `piece_id` pieces of `color_id`.
+    pub fn piece_board(&self, piece_id: u8, color_id: u8) -> u64 {
+        self.bitboard.get_piece_board(piece_id).unwrap_or(0)
+            & self.bitboard.get_color_board(color_id)
+    }
+
+    /// Returns the squares occupied by any piece.
+    pub fn occupancy(&self) -> u64 {
+        self.bitboard.white_board | self.bitboard.black_board
+    }
+
     /// Returns the color id of the side to move.
     pub fn current_turn(&self) -> u8 {
         self.bitboard.current_turn()
     }
 
+    /// Returns the Zobrist hash of the position (pieces, side to move, castle rights and
+    /// en passant file).
+    pub fn zobrist_key(&self) -> u64 {
+        let mut key = 0;
+        for (index, (piece_id, color_id)) in self.get_iterator_on_pieces() {
+            key ^= ZOBRIST.pieces[color_id as usize][piece_id as usize][index as usize];
+        }
+
+        if self.current_turn() == BLACK_ID {
+            key ^= ZOBRIST.black_to_move;
+        }
+
+        key ^= ZOBRIST.castling[(self.bitboard.flags & 0b1111) as usize];
+
+        if self.bitboard.en_passant != 0 {
+            key ^= ZOBRIST.en_passant[self.bitboard.en_passant.trailing_zeros() as usize];
+        }
+
+        key
+    }
+
+    /// Returns a Zobrist hash of the pawns only, used to cache pawn structure evaluations.
+    pub fn pawn_key(&self) -> u64 {
+        let mut key = 0;
+        for color_id in [BLACK_ID, WHITE_ID] {
+            for index in utility::iter_bits(self.piece_board(PAWN_ID, color_id)) {
+                key ^= ZOBRIST.pieces[color_id as usize][PAWN_ID as usize][index as usize];
+            }
+        }
+        key
+    }
+
     pub fn to_tensor(&self) -> Tensor {
         let mut planes: Vec<Tensor> = [
             self.bitboard.white_board & self.bitboard.pawn_board,
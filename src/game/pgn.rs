@@ -0,0 +1,586 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    constants::{
+        BISHOP_ID, EMPTY_ID, KING_ID, KNIGHT_ID, PAWN_ID, QUEEN_ID, ROOK_ID, START_FEN, WHITE_ID,
+    },
+    game::{chess_move::Move, chessboard::Chessboard, utility},
+};
+
+/// Game read from a PGN file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnGame {
+    /// Tag pairs in the order of the file, such as `("White", "Carlsen, Magnus")`.
+    pub tags: Vec<(String, String)>,
+    /// Starting position, from the `FEN` tag or the standard one.
+    pub start_fen: String,
+    /// Moves of the main line; variations are skipped.
+    pub moves: Vec<Move>,
+    /// Score of White (1, 0.5 or 0), or `None` for unfinished games.
+    pub result: Option<f64>,
+}
+
+impl PgnGame {
+    /// Returns the value of the tag `name`, compared case-insensitively.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the rating of the player of `color_id` from the `WhiteElo`/`BlackElo` tags.
+    pub fn elo(&self, color_id: u8) -> Option<u32> {
+        let tag = if color_id == WHITE_ID {
+            "WhiteElo"
+        } else {
+            "BlackElo"
+        };
+        self.tag(tag)?.trim().parse().ok()
+    }
+}
+
+/// Returns White's score for a game termination marker, `None` for `*` or anything else.
+fn parse_result(token: &str) -> Option<f64> {
+    match token {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        _ => None,
+    }
+}
+
+fn is_termination(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+/// SAN letters indexed by piece id, pawns having none.
+const PIECE_LETTERS: [char; 7] = [' ', ' ', 'N', 'B', 'R', 'Q', 'K'];
+
+fn piece_from_letter(letter: char) -> Option<u8> {
+    match letter {
+        'N' => Some(KNIGHT_ID),
+        'B' => Some(BISHOP_ID),
+        'R' => Some(ROOK_ID),
+        'Q' => Some(QUEEN_ID),
+        'K' => Some(KING_ID),
+        _ => None,
+    }
+}
+
+/// Converts a move in standard algebraic notation (like `Nbd7`, `exd6`, `O-O` or `e8=Q+`)
+/// into the legal move of `board` it names.
+pub fn parse_san(board: &Chessboard, san: &str) -> Result<Move> {
+    let text = san.trim_end_matches(['+', '#', '!', '?']);
+    let legal = board.legal_moves_vec(board.current_turn());
+
+    let castle = match text {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    };
+    if let Some(king_side) = castle {
+        return legal
+            .into_iter()
+            .find(|move_| {
+                board.piece_at(move_.start_index).0 == KING_ID
+                    && move_.start_index.abs_diff(move_.end_index) == 2
+                    && (move_.end_index > move_.start_index) == king_side
+            })
+            .ok_or_else(|| anyhow!("illegal castling '{}'", san));
+    }
+
+    let mut chars: Vec<char> = text.chars().filter(|&c| c != 'x' && c != '=').collect();
+    let piece_id = match chars.first().copied().and_then(piece_from_letter) {
+        Some(piece_id) => {
+            chars.remove(0);
+            piece_id
+        }
+        None => PAWN_ID,
+    };
+    let promotion_piece = match chars.last().copied().and_then(piece_from_letter) {
+        Some(promotion) if piece_id == PAWN_ID => {
+            chars.pop();
+            promotion
+        }
+        _ => 0,
+    };
+    if chars.len() < 2 {
+        return Err(anyhow!("invalid move '{}'", san));
+    }
+
+    let destination: String = chars.split_off(chars.len() - 2).into_iter().collect();
+    let (row, col) = utility::string_to_square(&destination)
+        .ok_or_else(|| anyhow!("invalid destination in move '{}'", san))?;
+    let end_index = utility::square_to_index(row, col);
+    // Whatever is left disambiguates the origin by file and/or rank.
+    let mut from_col = None;
+    let mut from_row = None;
+    for c in chars {
+        match c {
+            'a'..='h' => from_col = Some(c as u32 - 'a' as u32),
+            '1'..='8' => from_row = Some(8 - (c as u32 - '0' as u32)),
+            _ => return Err(anyhow!("invalid move '{}'", san)),
+        }
+    }
+
+    let mut candidates = legal.into_iter().filter(|move_| {
+        move_.end_index == end_index
+            && move_.promotion_piece == promotion_piece
+            && board.piece_at(move_.start_index).0 == piece_id
+            && from_col.is_none_or(|col| move_.start_index % 8 == col)
+            && from_row.is_none_or(|row| move_.start_index / 8 == row)
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(move_), None) => Ok(move_),
+        (Some(_), Some(_)) => Err(anyhow!("ambiguous move '{}'", san)),
+        (None, _) => Err(anyhow!("illegal move '{}'", san)),
+    }
+}
+
+/// Formats a legal move of `board` in standard algebraic notation, the inverse of
+/// [`parse_san`].
+pub fn to_san(board: &Chessboard, move_: Move) -> String {
+    let (piece_id, _) = board.piece_at(move_.start_index);
+    let destination = utility::index_to_string(move_.end_index);
+    let legal = board.legal_moves_vec(board.current_turn());
+
+    let mut san = if piece_id == KING_ID && move_.start_index.abs_diff(move_.end_index) == 2 {
+        if move_.end_index > move_.start_index {
+            "O-O".to_string()
+        } else {
+            "O-O-O".to_string()
+        }
+    } else {
+        let from = utility::index_to_string(move_.start_index);
+        let capture = board.piece_at(move_.end_index).0 != EMPTY_ID
+            || (piece_id == PAWN_ID && move_.start_index % 8 != move_.end_index % 8);
+        let mut san = String::new();
+        if piece_id == PAWN_ID {
+            if capture {
+                san.push_str(&from[..1]);
+            }
+        } else {
+            san.push(PIECE_LETTERS[piece_id as usize]);
+            let rivals: Vec<u32> = legal
+                .iter()
+                .filter(|other| {
+                    other.end_index == move_.end_index
+                        && other.start_index != move_.start_index
+                        && board.piece_at(other.start_index).0 == piece_id
+                })
+                .map(|other| other.start_index)
+                .collect();
+            if !rivals.is_empty() {
+                if rivals
+                    .iter()
+                    .all(|rival| rival % 8 != move_.start_index % 8)
+                {
+                    san.push_str(&from[..1]);
+                } else if rivals
+                    .iter()
+                    .all(|rival| rival / 8 != move_.start_index / 8)
+                {
+                    san.push_str(&from[1..]);
+                } else {
+                    san.push_str(&from);
+                }
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&destination);
+        if move_.promotion_piece != 0 {
+            san.push('=');
+            san.push(PIECE_LETTERS[move_.promotion_piece as usize]);
+        }
+        san
+    };
+
+    let mut after = board.clone();
+    after.make_move_unchecked(move_);
+    let color_id = after.current_turn();
+    if after.is_king_attacked(color_id) {
+        san.push(if after.legal_moves_vec(color_id).is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    san
+}
+
+/// Formats a game in PGN: the tag pairs, then the moves in standard algebraic notation
+/// followed by `result`, wrapped at 80 columns.
+///
+/// `SetUp` and `FEN` tags are added when the game does not start from the standard
+/// position.
+pub fn format_game(
+    tags: &[(String, String)],
+    start_fen: &str,
+    moves: &[Move],
+    result: &str,
+) -> String {
+    let mut text = String::new();
+    let mut tags = tags.to_vec();
+    if start_fen != START_FEN {
+        tags.push(("SetUp".to_string(), "1".to_string()));
+        tags.push(("FEN".to_string(), start_fen.to_string()));
+    }
+    for (name, value) in &tags {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        text.push_str(&format!("[{} \"{}\"]\n", name, value));
+    }
+    text.push('\n');
+
+    let mut board = Chessboard::from_fen(start_fen, " ");
+    let mut number: u32 = start_fen
+        .split(' ')
+        .nth(5)
+        .and_then(|field| field.parse().ok())
+        .unwrap_or(1);
+    let mut symbols = Vec::new();
+    for (ply, &move_) in moves.iter().enumerate() {
+        if board.current_turn() == WHITE_ID {
+            symbols.push(format!("{}.", number));
+        } else if ply == 0 {
+            symbols.push(format!("{}...", number));
+        }
+        symbols.push(to_san(&board, move_));
+        if board.current_turn() != WHITE_ID {
+            number += 1;
+        }
+        board.make_move_unchecked(move_);
+    }
+    symbols.push(result.to_string());
+
+    let mut line = String::new();
+    for symbol in symbols {
+        if !line.is_empty() && line.len() + 1 + symbol.len() > 80 {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&symbol);
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+/// Splits PGN text into tokens: tag pairs, move text symbols and termination markers.
+///
+/// Comments, variations, numeric annotations and move numbers are dropped.
+struct Tokenizer<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Tag(String, String),
+    Symbol(&'a str),
+}
+
+impl<'a> Tokenizer<'a> {
+    fn skip_until(&mut self, end: char) {
+        self.position = match self.text[self.position..].find(end) {
+            Some(offset) => self.position + offset + end.len_utf8(),
+            None => self.text.len(),
+        };
+    }
+
+    fn skip_variation(&mut self) {
+        let mut depth = 0;
+        while let Some(c) = self.text[self.position..].chars().next() {
+            self.position += c.len_utf8();
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                '{' => self.skip_until('}'),
+                ';' => self.skip_until('\n'),
+                _ => {}
+            }
+        }
+    }
+
+    fn read_tag(&mut self) -> Token<'a> {
+        let rest = &self.text[self.position + 1..];
+        let end = rest.find(']').unwrap_or(rest.len());
+        self.position = (self.position + 1 + end + 1).min(self.text.len());
+
+        let content = rest[..end].trim();
+        let (name, value) = content
+            .split_once(char::is_whitespace)
+            .unwrap_or((content, ""));
+        let value = value.trim();
+        let value = value.strip_prefix('"').unwrap_or(value);
+        let value = value
+            .strip_suffix('"')
+            .unwrap_or(value)
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\");
+        Token::Tag(name.to_string(), value)
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        loop {
+            let c = self.text[self.position..].chars().next()?;
+            match c {
+                '[' => return Some(self.read_tag()),
+                '{' => self.skip_until('}'),
+                // Line comments, and `%` escape lines.
+                ';' | '%' => self.skip_until('\n'),
+                '(' => self.skip_variation(),
+                c if c.is_whitespace() || c == ')' || c == ']' => self.position += c.len_utf8(),
+                _ => {
+                    let rest = &self.text[self.position..];
+                    let end = rest
+                        .find(|c: char| c.is_whitespace() || "[]{}();".contains(c))
+                        .unwrap_or(rest.len());
+                    self.position += end;
+
+                    let symbol = &rest[..end];
+                    if is_termination(symbol) {
+                        return Some(Token::Symbol(symbol));
+                    }
+                    // Move numbers may be glued to the move, as in `1.e4` or `3...Nf6`.
+                    let symbol =
+                        symbol.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                    if !symbol.is_empty() && !symbol.starts_with('$') {
+                        return Some(Token::Symbol(symbol));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Iterator over the games of a PGN text, returned by [`read_games`].
+pub struct PgnGames<'a> {
+    tokens: std::iter::Peekable<Tokenizer<'a>>,
+    /// Number of games read so far, used in error messages.
+    count: usize,
+}
+
+impl Iterator for PgnGames<'_> {
+    type Item = Result<PgnGame>;
+
+    fn next(&mut self) -> Option<Result<PgnGame>> {
+        self.tokens.peek()?;
+        self.count += 1;
+
+        let mut tags = Vec::new();
+        while let Some(Token::Tag(..)) = self.tokens.peek() {
+            if let Some(Token::Tag(name, value)) = self.tokens.next() {
+                tags.push((name, value));
+            }
+        }
+
+        let start_fen = tags
+            .iter()
+            .find(|(name, _)| name == "FEN")
+            .map_or(START_FEN.to_string(), |(_, fen)| fen.clone());
+        let mut board = Chessboard::from_fen(&start_fen, " ");
+        let mut moves = Vec::new();
+        let mut error = None;
+        let mut result = None;
+
+        // The move text ends with a termination marker, or at the tags of the next game.
+        while let Some(Token::Symbol(symbol)) = self.tokens.peek() {
+            let symbol = *symbol;
+            self.tokens.next();
+            if is_termination(symbol) {
+                result = parse_result(symbol);
+                break;
+            }
+            if error.is_some() {
+                continue;
+            }
+            match parse_san(&board, symbol) {
+                Ok(move_) => {
+                    board.make_move_unchecked(move_);
+                    moves.push(move_);
+                }
+                Err(err) => {
+                    error =
+                        Some(err.context(format!("game {}, move {}", self.count, moves.len() + 1)))
+                }
+            }
+        }
+
+        if let Some(error) = error {
+            return Some(Err(error));
+        }
+        let result = result.or_else(|| {
+            tags.iter()
+                .find(|(name, _)| name == "Result")
+                .and_then(|(_, value)| parse_result(value))
+        });
+        Some(Ok(PgnGame {
+            tags,
+            start_fen,
+            moves,
+            result,
+        }))
+    }
+}
+
+/// Reads the games of a PGN text one by one.
+///
+/// A game with an illegal or ambiguous move yields an error, and reading resumes with the
+/// next game.
+pub fn read_games(text: &str) -> PgnGames<'_> {
+    PgnGames {
+        tokens: Tokenizer { text, position: 0 }.peekable(),
+        count: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci::protocol::parse_uci_move;
+
+    fn san(fen: &str, san: &str) -> Result<String> {
+        parse_san(&Chessboard::from_fen(fen, " "), san).map(|move_| move_.uci_move())
+    }
+
+    #[test]
+    fn test_parse_san() {
+        assert_eq!(san(START_FEN, "e4").unwrap(), "e2e4");
+        assert_eq!(san(START_FEN, "Nf3").unwrap(), "g1f3");
+        assert!(san(START_FEN, "e5").is_err());
+        assert!(san(START_FEN, "Qh5").is_err());
+
+        let fen = "r3k2r/1P4P1/8/3pP3/8/1N3N2/8/R3K2R w KQkq d6 0 1";
+        assert_eq!(san(fen, "O-O").unwrap(), "e1g1");
+        assert_eq!(san(fen, "O-O-O+").unwrap(), "e1c1");
+        assert_eq!(san(fen, "exd6").unwrap(), "e5d6");
+        assert_eq!(san(fen, "bxa8=Q").unwrap(), "b7a8q");
+        assert_eq!(san(fen, "b8N").unwrap(), "b7b8n");
+        assert_eq!(san(fen, "gxh8=R#").unwrap(), "g7h8r");
+        assert_eq!(san(fen, "Nbd4").unwrap(), "b3d4");
+        assert_eq!(san(fen, "Nfxd4").unwrap(), "f3d4");
+        assert!(san(fen, "Nd4").is_err());
+        assert!(san(fen, "b8").is_err());
+
+        let fen = "8/8/8/8/8/R7/8/R3K2k w - - 0 1";
+        assert_eq!(san(fen, "R1a2").unwrap(), "a1a2");
+        assert_eq!(san(fen, "R3a2!?").unwrap(), "a3a2");
+    }
+
+    #[test]
+    fn test_to_san_round_trips_legal_moves() {
+        let fens = [
+            START_FEN,
+            "r3k2r/1P4P1/8/3pP3/8/1N3N2/8/R3K2R w KQkq d6 0 1",
+            "8/8/8/8/8/R7/8/R3K2k w - - 0 1",
+            "4k3/8/8/8/1Q5Q/8/8/1Q2K2Q w - - 0 1",
+        ];
+        for fen in fens {
+            let board = Chessboard::from_fen(fen, " ");
+            for move_ in board.legal_moves_vec(board.current_turn()) {
+                let san = to_san(&board, move_);
+                assert_eq!(
+                    parse_san(&board, &san).unwrap(),
+                    move_,
+                    "{} in {}",
+                    san,
+                    fen
+                );
+            }
+        }
+
+        let board = Chessboard::from_fen("4k3/8/8/8/1Q5Q/8/8/1Q2K2Q w - - 0 1", " ");
+        let san = |uci: &str| to_san(&board, parse_uci_move(uci).unwrap());
+        assert_eq!(san("b1e4"), "Qb1e4+");
+        assert_eq!(san("h4e7"), "Qhe7#");
+        assert_eq!(san("b4b8"), "Qb8+");
+        assert_eq!(san("h1h2"), "Q1h2");
+    }
+
+    #[test]
+    fn test_format_game_is_read_back() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 12";
+        let moves: Vec<Move> = ["e8d7", "e2e4", "d7e6"]
+            .iter()
+            .map(|uci| parse_uci_move(uci).unwrap())
+            .collect();
+        let tags = vec![("Event".to_string(), "Test \"quoted\"".to_string())];
+        let text = format_game(&tags, fen, &moves, "1/2-1/2");
+        assert!(text.contains("12... Kd7 13. e4 Ke6 1/2-1/2"), "{}", text);
+
+        let game = read_games(&text).next().unwrap().unwrap();
+        assert_eq!(game.tag("Event"), Some("Test \"quoted\""));
+        assert_eq!(game.start_fen, fen);
+        assert_eq!(game.moves, moves);
+        assert_eq!(game.result, Some(0.5));
+
+        let shuffle: Vec<Move> = ["g1f3", "g8f6", "f3g1", "f6g8"]
+            .iter()
+            .cycle()
+            .take(40)
+            .map(|uci| parse_uci_move(uci).unwrap())
+            .collect();
+        let long = format_game(&[], START_FEN, &shuffle, "*");
+        assert!(long.lines().count() > 3);
+        assert!(long.lines().all(|line| line.len() <= 80));
+    }
+
+    #[test]
+    fn test_read_games() {
+        let text = r#"
+[Event "Casual \"blitz\""]
+[WhiteElo "2400"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 (1... c5 2. Nf3 (2. c3) d6) 2. Nf3 $1 Nc6 ; line comment
+3.Bb5 a6 1-0
+
+[Event "Broken"]
+1. e4 e4 2. d4 *
+
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+[Result "1/2-1/2"]
+1. e3 Kd7
+"#;
+        let games: Vec<Result<PgnGame>> = read_games(text).collect();
+        assert_eq!(games.len(), 3);
+
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tag("event"), Some("Casual \"blitz\""));
+        assert_eq!(game.elo(WHITE_ID), Some(2400));
+        assert_eq!(game.elo(crate::constants::BLACK_ID), None);
+        assert_eq!(game.result, Some(1.0));
+        let moves: Vec<String> = game.moves.iter().map(Move::uci_move).collect();
+        assert_eq!(moves, ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6"]);
+
+        let error = format!("{:#}", games[1].as_ref().unwrap_err());
+        assert!(error.contains("game 2, move 2"), "{}", error);
+
+        // Without a termination marker the game ends at the text end, scored by its tag.
+        let game = games[2].as_ref().unwrap();
+        assert_eq!(game.result, Some(0.5));
+        assert_eq!(
+            game.moves,
+            [
+                parse_uci_move("e2e3").unwrap(),
+                parse_uci_move("e8d7").unwrap()
+            ]
+        );
+    }
+}
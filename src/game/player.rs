@@ -1,5 +1,291 @@
-use crate::game::chessboard::Chessboard;
+use std::fmt::{self, Display, Formatter};
+use std::io::{BufRead, Write};
 
+use anyhow::{anyhow, Result};
+
+use crate::constants::{opposite, BISHOP_ID, BLACK_ID, KING_ID, KNIGHT_ID, WHITE_ID};
+use crate::game::{chess_move::Move, chessboard::Chessboard, pgn};
+use crate::uci::protocol::parse_uci_move;
+
+/// Plies without capture or pawn move after which the game is drawn.
+const FIFTY_MOVE_PLIES: u32 = 100;
+
+/// Side of a game choosing its moves.
 pub trait Player {
-    fn next_move(&mut self, chessboard: Chessboard) -> u32;
+    /// Called before a new game starts.
+    fn new_game(&mut self) {}
+
+    /// Chooses the move to play in the position reached by playing `moves` from `start`.
+    ///
+    /// An error forfeits the game.
+    fn next_move(&mut self, start: &Chessboard, moves: &[Move]) -> Result<Move>;
+}
+
+/// Player choosing uniformly among the legal moves.
+pub struct RandomPlayer {
+    rng: fastrand::Rng,
+}
+
+impl RandomPlayer {
+    /// Creates a player seeded from the system.
+    pub fn new() -> Self {
+        Self {
+            rng: fastrand::Rng::new(),
+        }
+    }
+
+    /// Creates a player replaying the same choices for the same `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+}
+
+impl Default for RandomPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Player for RandomPlayer {
+    fn next_move(&mut self, start: &Chessboard, moves: &[Move]) -> Result<Move> {
+        let (board, _) = start.replay(moves);
+        let moves = board.legal_moves_vec(board.current_turn());
+        self.rng
+            .choice(moves)
+            .ok_or_else(|| anyhow!("no legal move"))
+    }
+}
+
+/// Player reading its moves, in UCI (`e2e4`) or standard algebraic (`Nf3`) notation, one
+/// per line of `input`.
+///
+/// Prompts and complaints about illegal moves are written to `output`, and the move is
+/// asked again.
+pub struct HumanPlayer<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> HumanPlayer<R, W> {
+    /// Creates a player reading from `input` and prompting on `output`.
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+}
+
+impl<R: BufRead, W: Write> Player for HumanPlayer<R, W> {
+    fn next_move(&mut self, start: &Chessboard, moves: &[Move]) -> Result<Move> {
+        let (board, _) = start.replay(moves);
+        let legal = board.legal_moves_vec(board.current_turn());
+        loop {
+            write!(self.output, "Your move: ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Err(anyhow!("end of input"));
+            }
+            let text = line.trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            let move_ = parse_uci_move(text)
+                .ok()
+                .filter(|move_| legal.contains(move_))
+                .or_else(|| pgn::parse_san(&board, text).ok());
+            match move_ {
+                Some(move_) => return Ok(move_),
+                None => writeln!(self.output, "Illegal move '{}'", text)?,
+            }
+        }
+    }
+}
+
+/// Final result of a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    /// Returns the result in which `color_id` wins.
+    pub fn win_for(color_id: u8) -> Self {
+        if color_id == WHITE_ID {
+            GameResult::WhiteWins
+        } else {
+            GameResult::BlackWins
+        }
+    }
+
+    /// Returns White's score: 1, 0.5 or 0.
+    pub fn white_score(&self) -> f64 {
+        match self {
+            GameResult::WhiteWins => 1.0,
+            GameResult::BlackWins => 0.0,
+            GameResult::Draw => 0.5,
+        }
+    }
+}
+
+/// Formats the result as a PGN termination marker (`1-0`, `0-1` or `1/2-1/2`).
+impl Display for GameResult {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let marker = match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        };
+        write!(f, "{}", marker)
+    }
+}
+
+/// Reason why a game ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+    /// The player to move chose an illegal move and loses.
+    IllegalMove(Move),
+    /// The player to move failed to choose a move and loses.
+    Forfeit(String),
+    /// The player to move exceeded its time and loses.
+    TimeForfeit,
+    /// The game was stopped and scored by the arbiter, for the given reason.
+    Adjudication(String),
+}
+
+/// Complete game played by [`play_game`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    /// Position the game started from.
+    pub start_fen: String,
+    /// Legal moves played, in order.
+    pub moves: Vec<Move>,
+    pub result: GameResult,
+    pub termination: Termination,
+}
+
+/// Returns whether neither side has enough material left to checkmate: bare kings, a
+/// single minor piece, or bishops all standing on squares of the same color.
+pub fn is_insufficient_material(board: &Chessboard) -> bool {
+    let mut minors = 0;
+    let mut bishop_colors = [false; 2];
+    for (index, (piece_id, _)) in board.get_iterator_on_pieces() {
+        match piece_id {
+            KING_ID => {}
+            KNIGHT_ID => minors += 1,
+            BISHOP_ID => {
+                minors += 1;
+                let (row, col) = (index / 8, index % 8);
+                bishop_colors[((row + col) % 2) as usize] = true;
+            }
+            _ => return false,
+        }
+    }
+
+    let bishops_only =
+        board.piece_board(KNIGHT_ID, WHITE_ID) == 0 && board.piece_board(KNIGHT_ID, BLACK_ID) == 0;
+    minors <= 1 || (bishops_only && !(bishop_colors[0] && bishop_colors[1]))
+}
+
+/// Returns how the game ends in `board`, or `None` when it goes on.
+///
+/// `history` holds the Zobrist keys of the earlier positions of the game, oldest first.
+/// Repetitions and the fifty-move rule end the game as soon as they occur, as if a
+/// player claimed the draw.
+pub fn game_end(board: &Chessboard, history: &[u64]) -> Option<(GameResult, Termination)> {
+    let color_id = board.current_turn();
+    if board.legal_moves_vec(color_id).is_empty() {
+        return Some(if board.is_king_attacked(color_id) {
+            (
+                GameResult::win_for(opposite(color_id)),
+                Termination::Checkmate,
+            )
+        } else {
+            (GameResult::Draw, Termination::Stalemate)
+        });
+    }
+
+    let key = board.zobrist_key();
+    if history.iter().filter(|&&earlier| earlier == key).count() >= 2 {
+        return Some((GameResult::Draw, Termination::ThreefoldRepetition));
+    }
+    if board.halfmove_clock() >= FIFTY_MOVE_PLIES {
+        return Some((GameResult::Draw, Termination::FiftyMoveRule));
+    }
+    if is_insufficient_material(board) {
+        return Some((GameResult::Draw, Termination::InsufficientMaterial));
+    }
+    None
+}
+
+/// Plays a game between `white` and `black` from `start_fen` until it ends.
+///
+/// Every move is checked for legality: a player choosing an illegal move, or failing to
+/// choose one, loses the game.
+pub fn play_game(white: &mut dyn Player, black: &mut dyn Player, start_fen: &str) -> GameRecord {
+    white.new_game();
+    black.new_game();
+
+    let start = Chessboard::from_fen(start_fen, " ");
+    let mut board = start.clone();
+    let mut history = Vec::new();
+    let mut moves = Vec::new();
+    let (result, termination) = loop {
+        if let Some(end) = game_end(&board, &history) {
+            break end;
+        }
+
+        let color_id = board.current_turn();
+        let choice = if color_id == WHITE_ID {
+            white.next_move(&start, &moves)
+        } else {
+            black.next_move(&start, &moves)
+        };
+        let loss = GameResult::win_for(opposite(color_id));
+        let move_ = match choice {
+            Ok(move_) => move_,
+            Err(err) => break (loss, Termination::Forfeit(format!("{:#}", err))),
+        };
+        if !board.legal_moves_vec(color_id).contains(&move_) {
+            break (loss, Termination::IllegalMove(move_));
+        }
+
+        history.push(board.zobrist_key());
+        board.make_move_unchecked(move_);
+        moves.push(move_);
+    };
+
+    GameRecord {
+        start_fen: start_fen.to_string(),
+        moves,
+        result,
+        termination,
+    }
+}
+
+/// Formats the end of a game for humans, like `1-0 (checkmate)`.
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let reason = match &self.termination {
+            Termination::Checkmate => "checkmate".to_string(),
+            Termination::Stalemate => "stalemate".to_string(),
+            Termination::ThreefoldRepetition => "threefold repetition".to_string(),
+            Termination::FiftyMoveRule => "fifty-move rule".to_string(),
+            Termination::InsufficientMaterial => "insufficient material".to_string(),
+            Termination::IllegalMove(move_) => format!("illegal move {}", move_),
+            Termination::Forfeit(reason) => format!("forfeit: {}", reason),
+            Termination::TimeForfeit => "time forfeit".to_string(),
+            Termination::Adjudication(reason) => format!("adjudication: {}", reason),
+        };
+        write!(f, "{} ({})", self.result, reason)
+    }
 }
@@ -21,13 +21,16 @@ pub fn string_to_square(s: &str) -> Option<(u32, u32)> {
 
     // Convert the first character to a zero-based index (assuming it is a letter)
     let alphabet_index = match first_char {
-        'a'..='z' => (first_char as u32) - ('a' as u32),
-        _ => return None, // Return None if the first character is not a letter
+        'a'..='h' => (first_char as u32) - ('a' as u32),
+        _ => return None, // Return None if the first character is not a file
     };
 
-    let digit_value = second_char.to_digit(10).map(|d| 8 - d);
+    let digit_value = second_char
+        .to_digit(10)
+        .filter(|d| (1..=8).contains(d))
+        .map(|d| 8 - d);
 
-    // Return None if the second character is not a digit
+    // Return None if the second character is not a rank
     digit_value.map(|d| (d, alphabet_index))
 }
 
@@ -80,6 +83,25 @@ pub fn south_one(bits: u64) -> u64 {
     bits << 8
 }
 
+/// Extends every bit of `board` to the north edge of the board.
+pub fn north_fill(board: u64) -> u64 {
+    let mut fill = board | board >> 8;
+    fill |= fill >> 16;
+    fill | fill >> 32
+}
+
+/// Extends every bit of `board` to the south edge of the board.
+pub fn south_fill(board: u64) -> u64 {
+    let mut fill = board | board << 8;
+    fill |= fill << 16;
+    fill | fill << 32
+}
+
+/// Extends every bit of `board` to its whole file.
+pub fn file_fill(board: u64) -> u64 {
+    north_fill(board) | south_fill(board)
+}
+
 /// Masks `board` by a specific `(row, col)` coordinate.
 pub fn mask_row_col(board: u64, row: i32, col: i32) -> u64 {
     board & (A_FILE_MASK >> row) & (RANK_1_MASK >> col)
@@ -0,0 +1,74 @@
+/// Random keys XOR-ed together to hash a position.
+///
+/// Keys are generated at compile time from a fixed seed, so hashes are stable across runs.
+pub struct ZobristKeys {
+    /// Keys indexed by `[color_id][piece_id][square index]`.
+    pub pieces: [[[u64; 64]; 7]; 2],
+    /// Key toggled when black is to move.
+    pub black_to_move: u64,
+    /// Keys indexed by the 4 castle-right bits of the bitboard flags.
+    pub castling: [u64; 16],
+    /// Keys indexed by the file of the en passant square.
+    pub en_passant: [u64; 8],
+}
+
+/// SplitMix64 step, returning the next state and its output.
+const fn split_mix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_keys() -> ZobristKeys {
+    let mut state = 0x0d1e_5eed_u64;
+    let mut keys = ZobristKeys {
+        pieces: [[[0; 64]; 7]; 2],
+        black_to_move: 0,
+        castling: [0; 16],
+        en_passant: [0; 8],
+    };
+
+    let mut color = 0;
+    while color < 2 {
+        let mut piece = 0;
+        while piece < 7 {
+            let mut square = 0;
+            while square < 64 {
+                let (next, key) = split_mix(state);
+                state = next;
+                keys.pieces[color][piece][square] = key;
+                square += 1;
+            }
+            piece += 1;
+        }
+        color += 1;
+    }
+
+    let (next, key) = split_mix(state);
+    state = next;
+    keys.black_to_move = key;
+
+    // Combinations of castle rights get a key each so that `flags & 0b1111` indexes them.
+    let mut castle = 1;
+    while castle < 16 {
+        let (next, key) = split_mix(state);
+        state = next;
+        keys.castling[castle] = key;
+        castle += 1;
+    }
+
+    let mut file = 0;
+    while file < 8 {
+        let (next, key) = split_mix(state);
+        state = next;
+        keys.en_passant[file] = key;
+        file += 1;
+    }
+
+    keys
+}
+
+/// Zobrist keys shared by every hashed board.
+pub static ZOBRIST: ZobristKeys = generate_keys();
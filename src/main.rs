@@ -1,31 +1,113 @@
 use anyhow::Result;
-use odyn::{
-    engine::implementations::{odyn_engine::OdynEngine, odyn_evaluator::OdynEvaluator},
-    uci::protocol::UciWrapper,
+use clap::{Arg, ArgMatches, Command};
+use odyn::constants::{EVAL_COMMAND, POSITION_COMMAND};
+use odyn::engine::{
+    engine::ChessEngine,
+    implementations::{
+        nnue::EVAL_FILE_OPTION,
+        odyn_engine::OdynEngine,
+        selectable_evaluator::{
+            EvaluatorKind, SelectableEvaluator, EVALUATOR_OPTION, PARAMS_FILE_OPTION,
+        },
+    },
 };
-use std::io::{self, BufRead};
+use odyn::uci::protocol::UciWrapper;
+use std::{
+    io::{self, BufRead},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// Reads stdin on its own thread so that commands keep flowing during a search.
+fn spawn_stdin_reader() -> Receiver<io::Result<String>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Parses the command-line flags choosing the initial evaluator.
+///
+/// They set the same engine options a GUI can change later with `setoption`. The `eval`
+/// subcommand prints the evaluation of a position instead of speaking UCI.
+fn parse_args() -> ArgMatches {
+    let kinds: Vec<&'static str> = EvaluatorKind::ALL.iter().map(|kind| kind.name()).collect();
+    Command::new("odyn")
+        .about("Odyn UCI chess engine")
+        .arg(
+            Arg::new("evaluator")
+                .long("evaluator")
+                .value_parser(kinds)
+                .ignore_case(true)
+                .default_value(EvaluatorKind::default().name())
+                .help("Evaluation function used by the search"),
+        )
+        .arg(
+            Arg::new("params-file")
+                .long("params-file")
+                .value_name("PATH")
+                .help("Parameter file of the tapered evaluator"),
+        )
+        .arg(
+            Arg::new("eval-file")
+                .long("eval-file")
+                .value_name("PATH")
+                .help("Weight file of the NNUE evaluator"),
+        )
+        .subcommand(
+            Command::new(EVAL_COMMAND)
+                .about("Print the evaluation of a position term by term")
+                .arg(
+                    Arg::new("fen")
+                        .long("fen")
+                        .value_name("FEN")
+                        .help("Position to evaluate, the start position by default"),
+                )
+                .arg(
+                    Arg::new("moves")
+                        .long("moves")
+                        .num_args(1..)
+                        .value_name("MOVE")
+                        .help("Moves played from the position, in UCI notation"),
+                ),
+        )
+        .get_matches()
+}
 
 fn main() -> Result<()> {
-    let stdin = io::stdin();
+    let args = parse_args();
     let mut stdout = io::stdout();
 
-    let engine = OdynEngine::new(OdynEvaluator);
+    let mut engine = OdynEngine::new(SelectableEvaluator::default());
+    if let Some(evaluator) = args.get_one::<String>("evaluator") {
+        engine.set_option(EVALUATOR_OPTION, evaluator)?;
+    }
+    if let Some(path) = args.get_one::<String>("params-file") {
+        engine.set_option(PARAMS_FILE_OPTION, path)?;
+    }
+    if let Some(path) = args.get_one::<String>("eval-file") {
+        engine.set_option(EVAL_FILE_OPTION, path)?;
+    }
     let mut uci = UciWrapper::new(engine);
 
-    for line in stdin.lock().lines() {
-        let line = line?;
-        let quit = match uci.handle_line(&line, &mut stdout) {
-            Ok(quit) => quit,
-            Err(err) => {
-                // Don't crash on malformed UCI input; lichess-bot/GUIs will keep going.
-                eprintln!("UCI error: {err:#}");
-                false
-            }
+    if let Some(eval) = args.subcommand_matches(EVAL_COMMAND) {
+        let mut position = match eval.get_one::<String>("fen") {
+            Some(fen) => format!("{} fen {}", POSITION_COMMAND, fen),
+            None => format!("{} startpos", POSITION_COMMAND),
         };
-        if quit {
-            break;
+        if let Some(moves) = eval.get_many::<String>("moves") {
+            let moves: Vec<&str> = moves.map(String::as_str).collect();
+            position = format!("{} moves {}", position, moves.join(" "));
         }
+        uci.handle_line(&position, &mut stdout)?;
+        uci.handle_line(EVAL_COMMAND, &mut stdout)?;
+        return Ok(());
     }
 
-    Ok(())
+    uci.run(spawn_stdin_reader(), &mut stdout)
 }
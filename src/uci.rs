@@ -1,5 +1,7 @@
 //! UCI protocol entry points and command configuration.
 
+/// UCI client driving external engine processes.
+pub mod client;
 /// Clap command definitions used by the CLI wrapper.
 pub mod commands;
 /// UCI protocol parser and command dispatcher.
@@ -14,6 +16,7 @@ pub fn init_cli() -> Result<clap::Command> {
         .about("Odyn is a (badly programmed) chess engine")
         .author("MichelDucartier")
         .subcommand(commands::init_position_command())
+        .subcommand(commands::init_eval_command())
         .subcommand(commands::init_quit_command());
 
     Ok(cmd)
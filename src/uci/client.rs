@@ -0,0 +1,631 @@
+//! Client side of the UCI protocol: drives an external engine process.
+//!
+//! [`UciClient`] is the counterpart of [`UciWrapper`](crate::uci::protocol::UciWrapper):
+//! it writes the commands the wrapper reads and parses the lines it prints, so that any
+//! UCI engine can be used wherever a [`ChessEngine`] is expected.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    engine::{
+        engine::ChessEngine,
+        options::{parse_check, parse_spin, EngineOption, OptionKind},
+        search::{InfoSink, Score, SearchInfo, SearchLimits, SearchResult},
+    },
+    game::{chess_move::Move, chessboard::Chessboard},
+    uci::protocol::parse_uci_move,
+};
+
+/// Time allowed to answer `uci` and `isready`.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time allowed to answer `stop` with `bestmove` before the engine is given up on.
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often the stop flag is checked while waiting for `bestmove`.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(2);
+/// Time given to the engine to exit after `quit` before it is killed.
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+/// UCI name of the option setting the number of lines searched.
+const MULTIPV_OPTION: &str = "MultiPV";
+
+/// External UCI engine running as a child process.
+///
+/// The handshake is performed on [`UciClient::spawn`], which records the name and options
+/// the engine advertises. Searches are answered as soon as the engine prints `bestmove`;
+/// an engine that exits or ignores `stop` makes them return without a move. The late
+/// `bestmove` of such a search is discarded before the next one, and an engine that never
+/// sends it is killed.
+pub struct UciClient {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines printed by the engine, read on their own thread.
+    lines: Receiver<String>,
+    name: String,
+    author: String,
+    options: Vec<EngineOption>,
+    /// Value of MultiPV last sent, or advertised by the engine.
+    multipv: String,
+    /// `position` command describing the position to search.
+    position: String,
+    /// Whether a search was given up on before the engine answered it.
+    awaiting_bestmove: bool,
+}
+
+impl UciClient {
+    /// Launches `command` with `args` and performs the UCI handshake.
+    pub fn spawn(command: &Path, args: &[String]) -> Result<Self> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("cannot start {}", command.display()))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut client = Self {
+            child,
+            stdin,
+            lines,
+            name: String::new(),
+            author: String::new(),
+            options: Vec::new(),
+            multipv: "1".to_string(),
+            position: "position startpos".to_string(),
+            awaiting_bestmove: false,
+        };
+        client
+            .handshake()
+            .with_context(|| format!("{} does not speak UCI", command.display()))?;
+        Ok(client)
+    }
+
+    /// Returns the name sent by the engine in `id name`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the author sent by the engine in `id author`.
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    /// Returns whether the engine process is still running.
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Waits until the engine has processed every command sent so far.
+    pub fn sync(&mut self) -> Result<()> {
+        self.send("isready")?;
+        self.wait_for("readyok", HANDSHAKE_TIMEOUT)
+    }
+
+    fn handshake(&mut self) -> Result<()> {
+        self.send("uci")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = self.recv_until(deadline, "uciok")?;
+            let line = line.trim();
+            if line == "uciok" {
+                return Ok(());
+            } else if let Some(name) = line.strip_prefix("id name ") {
+                self.name = name.trim().to_string();
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                self.author = author.trim().to_string();
+            } else if line.starts_with("option ") {
+                // Options we cannot make sense of are left alone rather than failing.
+                if let Ok(option) = parse_option(line) {
+                    if let OptionKind::Spin { default, .. } = option.kind {
+                        if option.name.eq_ignore_ascii_case(MULTIPV_OPTION) {
+                            self.multipv = default.to_string();
+                        }
+                    }
+                    self.options.push(option);
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, command: &str) -> Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    /// Receives the next line, failing once `deadline` has passed while waiting for
+    /// `expected`.
+    fn recv_until(&mut self, deadline: Instant, expected: &str) -> Result<String> {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(left) {
+            Ok(line) => {
+                if line.split_whitespace().next() == Some("bestmove") {
+                    self.awaiting_bestmove = false;
+                }
+                Ok(line)
+            }
+            Err(RecvTimeoutError::Timeout) => Err(anyhow!("no '{}' in time", expected)),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(anyhow!("engine exited while waiting for '{}'", expected))
+            }
+        }
+    }
+
+    /// Reads lines until one equals `token`.
+    fn wait_for(&mut self, token: &str, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        while self.recv_until(deadline, token)?.trim() != token {}
+        Ok(())
+    }
+
+    /// Reads the answer of a search given up on, so that it is not taken for the answer
+    /// of the next one.
+    fn discard_late_bestmove(&mut self) -> Result<()> {
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while self.awaiting_bestmove {
+            self.recv_until(deadline, "bestmove")?;
+        }
+        Ok(())
+    }
+
+    fn find_option(&self, name: &str) -> Option<&EngineOption> {
+        self.options
+            .iter()
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Drop for UciClient {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl ChessEngine for UciClient {
+    fn new_game(&mut self) {
+        let _ = self.send("ucinewgame").and_then(|_| self.sync());
+    }
+
+    /// Sends the moves along with the start position, so that the engine knows the
+    /// positions repeated along the way.
+    fn set_position(&mut self, start: &Chessboard, moves: &[Move]) {
+        let mut position = format!("position fen {}", start.to_fen(" "));
+        if !moves.is_empty() {
+            position.push_str(" moves");
+            for move_ in moves {
+                position.push_str(&format!(" {}", move_));
+            }
+        }
+        self.position = position;
+    }
+
+    fn search(
+        &mut self,
+        limits: SearchLimits,
+        info: &dyn InfoSink,
+        stop: &AtomicBool,
+    ) -> SearchResult {
+        if self.discard_late_bestmove().is_err() {
+            let _ = self.child.kill();
+            return SearchResult::default();
+        }
+        let position = self.position.clone();
+        if self.send(&position).is_err() || self.send(&format_go(&limits)).is_err() {
+            return SearchResult::default();
+        }
+
+        // Latest score and principal variation of every line, by MultiPV rank.
+        let mut lines: Vec<(Option<Score>, Vec<Move>)> = Vec::new();
+        let mut stopped_at = None;
+        loop {
+            if stopped_at.is_none() && stop.load(Ordering::Relaxed) {
+                if self.send("stop").is_err() {
+                    return SearchResult::default();
+                }
+                stopped_at = Some(Instant::now());
+            }
+            if stopped_at.is_some_and(|at| at.elapsed() > STOP_TIMEOUT) {
+                self.awaiting_bestmove = true;
+                return SearchResult::default();
+            }
+
+            let line = match self.lines.recv_timeout(STOP_POLL_INTERVAL) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return SearchResult::default(),
+            };
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens.first() {
+                Some(&"info") => {
+                    let report = parse_info(&line);
+                    let rank = report.multipv.unwrap_or(1).max(1) as usize;
+                    if report.score.is_some() || !report.pv.is_empty() {
+                        if lines.len() < rank {
+                            lines.resize(rank, (None, Vec::new()));
+                        }
+                        let (score, pv) = &mut lines[rank - 1];
+                        *score = report.score.or(*score);
+                        if !report.pv.is_empty() {
+                            *pv = report.pv.clone();
+                        }
+                    }
+                    info.info(&report);
+                }
+                Some(&"bestmove") => {
+                    let parse = |token: Option<&&str>| {
+                        token
+                            .filter(|&&token| token != "0000" && token != "(none)")
+                            .and_then(|token| parse_uci_move(token).ok())
+                    };
+                    let best_move = parse(tokens.get(1));
+                    let ponder_move = (tokens.get(2) == Some(&"ponder"))
+                        .then(|| parse(tokens.get(3)))
+                        .flatten();
+                    return search_result(best_move, ponder_move, lines);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn analyze(
+        &mut self,
+        limits: SearchLimits,
+        lines: usize,
+        info: &dyn InfoSink,
+        stop: &AtomicBool,
+    ) -> Vec<(Move, Score, Vec<Move>)> {
+        if lines <= 1 || self.find_option(MULTIPV_OPTION).is_none() {
+            return self.search(limits, info, stop).lines;
+        }
+        let previous = self.multipv.clone();
+        let _ = self.set_option(MULTIPV_OPTION, &lines.to_string());
+        let result = self.search(limits, info, stop);
+        let _ = self.set_option(MULTIPV_OPTION, &previous);
+        result.lines
+    }
+
+    /// Checks `value` against the option advertised by the engine before sending it.
+    fn set_option(&mut self, name: &str, value: &str) -> Result<()> {
+        let option = self
+            .find_option(name)
+            .ok_or_else(|| anyhow!("unknown option '{}'", name))?
+            .clone();
+        match &option.kind {
+            OptionKind::Spin { min, max, .. } => {
+                parse_spin(&option.name, value, *min, *max)?;
+            }
+            OptionKind::Check { .. } => {
+                parse_check(&option.name, value)?;
+            }
+            OptionKind::Combo { vars, .. } => {
+                if !vars
+                    .iter()
+                    .any(|var| var.eq_ignore_ascii_case(value.trim()))
+                {
+                    return Err(anyhow!(
+                        "option {}: '{}' is not one of {}",
+                        option.name,
+                        value,
+                        vars.join(", ")
+                    ));
+                }
+            }
+            OptionKind::Button | OptionKind::String { .. } => {}
+        }
+
+        let command = match option.kind {
+            OptionKind::Button => format!("setoption name {}", option.name),
+            _ => format!("setoption name {} value {}", option.name, value.trim()),
+        };
+        self.send(&command)?;
+        if option.name.eq_ignore_ascii_case(MULTIPV_OPTION) {
+            self.multipv = value.trim().to_string();
+        }
+        Ok(())
+    }
+
+    fn options(&self) -> Vec<EngineOption> {
+        self.options.clone()
+    }
+}
+
+/// Assembles the result of a search from the `bestmove` answer and the last lines
+/// reported.
+fn search_result(
+    best_move: Option<Move>,
+    ponder_move: Option<Move>,
+    lines: Vec<(Option<Score>, Vec<Move>)>,
+) -> SearchResult {
+    let lines: Vec<(Move, Score, Vec<Move>)> = lines
+        .into_iter()
+        .filter_map(|(score, pv)| Some((*pv.first()?, score?, pv)))
+        .collect();
+    let main_line = lines
+        .first()
+        .filter(|(move_, _, _)| Some(*move_) == best_move);
+    let pv = match (main_line, best_move) {
+        (Some((_, _, pv)), _) => pv.clone(),
+        (None, Some(best_move)) => vec![best_move],
+        (None, None) => Vec::new(),
+    };
+    SearchResult {
+        best_move,
+        ponder_move: ponder_move.or_else(|| pv.get(1).copied()),
+        score: lines.first().map(|(_, score, _)| *score),
+        pv,
+        lines,
+    }
+}
+
+/// Formats search limits as a UCI `go` command.
+pub fn format_go(limits: &SearchLimits) -> String {
+    let mut command = String::from("go");
+    if limits.ponder {
+        command.push_str(" ponder");
+    }
+    let times = [
+        ("wtime", limits.wtime),
+        ("btime", limits.btime),
+        ("winc", limits.winc),
+        ("binc", limits.binc),
+        ("movetime", limits.movetime),
+    ];
+    for (name, time) in times {
+        if let Some(time) = time {
+            command.push_str(&format!(" {} {}", name, time.as_millis()));
+        }
+    }
+    if let Some(movestogo) = limits.movestogo {
+        command.push_str(&format!(" movestogo {}", movestogo));
+    }
+    if let Some(depth) = limits.depth {
+        command.push_str(&format!(" depth {}", depth));
+    }
+    if let Some(nodes) = limits.nodes {
+        command.push_str(&format!(" nodes {}", nodes));
+    }
+    if let Some(mate) = limits.mate {
+        command.push_str(&format!(" mate {}", mate));
+    }
+    if limits.infinite {
+        command.push_str(" infinite");
+    }
+    command
+}
+
+/// Parses a UCI `info` line. Unknown fields and malformed values are skipped.
+pub fn parse_info(line: &str) -> SearchInfo {
+    let mut info = SearchInfo::default();
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let mut index = usize::from(tokens.first() == Some(&"info"));
+    let value = |index: usize| tokens.get(index + 1).copied().unwrap_or_default();
+
+    while index < tokens.len() {
+        match tokens[index] {
+            "depth" => info.depth = value(index).parse().ok(),
+            "seldepth" => info.seldepth = value(index).parse().ok(),
+            "multipv" => info.multipv = value(index).parse().ok(),
+            "nodes" => info.nodes = value(index).parse().ok(),
+            "nps" => info.nps = value(index).parse().ok(),
+            "hashfull" => info.hashfull = value(index).parse().ok(),
+            "currmovenumber" => info.currmovenumber = value(index).parse().ok(),
+            "time" => info.time = value(index).parse().ok().map(Duration::from_millis),
+            "currmove" => info.currmove = parse_uci_move(value(index)).ok(),
+            "score" => {
+                let number = tokens.get(index + 2).and_then(|value| value.parse().ok());
+                info.score = match (value(index), number) {
+                    ("cp", Some(value)) => Some(Score::Centipawns(value)),
+                    ("mate", Some(value)) => Some(Score::Mate(value)),
+                    _ => None,
+                };
+                index += 1;
+            }
+            "pv" => {
+                info.pv = tokens[index + 1..]
+                    .iter()
+                    .map_while(|token| parse_uci_move(token).ok())
+                    .collect();
+                break;
+            }
+            "string" => {
+                info.string = Some(tokens[index + 1..].join(" "));
+                break;
+            }
+            _ => {
+                index += 1;
+                continue;
+            }
+        }
+        index += 2;
+    }
+    info
+}
+
+/// Parses a UCI `option` line into the option it advertises.
+pub fn parse_option(line: &str) -> Result<EngineOption> {
+    const KEYWORDS: [&str; 5] = ["type", "default", "min", "max", "var"];
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.first() != Some(&"option") || tokens.get(1) != Some(&"name") {
+        return Err(anyhow!("expected 'option name' in '{}'", line));
+    }
+
+    // Every keyword is followed by a value running until the next keyword: names,
+    // defaults and vars may hold spaces.
+    let mut name = Vec::new();
+    let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+    for &token in &tokens[2..] {
+        if KEYWORDS.contains(&token) {
+            fields.push((token, Vec::new()));
+        } else if let Some((_, value)) = fields.last_mut() {
+            value.push(token);
+        } else {
+            name.push(token);
+        }
+    }
+    let name = name.join(" ");
+    if name.is_empty() {
+        return Err(anyhow!("missing option name in '{}'", line));
+    }
+    let field = |keyword: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| *key == keyword)
+            .map(|(_, value)| value.join(" "))
+    };
+    let number = |keyword: &str| -> Result<i64> {
+        let value =
+            field(keyword).ok_or_else(|| anyhow!("option {}: missing {}", name, keyword))?;
+        value
+            .parse()
+            .map_err(|_| anyhow!("option {}: invalid {} '{}'", name, keyword, value))
+    };
+    let default = field("default").unwrap_or_default();
+
+    let kind = match field("type").as_deref() {
+        Some("check") => OptionKind::Check {
+            default: parse_check(&name, &default)?,
+        },
+        Some("spin") => OptionKind::Spin {
+            default: number("default")?,
+            min: number("min")?,
+            max: number("max")?,
+        },
+        Some("combo") => OptionKind::Combo {
+            default,
+            vars: fields
+                .iter()
+                .filter(|(key, _)| *key == "var")
+                .map(|(_, value)| value.join(" "))
+                .collect(),
+        },
+        Some("button") => OptionKind::Button,
+        Some("string") => OptionKind::String {
+            default: if default == "<empty>" {
+                String::new()
+            } else {
+                default
+            },
+        },
+        _ => return Err(anyhow!("option {}: unknown type in '{}'", name, line)),
+    };
+    Ok(EngineOption { name, kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci::protocol::{format_info, format_option};
+
+    #[test]
+    fn test_parse_option_reads_format_option() {
+        let options = [
+            EngineOption::check("Ponder", false),
+            EngineOption::spin("Skill Level", 20, 0, 20),
+            EngineOption::spin("Contempt", 0, -100, 100),
+            EngineOption::combo("Evaluator", "Material", &["Material", "Tapered", "NNUE"]),
+            EngineOption::string("BookFile", ""),
+            EngineOption::string("Book File", "my book.bin"),
+            EngineOption::button("Clear Hash"),
+        ];
+        for option in options {
+            assert_eq!(parse_option(&format_option(&option)).unwrap(), option);
+        }
+        assert!(parse_option("option name Hash type spin default 16").is_err());
+        assert!(parse_option("option name Hash type table").is_err());
+        assert!(parse_option("id name Hash").is_err());
+    }
+
+    #[test]
+    fn test_parse_info_reads_format_info() {
+        let pv = vec![
+            parse_uci_move("e2e4").unwrap(),
+            parse_uci_move("e7e5").unwrap(),
+        ];
+        let reports = [
+            SearchInfo {
+                depth: Some(3),
+                seldepth: Some(5),
+                multipv: Some(2),
+                score: Some(Score::Mate(-2)),
+                nodes: Some(1200),
+                nps: Some(60000),
+                time: Some(Duration::from_millis(20)),
+                hashfull: Some(7),
+                pv,
+                ..Default::default()
+            },
+            SearchInfo {
+                depth: Some(8),
+                currmove: Some(parse_uci_move("e2e4").unwrap()),
+                currmovenumber: Some(4),
+                ..Default::default()
+            },
+            SearchInfo {
+                string: Some("mate in 3 refuted".to_string()),
+                ..Default::default()
+            },
+        ];
+        for report in reports {
+            assert_eq!(parse_info(&format_info(&report)), report);
+        }
+
+        let info = parse_info("info depth 7 score cp 13 lowerbound tbhits 0 pv g1f3");
+        assert_eq!(info.score, Some(Score::Centipawns(13)));
+        assert_eq!(info.pv, vec![parse_uci_move("g1f3").unwrap()]);
+    }
+
+    #[test]
+    fn test_format_go() {
+        assert_eq!(format_go(&SearchLimits::default()), "go");
+        let limits = SearchLimits {
+            wtime: Some(Duration::from_millis(60000)),
+            btime: Some(Duration::from_millis(59000)),
+            winc: Some(Duration::from_millis(100)),
+            binc: Some(Duration::from_millis(100)),
+            movestogo: Some(20),
+            depth: Some(12),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_go(&limits),
+            "go wtime 60000 btime 59000 winc 100 binc 100 movestogo 20 depth 12"
+        );
+        let limits = SearchLimits {
+            infinite: true,
+            ..Default::default()
+        };
+        assert_eq!(format_go(&limits), "go infinite");
+    }
+}
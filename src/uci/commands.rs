@@ -20,3 +20,8 @@ pub fn init_position_command() -> Command {
 pub fn init_quit_command() -> Command {
     Command::new(constants::QUIT_COMMAND).about("Quit Odyn CLI")
 }
+
+/// Builds the `eval` subcommand used by the CLI mode.
+pub fn init_eval_command() -> Command {
+    Command::new(constants::EVAL_COMMAND).about("Print the evaluation of the position term by term")
+}
@@ -1,10 +1,29 @@
 use crate::constants::{self, START_FEN, UCI_OK};
-use crate::engine::engine::ChessEngine;
+use crate::engine::{
+    engine::ChessEngine,
+    evaluator::{EvalTrace, PhaseScore},
+    implementations::{
+        book::{Book, BOOK_FILE_OPTION, BOOK_VARIETY_OPTION, MAX_BOOK_VARIETY, OWN_BOOK_OPTION},
+        pst_evaluator::MAX_PHASE,
+    },
+    options::{self, EngineOption, OptionKind},
+    search::{Score, SearchInfo, SearchLimits, SearchResult, StopTimer},
+};
 use crate::game::chess_move::Move;
 use crate::game::utility;
-use crate::game::{chessboard::Chessboard, perft};
+use crate::game::{
+    chessboard::{format_chessboard, Chessboard},
+    perft,
+};
 use anyhow::{anyhow, Result};
-use std::io::Write;
+use std::io::{self, Write};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    Arc, Mutex, MutexGuard,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 /// Current position state tracked by the UCI wrapper.
 #[derive(Debug, Clone)]
@@ -24,25 +43,177 @@ impl Default for PositionState {
     }
 }
 
+impl PositionState {
+    /// Builds the current board along with the Zobrist keys of the positions reached
+    /// before it, oldest first.
+    pub fn board_with_history(&self) -> (Chessboard, Vec<u64>) {
+        Chessboard::from_fen(&self.fen, " ").replay(&self.moves)
+    }
+}
+
+/// Name of the option telling the engine whether the GUI may send `go ponder`.
+const PONDER_OPTION: &str = "Ponder";
+
+/// How often [`UciWrapper::run`] flushes search output while the engine is thinking.
+pub const OUTPUT_POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+/// Search running on a worker thread.
+struct SearchHandle {
+    /// Raised to make the engine return as soon as possible.
+    stop: Arc<AtomicBool>,
+    /// Set while the GUI has not confirmed the pondered move with `ponderhit`.
+    pondering: Arc<AtomicBool>,
+    /// Limits of the `go` command that started the search.
+    limits: SearchLimits,
+    /// Side to move in the searched position.
+    color_id: u8,
+    /// Timer raising `stop` once the time budget is spent, cancelled with the search.
+    timer: Option<StopTimer>,
+    thread: JoinHandle<()>,
+}
+
+impl SearchHandle {
+    /// Raises the stop flag once `budget` has elapsed.
+    fn arm_timer(&mut self, budget: Duration) {
+        self.timer = Some(StopTimer::start(Arc::clone(&self.stop), budget));
+    }
+}
+
 /// Minimal UCI command loop adapter around a [`ChessEngine`].
-pub struct UciWrapper<T: ChessEngine> {
-    engine: T,
+///
+/// Searches run on a worker thread so that `stop`, `ponderhit`, `isready` and `quit` are
+/// handled while the engine is thinking. Lines produced by the worker (such as `bestmove`)
+/// are buffered and written to the `out` writer of the next call to
+/// [`UciWrapper::handle_line`] or [`UciWrapper::flush_output`].
+///
+/// The wrapper owns the `Ponder` option: when it is enabled, `bestmove` also names the
+/// reply the engine expects, which the GUI may then ask to ponder on with `go ponder`.
+///
+/// It also owns the opening book options: with `OwnBook` enabled and a Polyglot book set
+/// through `BookFile`, `go` is answered from the book without invoking the engine whenever
+/// the position is in it.
+pub struct UciWrapper<T: ChessEngine + Send + 'static> {
+    engine: Arc<Mutex<T>>,
+    /// Options listed by the engine, kept here so that `uci` does not wait for a search
+    /// holding the engine.
+    engine_options: Vec<EngineOption>,
     position: PositionState,
+    /// Value of the `Ponder` option.
+    ponder: bool,
+    /// Value of the `OwnBook` option.
+    own_book: bool,
+    /// Book loaded from the `BookFile` option.
+    book: Option<Book>,
+    /// Value of the `BookVariety` option.
+    book_variety: u8,
+    search: Option<SearchHandle>,
+    output_tx: Sender<String>,
+    output_rx: Receiver<String>,
 }
 
-impl<T: ChessEngine> UciWrapper<T> {
+impl<T: ChessEngine + Send + 'static> UciWrapper<T> {
     /// Creates a new wrapper with default `startpos` state.
     pub fn new(engine: T) -> Self {
+        let (output_tx, output_rx) = mpsc::channel();
         Self {
-            engine,
+            engine_options: engine.options(),
+            engine: Arc::new(Mutex::new(engine)),
             position: PositionState::default(),
+            ponder: false,
+            own_book: false,
+            book: None,
+            book_variety: MAX_BOOK_VARIETY,
+            search: None,
+            output_tx,
+            output_rx,
         }
     }
 
+    /// Returns whether a search is running, or has finished without its output being
+    /// flushed yet.
+    pub fn is_searching(&self) -> bool {
+        self.search.is_some()
+    }
+
+    /// Writes every line produced by the search worker since the last call.
+    pub fn flush_output(&mut self, out: &mut dyn Write) -> Result<()> {
+        if self
+            .search
+            .as_ref()
+            .is_some_and(|search| search.thread.is_finished())
+        {
+            self.join_search();
+        }
+
+        for line in self.output_rx.try_iter() {
+            writeln!(out, "{}", line)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Blocks until the running search finishes, then flushes its output.
+    ///
+    /// Searches that would wait for `stop` (`go infinite`, or `go ponder` before
+    /// `ponderhit`) are stopped instead, so this never blocks forever.
+    pub fn wait_for_search(&mut self, out: &mut dyn Write) -> Result<()> {
+        let waits_for_stop = self.search.as_ref().is_some_and(|search| {
+            search.limits.infinite || search.pondering.load(Ordering::Relaxed)
+        });
+        if waits_for_stop {
+            return self.stop_search(out);
+        }
+
+        self.join_search();
+        self.flush_output(out)
+    }
+
+    /// Runs the UCI command loop on `lines` until `quit` or until `lines` is closed.
+    ///
+    /// While a search is running, the output of the worker is flushed to `out` every
+    /// [`OUTPUT_POLL_INTERVAL`] even when no command arrives.
+    pub fn run(&mut self, lines: Receiver<io::Result<String>>, out: &mut dyn Write) -> Result<()> {
+        loop {
+            let line = if self.is_searching() {
+                match lines.recv_timeout(OUTPUT_POLL_INTERVAL) {
+                    Ok(line) => Some(line),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            } else {
+                match lines.recv() {
+                    Ok(line) => Some(line),
+                    Err(_) => break,
+                }
+            };
+
+            if let Some(line) = line {
+                let quit = match self.handle_line(&line?, out) {
+                    Ok(quit) => quit,
+                    Err(err) => {
+                        // Don't crash on malformed UCI input; lichess-bot/GUIs will keep going.
+                        eprintln!("UCI error: {err:#}");
+                        false
+                    }
+                };
+                if quit {
+                    return Ok(());
+                }
+            }
+
+            self.flush_output(out)?;
+        }
+
+        // The input was closed: let a bounded search finish so its `bestmove` is not lost.
+        self.wait_for_search(out)
+    }
+
     /// Handle one UCI input line.
     ///
     /// Returns `Ok(true)` when the caller should quit.
     pub fn handle_line(&mut self, line: &str, out: &mut dyn Write) -> Result<bool> {
+        self.flush_output(out)?;
+
         let line = line.trim();
         if line.is_empty() {
             return Ok(false);
@@ -58,12 +229,31 @@ impl<T: ChessEngine> UciWrapper<T> {
                 // Minimal identification is required by most GUIs/bots.
                 writeln!(out, "id name Odyn")?;
                 writeln!(out, "id author MichelDucartier")?;
+                let wrapper_options = [
+                    EngineOption::check(PONDER_OPTION, false),
+                    EngineOption::check(OWN_BOOK_OPTION, false),
+                    EngineOption::string(BOOK_FILE_OPTION, ""),
+                    EngineOption::spin(
+                        BOOK_VARIETY_OPTION,
+                        MAX_BOOK_VARIETY as i64,
+                        0,
+                        MAX_BOOK_VARIETY as i64,
+                    ),
+                ];
+                for option in wrapper_options {
+                    writeln!(out, "{}", format_option(&option))?;
+                }
+                for option in &self.engine_options {
+                    writeln!(out, "{}", format_option(option))?;
+                }
                 writeln!(out, "{}", UCI_OK)?;
             }
             constants::IS_READY_COMMAND => {
                 writeln!(out, "{}", constants::READY_OK)?;
             }
             constants::UCINEWGAME_COMMAND => {
+                self.stop_search(out)?;
+                self.lock_engine().new_game();
                 self.position = PositionState::default();
                 // No output required by the UCI spec.
             }
@@ -74,41 +264,73 @@ impl<T: ChessEngine> UciWrapper<T> {
             constants::GO_COMMAND => {
                 let rest: Vec<&str> = it.collect();
                 if let Some(depth) = parse_go_perft_depth(&rest)? {
+                    self.stop_search(out)?;
                     self.run_perft(depth, out)?;
                     out.flush()?;
                     return Ok(false);
                 }
 
-                // For now: compute a best move synchronously.
-                // Lichess-bot is fine with a single `bestmove` line.
-                self.engine
-                    .position(&self.position.fen, self.position.moves.clone());
-
-                let requested = self.engine.current_best_move();
-
-                if let Some((mv, _val)) = requested {
-                    writeln!(out, "bestmove {}", mv)?;
-                } else {
-                    writeln!(out, "bestmove 0000")?;
+                let limits = parse_go_limits(&rest)?;
+                self.stop_search(out)?;
+                match self.book_move(&limits) {
+                    Some(move_) => {
+                        writeln!(out, "info string book move")?;
+                        writeln!(out, "bestmove {}", move_)?;
+                    }
+                    None => self.start_search(limits),
                 }
             }
             constants::STOP_COMMAND => {
-                // No-op until we have async search.
+                self.stop_search(out)?;
             }
             constants::SET_OPTION_COMMAND => {
-                // Accept and ignore for now.
-                // UCI option wiring can be added once the engine supports it.
+                let rest: Vec<&str> = it.collect();
+                let (name, value) = parse_setoption(&rest)?;
+                // Options must not change under a running search.
+                self.stop_search(out)?;
+                if name.eq_ignore_ascii_case(PONDER_OPTION) {
+                    self.ponder = options::parse_check(&name, &value)?;
+                } else if name.eq_ignore_ascii_case(OWN_BOOK_OPTION) {
+                    self.own_book = options::parse_check(&name, &value)?;
+                } else if name.eq_ignore_ascii_case(BOOK_FILE_OPTION) {
+                    let path = value.trim();
+                    // GUIs send `<empty>` to reset a string option.
+                    self.book = if path.is_empty() || path == "<empty>" {
+                        None
+                    } else {
+                        Some(Book::from_file(path)?)
+                    };
+                } else if name.eq_ignore_ascii_case(BOOK_VARIETY_OPTION) {
+                    let max = MAX_BOOK_VARIETY as i64;
+                    self.book_variety = options::parse_spin(&name, &value, 0, max)? as u8;
+                } else {
+                    let options = {
+                        let mut engine = self.lock_engine();
+                        engine.set_option(&name, &value)?;
+                        engine.options()
+                    };
+                    self.engine_options = options;
+                }
+            }
+            constants::EVAL_COMMAND => {
+                self.stop_search(out)?;
+                let (board, _) = self.position.board_with_history();
+                match self.lock_engine().eval_trace(&board) {
+                    Some(trace) => write!(out, "{}", format_eval(&board, &trace))?,
+                    None => writeln!(out, "info string eval is not supported by this engine")?,
+                }
             }
             constants::DEBUG_COMMAND => {
                 // Accept and ignore.
             }
             constants::PONDERHIT_COMMAND => {
-                // Accept and ignore.
+                self.ponderhit();
             }
             constants::REGISTER_COMMAND => {
                 // Accept and ignore.
             }
             constants::QUIT_COMMAND => {
+                self.stop_search(out)?;
                 return Ok(true);
             }
             _ => {
@@ -120,6 +342,110 @@ impl<T: ChessEngine> UciWrapper<T> {
         Ok(false)
     }
 
+    /// Returns the book move to play instead of searching, if any.
+    ///
+    /// Searches that must wait for `stop` are never answered from the book.
+    fn book_move(&self, limits: &SearchLimits) -> Option<Move> {
+        if !self.own_book || limits.is_unbounded() {
+            return None;
+        }
+        let (board, _) = self.position.board_with_history();
+        self.book.as_ref()?.pick(&board, self.book_variety)
+    }
+
+    fn start_search(&mut self, limits: SearchLimits) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let pondering = Arc::new(AtomicBool::new(limits.ponder));
+        let start = Chessboard::from_fen(&self.position.fen, " ");
+        let moves = self.position.moves.clone();
+        let color_id = start.replay(&moves).0.current_turn();
+
+        let thread = {
+            let engine = Arc::clone(&self.engine);
+            let stop = Arc::clone(&stop);
+            let pondering = Arc::clone(&pondering);
+            let limits = limits.clone();
+            let output = self.output_tx.clone();
+            let ponder = self.ponder;
+
+            thread::spawn(move || {
+                let result = {
+                    let mut engine = engine.lock().unwrap_or_else(|err| err.into_inner());
+                    let info = |info: &SearchInfo| {
+                        let _ = output.send(format_info(info));
+                    };
+                    engine.set_position(&start, &moves);
+                    engine.search(limits.clone(), &info, &stop)
+                };
+
+                // UCI forbids answering an infinite or ponder search before the GUI asks.
+                while (limits.infinite || pondering.load(Ordering::Relaxed))
+                    && !stop.load(Ordering::Relaxed)
+                {
+                    thread::park();
+                }
+
+                // The receiver only disappears with the wrapper itself.
+                let _ = output.send(format_bestmove(&result, ponder));
+            })
+        };
+
+        let mut search = SearchHandle {
+            stop,
+            pondering,
+            limits,
+            color_id,
+            timer: None,
+            thread,
+        };
+        if !search.limits.ponder {
+            if let Some(budget) = search.limits.time_budget(color_id) {
+                search.arm_timer(budget);
+            }
+        }
+        self.search = Some(search);
+    }
+
+    /// Stops the running search (if any) and writes its `bestmove` to `out`.
+    fn stop_search(&mut self, out: &mut dyn Write) -> Result<()> {
+        if let Some(search) = &self.search {
+            search.pondering.store(false, Ordering::Relaxed);
+            search.stop.store(true, Ordering::Relaxed);
+            search.thread.thread().unpark();
+        }
+        self.join_search();
+        self.flush_output(out)
+    }
+
+    /// Switches a pondering search to a normal timed search.
+    fn ponderhit(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        if !search.pondering.swap(false, Ordering::Relaxed) {
+            return;
+        }
+
+        if let Some(budget) = search.limits.time_budget(search.color_id) {
+            search.arm_timer(budget);
+        }
+        search.thread.thread().unpark();
+    }
+
+    fn lock_engine(&self) -> MutexGuard<'_, T> {
+        // A panicking search poisons the lock, but the engine itself is still usable.
+        self.engine.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn join_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            if search.thread.join().is_err() {
+                // The engine panicked: still answer so that the GUI does not hang.
+                let _ = self.output_tx.send("bestmove 0000".to_string());
+            }
+        }
+    }
+
     fn handle_position(&mut self, tokens: &[&str]) -> Result<()> {
         if tokens.is_empty() {
             return Err(anyhow!("position: missing arguments"));
@@ -168,7 +494,7 @@ impl<T: ChessEngine> UciWrapper<T> {
     }
 
     fn run_perft(&self, depth: u8, out: &mut dyn Write) -> Result<()> {
-        let board = Chessboard::from_moves(&self.position.fen, self.position.moves.clone());
+        let (board, _) = self.position.board_with_history();
         let splits = perft::perft_divide(&board, depth);
         let total_nodes: u64 = if depth == 0 {
             1
@@ -205,6 +531,217 @@ fn parse_go_perft_depth(tokens: &[&str]) -> Result<Option<u8>> {
     Ok(Some(depth))
 }
 
+/// Formats the final answer of a search as a UCI `bestmove` line.
+///
+/// The expected reply is appended as `ponder <move>` when `ponder` is set and the search
+/// found one.
+pub fn format_bestmove(result: &SearchResult, ponder: bool) -> String {
+    match (result.best_move, result.ponder_move) {
+        (Some(best_move), Some(ponder_move)) if ponder => {
+            format!("bestmove {} ponder {}", best_move, ponder_move)
+        }
+        (Some(best_move), _) => format!("bestmove {}", best_move),
+        (None, _) => "bestmove 0000".to_string(),
+    }
+}
+
+/// Formats the answer to the `eval` command: the board followed by one row per evaluation
+/// term with its middlegame and endgame values for White, Black and in total, and the
+/// phase-blended score.
+///
+/// `trace` is expected from White's point of view, as returned by
+/// [`ChessEngine::eval_trace`].
+pub fn format_eval(board: &Chessboard, trace: &EvalTrace) -> String {
+    let cells = |midgame: &dyn ToString, endgame: &dyn ToString| {
+        format!(" {:>6} {:>6} ", midgame.to_string(), endgame.to_string())
+    };
+    let scores = |score: PhaseScore| cells(&score.midgame, &score.endgame);
+    let row = |name: &str, white: String, black: String, total: String| {
+        format!("{:>15} |{}|{}|{}", name, white, black, total)
+    };
+    let separator = format!("{:-<16}+{:-<15}+{:-<15}+{:-<15}", "", "", "", "");
+
+    let mut lines = vec![
+        format_chessboard(board),
+        String::new(),
+        format!(
+            "{:>15} |{:^15}|{:^15}|{:^15}",
+            "Term", "White", "Black", "Total"
+        ),
+        row(
+            "",
+            cells(&"MG", &"EG"),
+            cells(&"MG", &"EG"),
+            cells(&"MG", &"EG"),
+        ),
+        separator.clone(),
+    ];
+
+    let (mut white, mut black) = (PhaseScore::default(), PhaseScore::default());
+    for term in &trace.terms {
+        white = white + term.white;
+        black = black + term.black;
+        lines.push(row(
+            &term.name,
+            scores(term.white),
+            scores(term.black),
+            scores(term.total()),
+        ));
+    }
+    lines.push(separator);
+    lines.push(row(
+        "Total",
+        scores(white),
+        scores(black),
+        scores(white - black),
+    ));
+    lines.push(String::new());
+    lines.push(format!("Phase: {}/{}", trace.phase, MAX_PHASE));
+    lines.push(format!("Final evaluation: {:+} (White side)", trace.score));
+
+    lines
+        .iter()
+        .map(|line| format!("{}\n", line.trim_end()))
+        .collect()
+}
+
+/// Formats an engine option as a UCI `option` line.
+pub fn format_option(option: &EngineOption) -> String {
+    let details = match &option.kind {
+        OptionKind::Check { default } => format!("type check default {}", default),
+        OptionKind::Spin { default, min, max } => {
+            format!("type spin default {} min {} max {}", default, min, max)
+        }
+        OptionKind::Combo { default, vars } => {
+            let vars: Vec<String> = vars.iter().map(|var| format!("var {}", var)).collect();
+            format!("type combo default {} {}", default, vars.join(" "))
+        }
+        OptionKind::Button => "type button".to_string(),
+        OptionKind::String { default } => {
+            let default = if default.is_empty() {
+                "<empty>"
+            } else {
+                default
+            };
+            format!("type string default {}", default)
+        }
+    };
+
+    format!("option name {} {}", option.name, details)
+}
+
+/// Splits the arguments of `setoption name <id> [value <x>]` into the name and value.
+///
+/// Both may contain spaces, as in `setoption name Skill Level value 10`.
+fn parse_setoption(tokens: &[&str]) -> Result<(String, String)> {
+    if tokens.first().copied() != Some("name") {
+        return Err(anyhow!("setoption: expected 'name'"));
+    }
+
+    let value_index = tokens.iter().position(|token| *token == "value");
+    let name_end = value_index.unwrap_or(tokens.len());
+    let name = tokens[1..name_end].join(" ");
+    if name.is_empty() {
+        return Err(anyhow!("setoption: missing option name"));
+    }
+
+    let value = match value_index {
+        Some(index) => tokens[index + 1..].join(" "),
+        None => String::new(),
+    };
+
+    Ok((name, value))
+}
+
+/// Formats a search report as a UCI `info` line.
+pub fn format_info(info: &SearchInfo) -> String {
+    let mut line = String::from("info");
+
+    if let Some(depth) = info.depth {
+        line.push_str(&format!(" depth {}", depth));
+    }
+    if let Some(seldepth) = info.seldepth {
+        line.push_str(&format!(" seldepth {}", seldepth));
+    }
+    if let Some(multipv) = info.multipv {
+        line.push_str(&format!(" multipv {}", multipv));
+    }
+    match info.score {
+        Some(Score::Centipawns(cp)) => line.push_str(&format!(" score cp {}", cp)),
+        Some(Score::Mate(moves)) => line.push_str(&format!(" score mate {}", moves)),
+        None => {}
+    }
+    if let Some(nodes) = info.nodes {
+        line.push_str(&format!(" nodes {}", nodes));
+    }
+    if let Some(nps) = info.nps {
+        line.push_str(&format!(" nps {}", nps));
+    }
+    if let Some(time) = info.time {
+        line.push_str(&format!(" time {}", time.as_millis()));
+    }
+    if let Some(hashfull) = info.hashfull {
+        line.push_str(&format!(" hashfull {}", hashfull));
+    }
+    if let Some(currmove) = info.currmove {
+        line.push_str(&format!(" currmove {}", currmove));
+    }
+    if let Some(currmovenumber) = info.currmovenumber {
+        line.push_str(&format!(" currmovenumber {}", currmovenumber));
+    }
+    // `pv` and `string` must come last: GUIs read every remaining token as part of them.
+    if !info.pv.is_empty() {
+        line.push_str(" pv");
+        for move_ in &info.pv {
+            line.push_str(&format!(" {}", move_));
+        }
+    } else if let Some(string) = &info.string {
+        line.push_str(&format!(" string {}", string));
+    }
+
+    line
+}
+
+/// Parses the arguments of a `go` command into [`SearchLimits`].
+fn parse_go_limits(tokens: &[&str]) -> Result<SearchLimits> {
+    let mut limits = SearchLimits::default();
+    let mut it = tokens.iter().copied();
+
+    while let Some(token) = it.next() {
+        match token {
+            "infinite" => limits.infinite = true,
+            "ponder" => limits.ponder = true,
+            "wtime" => limits.wtime = Some(parse_go_millis(token, it.next())?),
+            "btime" => limits.btime = Some(parse_go_millis(token, it.next())?),
+            "winc" => limits.winc = Some(parse_go_millis(token, it.next())?),
+            "binc" => limits.binc = Some(parse_go_millis(token, it.next())?),
+            "movetime" => limits.movetime = Some(parse_go_millis(token, it.next())?),
+            "movestogo" => limits.movestogo = Some(parse_go_value(token, it.next())?),
+            "depth" => limits.depth = Some(parse_go_value(token, it.next())?),
+            "nodes" => limits.nodes = Some(parse_go_value(token, it.next())?),
+            "mate" => limits.mate = Some(parse_go_value(token, it.next())?),
+            _ => {
+                // Unknown/unsupported parameter (e.g. `searchmoves`): ignore.
+            }
+        }
+    }
+
+    Ok(limits)
+}
+
+fn parse_go_value<V: std::str::FromStr>(name: &str, value: Option<&str>) -> Result<V> {
+    let value = value.ok_or_else(|| anyhow!("go {}: missing value", name))?;
+    value
+        .parse::<V>()
+        .map_err(|_| anyhow!("go {}: invalid value '{}'", name, value))
+}
+
+fn parse_go_millis(name: &str, value: Option<&str>) -> Result<Duration> {
+    // Some GUIs send negative clock times when a player is about to flag.
+    let millis: i64 = parse_go_value(name, value)?;
+    Ok(Duration::from_millis(millis.max(0) as u64))
+}
+
 /// Parses a UCI move string (for example `e2e4` or `e7e8q`).
 pub fn parse_uci_move(s: &str) -> Result<Move> {
     // UCI move format:
@@ -264,15 +801,556 @@ pub fn parse_uci_move(s: &str) -> Result<Move> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::engine::implementations::book::{encode_move, polyglot_key, BookEntry};
+    use crate::engine::implementations::{odyn_engine::OdynEngine, odyn_evaluator::OdynEvaluator};
+    use crate::engine::search::InfoSink;
+    use std::fs;
 
     struct NoopEngine;
 
     impl ChessEngine for NoopEngine {
-        fn position(&mut self, _fen: &str, _moves: Vec<Move>) {}
+        fn set_position(&mut self, _start: &Chessboard, _moves: &[Move]) {}
+
+        fn search(
+            &mut self,
+            _limits: SearchLimits,
+            _info: &dyn InfoSink,
+            _stop: &AtomicBool,
+        ) -> SearchResult {
+            SearchResult::default()
+        }
+    }
+
+    /// Engine that keeps thinking until it is told to stop, then plays `e2e4` expecting
+    /// `e7e5`.
+    struct StoppableEngine;
+
+    impl ChessEngine for StoppableEngine {
+        fn set_position(&mut self, _start: &Chessboard, _moves: &[Move]) {}
+
+        fn search(
+            &mut self,
+            _limits: SearchLimits,
+            _info: &dyn InfoSink,
+            stop: &AtomicBool,
+        ) -> SearchResult {
+            while !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            SearchResult {
+                best_move: Some(parse_uci_move("e2e4").unwrap()),
+                ponder_move: Some(parse_uci_move("e7e5").unwrap()),
+                ..Default::default()
+            }
+        }
+    }
+
+    /// Engine recording the options and positions it receives.
+    #[derive(Default)]
+    struct RecordingEngine {
+        options: Vec<(String, String)>,
+        moves: Vec<Move>,
+        new_games: usize,
+    }
+
+    impl ChessEngine for Arc<Mutex<RecordingEngine>> {
+        fn new_game(&mut self) {
+            self.lock().unwrap().new_games += 1;
+        }
+
+        fn set_position(&mut self, _start: &Chessboard, moves: &[Move]) {
+            self.lock().unwrap().moves = moves.to_vec();
+        }
+
+        fn search(
+            &mut self,
+            _limits: SearchLimits,
+            _info: &dyn InfoSink,
+            _stop: &AtomicBool,
+        ) -> SearchResult {
+            SearchResult::default()
+        }
+
+        fn set_option(&mut self, name: &str, value: &str) -> Result<()> {
+            self.lock()
+                .unwrap()
+                .options
+                .push((name.to_string(), value.to_string()));
+            Ok(())
+        }
+
+        fn options(&self) -> Vec<EngineOption> {
+            vec![
+                EngineOption::spin("Hash", 16, 1, 4096),
+                EngineOption::combo("Style", "Normal", &["Solid", "Normal"]),
+                EngineOption::string("EvalFile", ""),
+                EngineOption::button("Clear Hash"),
+            ]
+        }
+    }
 
-        fn current_best_move(&self) -> Option<(Move, f32)> {
-            None
+    fn run_script<T: ChessEngine + Send + 'static>(
+        wrapper: &mut UciWrapper<T>,
+        lines: &[&str],
+    ) -> String {
+        let mut out = Vec::new();
+        for line in lines {
+            wrapper
+                .handle_line(line, &mut out)
+                .unwrap_or_else(|err| panic!("'{}' should succeed: {:#}", line, err));
+        }
+        String::from_utf8(out).expect("output must be utf8")
+    }
+
+    #[test]
+    fn test_parse_go_limits() {
+        let limits =
+            parse_go_limits(&["wtime", "1000", "btime", "-5", "winc", "10", "depth", "3"]).unwrap();
+        assert_eq!(limits.wtime, Some(Duration::from_millis(1000)));
+        assert_eq!(limits.btime, Some(Duration::ZERO));
+        assert_eq!(limits.winc, Some(Duration::from_millis(10)));
+        assert_eq!(limits.depth, Some(3));
+        assert!(!limits.infinite);
+
+        let limits = parse_go_limits(&["ponder", "infinite"]).unwrap();
+        assert!(limits.ponder && limits.infinite);
+
+        assert!(parse_go_limits(&["depth"]).is_err());
+        assert!(parse_go_limits(&["nodes", "many"]).is_err());
+    }
+
+    #[test]
+    fn test_go_depth_answers_from_worker_thread() {
+        let engine = OdynEngine::new(OdynEvaluator);
+        let mut wrapper = UciWrapper::new(engine);
+
+        let mut out = Vec::new();
+        wrapper
+            .handle_line("position startpos moves e2e4", &mut out)
+            .unwrap();
+        wrapper.handle_line("go depth 2", &mut out).unwrap();
+        wrapper.wait_for_search(&mut out).unwrap();
+
+        let output = String::from_utf8(out).expect("output must be utf8");
+        let bestmove = output
+            .lines()
+            .find(|line| line.starts_with("bestmove"))
+            .expect("a bestmove line");
+        assert_ne!(bestmove, "bestmove 0000");
+        assert!(!wrapper.is_searching());
+    }
+
+    #[test]
+    fn test_go_depth_emits_info_per_iteration() {
+        let engine = OdynEngine::new(OdynEvaluator);
+        let mut wrapper = UciWrapper::new(engine);
+
+        let mut out = Vec::new();
+        wrapper.handle_line("position startpos", &mut out).unwrap();
+        wrapper.handle_line("go depth 2", &mut out).unwrap();
+        wrapper.wait_for_search(&mut out).unwrap();
+
+        let output = String::from_utf8(out).expect("output must be utf8");
+        let infos: Vec<&str> = output
+            .lines()
+            .filter(|line| line.starts_with("info depth"))
+            .collect();
+        assert!(infos.iter().any(|line| line.starts_with("info depth 1 ")));
+        assert!(infos.iter().any(|line| line.starts_with("info depth 2 ")));
+        for token in ["seldepth", "score cp", "nodes", "time", "hashfull", " pv "] {
+            assert!(
+                infos.iter().all(|line| line.contains(token)),
+                "missing {}",
+                token
+            );
+        }
+
+        // The last reported principal variation starts with the move that is played.
+        let last_pv_move = infos.last().unwrap().split(" pv ").nth(1).unwrap();
+        let bestmove = output
+            .lines()
+            .last()
+            .unwrap()
+            .trim_start_matches("bestmove ");
+        assert!(last_pv_move.starts_with(bestmove));
+    }
+
+    #[test]
+    fn test_format_info() {
+        let e2e4 = parse_uci_move("e2e4").unwrap();
+        let e7e5 = parse_uci_move("e7e5").unwrap();
+
+        let info = SearchInfo {
+            depth: Some(3),
+            seldepth: Some(5),
+            multipv: Some(2),
+            score: Some(Score::Mate(-2)),
+            nodes: Some(1200),
+            nps: Some(60000),
+            time: Some(Duration::from_millis(20)),
+            hashfull: Some(7),
+            pv: vec![e2e4, e7e5],
+            ..Default::default()
+        };
+        assert_eq!(
+            format_info(&info),
+            "info depth 3 seldepth 5 multipv 2 score mate -2 nodes 1200 nps 60000 time 20 hashfull 7 pv e2e4 e7e5"
+        );
+
+        let info = SearchInfo {
+            depth: Some(8),
+            currmove: Some(e2e4),
+            currmovenumber: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_info(&info),
+            "info depth 8 currmove e2e4 currmovenumber 4"
+        );
+
+        let info = SearchInfo {
+            string: Some("mate in 3 refuted".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(format_info(&info), "info string mate in 3 refuted");
+    }
+
+    #[test]
+    fn test_isready_is_answered_while_searching() {
+        let mut wrapper = UciWrapper::new(StoppableEngine);
+        let output = run_script(
+            &mut wrapper,
+            &["position startpos", "go infinite", "isready"],
+        );
+
+        assert!(output.contains("readyok"));
+        assert!(!output.contains("bestmove"));
+        assert!(wrapper.is_searching());
+
+        run_script(&mut wrapper, &["stop"]);
+    }
+
+    #[test]
+    fn test_stop_returns_best_move_immediately() {
+        let mut wrapper = UciWrapper::new(StoppableEngine);
+        let output = run_script(&mut wrapper, &["position startpos", "go infinite", "stop"]);
+
+        assert!(output.contains("bestmove e2e4"));
+        assert!(!wrapper.is_searching());
+    }
+
+    #[test]
+    fn test_go_infinite_waits_for_stop() {
+        // The search itself finishes immediately, but UCI forbids answering before `stop`.
+        let mut wrapper = UciWrapper::new(NoopEngine);
+        let output = run_script(&mut wrapper, &["position startpos", "go infinite"]);
+        thread::sleep(Duration::from_millis(20));
+
+        let mut out = Vec::new();
+        wrapper.flush_output(&mut out).unwrap();
+        assert!(out.is_empty());
+        assert!(!output.contains("bestmove"));
+
+        let output = run_script(&mut wrapper, &["stop"]);
+        assert!(output.contains("bestmove 0000"));
+    }
+
+    #[test]
+    fn test_ponderhit_releases_bestmove() {
+        let mut wrapper = UciWrapper::new(NoopEngine);
+        let output = run_script(&mut wrapper, &["position startpos", "go ponder"]);
+        thread::sleep(Duration::from_millis(20));
+        assert!(!output.contains("bestmove"));
+        assert!(wrapper.is_searching());
+
+        let mut out = Vec::new();
+        wrapper.handle_line("ponderhit", &mut out).unwrap();
+        wrapper.wait_for_search(&mut out).unwrap();
+
+        let output = String::from_utf8(out).expect("output must be utf8");
+        assert!(output.contains("bestmove 0000"));
+    }
+
+    #[test]
+    fn test_ponderhit_starts_the_clock() {
+        let mut wrapper = UciWrapper::new(StoppableEngine);
+        run_script(
+            &mut wrapper,
+            &["position startpos", "go ponder movetime 20"],
+        );
+
+        // The time limit does not apply while pondering.
+        thread::sleep(Duration::from_millis(60));
+        assert!(wrapper.is_searching());
+
+        let mut out = Vec::new();
+        wrapper.handle_line("ponderhit", &mut out).unwrap();
+        while wrapper.is_searching() {
+            thread::sleep(Duration::from_millis(1));
+            wrapper.flush_output(&mut out).unwrap();
+        }
+
+        let output = String::from_utf8(out).expect("output must be utf8");
+        assert!(output.contains("bestmove e2e4"));
+    }
+
+    #[test]
+    fn test_ponder_miss_discards_the_pondering_search() {
+        let mut wrapper = UciWrapper::new(StoppableEngine);
+        let output = run_script(
+            &mut wrapper,
+            &[
+                "setoption name Ponder value true",
+                "position startpos moves e2e4 e7e5",
+                "go ponder wtime 1000 btime 1000",
+                // The opponent played another move: the GUI stops the search and ignores
+                // its answer before sending the real position.
+                "stop",
+                "position startpos moves e2e4 c7c5",
+                "go movetime 20",
+            ],
+        );
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            ["bestmove e2e4 ponder e7e5"]
+        );
+
+        let mut out = Vec::new();
+        wrapper.wait_for_search(&mut out).unwrap();
+        let output = String::from_utf8(out).expect("output must be utf8");
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            ["bestmove e2e4 ponder e7e5"]
+        );
+    }
+
+    #[test]
+    fn test_format_bestmove_names_ponder_move_when_enabled() {
+        let result = SearchResult {
+            best_move: Some(parse_uci_move("g1f3").unwrap()),
+            ponder_move: Some(parse_uci_move("g8f6").unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(format_bestmove(&result, true), "bestmove g1f3 ponder g8f6");
+        assert_eq!(format_bestmove(&result, false), "bestmove g1f3");
+        assert_eq!(
+            format_bestmove(&SearchResult::default(), true),
+            "bestmove 0000"
+        );
+    }
+
+    #[test]
+    fn test_movetime_stops_search() {
+        let mut wrapper = UciWrapper::new(StoppableEngine);
+        let mut out = Vec::new();
+        wrapper.handle_line("go movetime 50", &mut out).unwrap();
+        wrapper.wait_for_search(&mut out).unwrap();
+
+        let output = String::from_utf8(out).expect("output must be utf8");
+        assert!(output.contains("bestmove e2e4"));
+    }
+
+    #[test]
+    fn test_uci_is_answered_during_search() {
+        let mut wrapper = UciWrapper::new(StoppableEngine);
+        let mut out = Vec::new();
+        wrapper.handle_line("go infinite", &mut out).unwrap();
+        thread::sleep(Duration::from_millis(20));
+
+        wrapper.handle_line("uci", &mut out).unwrap();
+        assert!(wrapper.is_searching());
+        let output = String::from_utf8(out).expect("output must be utf8");
+        assert!(output.ends_with("uciok\n"));
+
+        let output = run_script(&mut wrapper, &["stop"]);
+        assert!(output.contains("bestmove e2e4"));
+    }
+
+    /// Output slow enough to flush that a quick search ends between two polls of the loop.
+    struct SlowOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SlowOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
         }
+
+        fn flush(&mut self) -> io::Result<()> {
+            thread::sleep(Duration::from_millis(50));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run_flushes_a_search_finishing_between_polls() {
+        let (lines, rx) = mpsc::channel();
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut out = SlowOutput(Arc::clone(&output));
+        let runner = thread::spawn(move || UciWrapper::new(StoppableEngine).run(rx, &mut out));
+
+        lines.send(Ok("position startpos".to_string())).unwrap();
+        // The search stops about 75 ms in, while the loop is flushing its output.
+        lines.send(Ok("go movetime 105".to_string())).unwrap();
+
+        // The input stays open, so `bestmove` must be written by the loop on its own.
+        let has_bestmove = || String::from_utf8_lossy(&output.lock().unwrap()).contains("bestmove");
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        while !has_bestmove() && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(has_bestmove());
+
+        drop(lines);
+        runner.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_quit_terminates_running_search() {
+        let mut wrapper = UciWrapper::new(StoppableEngine);
+        let mut out = Vec::new();
+        wrapper.handle_line("go infinite", &mut out).unwrap();
+
+        let quit = wrapper.handle_line("quit", &mut out).unwrap();
+        assert!(quit);
+        assert!(!wrapper.is_searching());
+
+        let output = String::from_utf8(out).expect("output must be utf8");
+        assert!(output.contains("bestmove e2e4"));
+    }
+
+    #[test]
+    fn test_uci_lists_engine_options() {
+        let mut wrapper = UciWrapper::new(Arc::new(Mutex::new(RecordingEngine::default())));
+        let output = run_script(&mut wrapper, &["uci"]);
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "id name Odyn",
+                "id author MichelDucartier",
+                "option name Ponder type check default false",
+                "option name OwnBook type check default false",
+                "option name BookFile type string default <empty>",
+                "option name BookVariety type spin default 100 min 0 max 100",
+                "option name Hash type spin default 16 min 1 max 4096",
+                "option name Style type combo default Normal var Solid var Normal",
+                "option name EvalFile type string default <empty>",
+                "option name Clear Hash type button",
+                "uciok",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_setoption_is_forwarded_to_engine() {
+        let engine = Arc::new(Mutex::new(RecordingEngine::default()));
+        let mut wrapper = UciWrapper::new(Arc::clone(&engine));
+        run_script(
+            &mut wrapper,
+            &[
+                "setoption name Hash value 64",
+                "setoption name Clear Hash",
+                "setoption name EvalFile value /tmp/my net.bin",
+            ],
+        );
+
+        assert_eq!(
+            engine.lock().unwrap().options,
+            [
+                ("Hash".to_string(), "64".to_string()),
+                ("Clear Hash".to_string(), String::new()),
+                ("EvalFile".to_string(), "/tmp/my net.bin".to_string()),
+            ]
+        );
+
+        let mut out = Vec::new();
+        assert!(wrapper.handle_line("setoption value 3", &mut out).is_err());
+    }
+
+    #[test]
+    fn test_go_is_answered_from_the_book() {
+        let board = Chessboard::from_fen(START_FEN, " ");
+        let d2d4 = parse_uci_move("d2d4").unwrap();
+        let book = Book::new(vec![BookEntry {
+            key: polyglot_key(&board),
+            raw_move: encode_move(&board, d2d4),
+            weight: 1,
+            learn: 0,
+        }]);
+        let path = std::env::temp_dir().join(format!("odyn-uci-book-{}.bin", std::process::id()));
+        book.save(&path).unwrap();
+
+        let mut wrapper = UciWrapper::new(StoppableEngine);
+        let go = |wrapper: &mut UciWrapper<StoppableEngine>, lines: &[&str]| {
+            let mut output = run_script(wrapper, lines).into_bytes();
+            wrapper.wait_for_search(&mut output).unwrap();
+            String::from_utf8(output).expect("output must be utf8")
+        };
+        let book_file = format!("setoption name BookFile value {}", path.display());
+        let output = go(&mut wrapper, &[&book_file, "go movetime 10"]);
+        assert!(
+            output.contains("bestmove e2e4"),
+            "book is disabled: {}",
+            output
+        );
+
+        let output = go(
+            &mut wrapper,
+            &["setoption name OwnBook value true", "go movetime 10"],
+        );
+        assert_eq!(output, "info string book move\nbestmove d2d4\n");
+
+        // Out of the book, and for searches waiting for `stop`, the engine answers.
+        let output = go(
+            &mut wrapper,
+            &["position startpos moves d2d4", "go movetime 10"],
+        );
+        assert!(output.contains("bestmove e2e4"), "{}", output);
+        let output = go(&mut wrapper, &["position startpos", "go infinite", "stop"]);
+        assert!(output.contains("bestmove e2e4"), "{}", output);
+
+        run_script(&mut wrapper, &["setoption name BookFile value <empty>"]);
+        fs::remove_file(&path).unwrap();
+        let mut out = Vec::new();
+        assert!(wrapper
+            .handle_line("setoption name BookVariety value 101", &mut out)
+            .is_err());
+    }
+
+    #[test]
+    fn test_position_history_reaches_engine() {
+        let engine = Arc::new(Mutex::new(RecordingEngine::default()));
+        let mut wrapper = UciWrapper::new(Arc::clone(&engine));
+        let mut out = Vec::new();
+        wrapper
+            .handle_line("position startpos moves e2e4 e7e5 g1f3", &mut out)
+            .unwrap();
+        wrapper.handle_line("go depth 1", &mut out).unwrap();
+        wrapper.wait_for_search(&mut out).unwrap();
+        wrapper.handle_line("ucinewgame", &mut out).unwrap();
+
+        let engine = engine.lock().unwrap();
+        let moves: Vec<String> = engine.moves.iter().map(|m| m.uci_move()).collect();
+        assert_eq!(moves, ["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(engine.new_games, 1);
+    }
+
+    #[test]
+    fn test_board_with_history_tracks_repetitions() {
+        let position = PositionState {
+            fen: START_FEN.to_string(),
+            moves: ["g1f3", "g8f6", "f3g1", "f6g8"]
+                .iter()
+                .map(|move_| parse_uci_move(move_).unwrap())
+                .collect(),
+        };
+        let (board, history) = position.board_with_history();
+
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0], board.zobrist_key());
+        assert_ne!(history[1], board.zobrist_key());
     }
 
     #[test]
@@ -316,4 +1394,50 @@ mod tests {
         assert!(output.contains("g1f3: 1"));
         assert!(output.contains("Nodes searched: 20"));
     }
+
+    #[test]
+    fn test_eval_prints_board_and_term_table() {
+        use crate::engine::implementations::selectable_evaluator::{
+            EvaluatorKind, SelectableEvaluator,
+        };
+
+        let evaluator = SelectableEvaluator::new(EvaluatorKind::Tapered);
+        let mut wrapper = UciWrapper::new(OdynEngine::new(evaluator));
+        let mut out = Vec::new();
+        wrapper
+            .handle_line("position startpos moves e2e4", &mut out)
+            .unwrap();
+        wrapper.handle_line("eval", &mut out).unwrap();
+
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("a   b   c   d   e   f   g   h"));
+        for name in ["material", "pawn", "mobility", "storm", "Total"] {
+            assert!(output.contains(name), "missing {} in\n{}", name, output);
+        }
+        assert!(output.contains("Phase: 24/24"));
+        assert!(output.contains("Final evaluation: +"));
+
+        // The material is balanced: its total is zero in both phases.
+        let material = output
+            .lines()
+            .find(|line| line.trim_start().starts_with("material"))
+            .unwrap();
+        let totals: Vec<&str> = material
+            .split('|')
+            .nth(3)
+            .unwrap()
+            .split_whitespace()
+            .collect();
+        assert_eq!(totals, ["0", "0"]);
+    }
+
+    #[test]
+    fn test_eval_without_trace_is_reported() {
+        let mut wrapper = UciWrapper::new(NoopEngine);
+        let mut out = Vec::new();
+        wrapper.handle_line("eval", &mut out).unwrap();
+
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("info string eval is not supported"));
+    }
 }
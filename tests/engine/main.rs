@@ -0,0 +1,2 @@
+mod test_tournament;
+mod test_uci_client;
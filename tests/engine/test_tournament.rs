@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use odyn::engine::sprt::MatchScore;
+use odyn::engine::tournament::{Adjudication, EngineConfig, Match, Opening, TimeControl};
+use odyn::game::pgn::read_games;
+use odyn::game::player::{GameResult, Termination};
+
+fn odyn() -> EngineConfig {
+    EngineConfig::new(env!("CARGO_BIN_EXE_odyn"))
+}
+
+fn time_control() -> TimeControl {
+    TimeControl {
+        base: Duration::from_secs(5),
+        increment: Duration::from_millis(50),
+    }
+}
+
+#[test]
+fn test_match_swaps_colors_and_writes_pgn() {
+    let queen_ending = Opening {
+        fen: "7k/8/5K2/8/8/8/8/6Q1 w - - 0 1".to_string(),
+        moves: Vec::new(),
+    };
+    let tournament = Match::new(
+        odyn().with_name("First"),
+        odyn().with_name("Second").with_option("Hash", "8"),
+        time_control(),
+        2,
+    )
+    .with_openings(vec![queen_ending])
+    .with_adjudication(Adjudication {
+        max_moves: Some(40),
+        ..Default::default()
+    });
+
+    let mut games = Vec::new();
+    let score = tournament
+        .run(|game, _| {
+            games.push(game.clone());
+            true
+        })
+        .unwrap();
+
+    assert_eq!(score.games(), 2);
+    assert_eq!(
+        (games[0].white.as_str(), games[0].black.as_str()),
+        ("First", "Second")
+    );
+    assert_eq!(
+        (games[1].white.as_str(), games[1].black.as_str()),
+        ("Second", "First")
+    );
+    for game in &games {
+        assert_eq!(game.record.result, GameResult::WhiteWins, "{}", game.record);
+        assert_eq!(game.record.termination, Termination::Checkmate);
+
+        let pgn = read_games(&game.to_pgn()).next().unwrap().unwrap();
+        assert_eq!(pgn.tag("White"), Some(game.white.as_str()));
+        assert_eq!(pgn.start_fen, game.record.start_fen);
+        assert_eq!(pgn.moves, game.record.moves);
+        assert_eq!(pgn.result, Some(1.0));
+    }
+    assert_eq!(
+        score,
+        MatchScore {
+            wins: 1,
+            draws: 0,
+            losses: 1
+        }
+    );
+}
+
+#[test]
+fn test_match_adjudicates_and_stops_early() {
+    let tournament =
+        Match::new(odyn(), odyn(), time_control(), 10).with_adjudication(Adjudication {
+            max_moves: Some(3),
+            ..Default::default()
+        });
+
+    let mut played = 0;
+    let score = tournament
+        .run(|game, score| {
+            played += 1;
+            assert_eq!(
+                game.record.termination,
+                Termination::Adjudication("move limit".to_string())
+            );
+            assert_eq!(game.record.moves.len(), 6);
+            score.games() < 2
+        })
+        .unwrap();
+    assert_eq!(played, 2);
+    assert_eq!(score.draws, 2);
+}
+
+#[test]
+fn test_match_fails_without_engine() {
+    let tournament = Match::new(
+        EngineConfig::new("/nonexistent/engine"),
+        odyn(),
+        time_control(),
+        2,
+    );
+    assert!(tournament.run(|_, _| true).is_err());
+}
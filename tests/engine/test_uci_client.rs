@@ -0,0 +1,167 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use odyn::constants::START_FEN;
+use odyn::engine::engine::{ChessEngine, EnginePlayer};
+use odyn::engine::options::OptionKind;
+use odyn::engine::search::{Score, SearchInfo, SearchLimits};
+use odyn::game::chessboard::Chessboard;
+use odyn::game::player::{play_game, RandomPlayer, Termination};
+use odyn::uci::client::UciClient;
+use odyn::uci::protocol::parse_uci_move;
+
+fn odyn() -> UciClient {
+    UciClient::spawn(Path::new(env!("CARGO_BIN_EXE_odyn")), &[]).unwrap()
+}
+
+fn depth(depth: u8) -> SearchLimits {
+    SearchLimits {
+        depth: Some(depth),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_handshake_reads_id_and_options() {
+    let client = odyn();
+    assert_eq!(client.name(), "Odyn");
+    assert_eq!(client.author(), "MichelDucartier");
+
+    let options = client.options();
+    let hash = options.iter().find(|option| option.name == "Hash").unwrap();
+    assert!(matches!(hash.kind, OptionKind::Spin { min: 1, .. }));
+    assert!(options.iter().any(|option| option.name == "Skill Level"));
+}
+
+#[test]
+fn test_set_option_is_checked_against_advertised_options() {
+    let mut client = odyn();
+    client.set_option("hash", "8").unwrap();
+    client.set_option("Evaluator", "Tapered").unwrap();
+    assert!(client.set_option("Hash", "0").is_err());
+    assert!(client.set_option("Evaluator", "Random").is_err());
+    assert!(client.set_option("No Such Option", "1").is_err());
+    client.sync().unwrap();
+}
+
+#[test]
+fn test_search_reports_info_and_best_move() {
+    let mut client = odyn();
+    let board = Chessboard::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", " ");
+    client.set_position(&board, &[]);
+
+    let reports: Mutex<Vec<SearchInfo>> = Mutex::new(Vec::new());
+    let sink = |info: &SearchInfo| reports.lock().unwrap().push(info.clone());
+    let result = client.search(depth(3), &sink, &AtomicBool::new(false));
+
+    let mate = parse_uci_move("a1a8").unwrap();
+    assert_eq!(result.best_move, Some(mate));
+    assert_eq!(result.pv.first(), Some(&mate));
+    assert_eq!(result.score, Some(Score::Mate(1)));
+    assert!(reports
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|info| info.depth.is_some() && info.pv.first() == Some(&mate)));
+}
+
+#[test]
+fn test_engine_sees_repetitions_in_the_moves() {
+    let mut client = odyn();
+    client.set_option("Contempt", "0").unwrap();
+    // White is a queen down: repeating the position a third time is the best outcome.
+    let start = Chessboard::from_fen("4k3/8/8/8/8/8/q7/4K1N1 w - - 0 1", " ");
+    let moves: Vec<_> = [
+        "g1f3", "e8d8", "f3g1", "d8e8", "g1f3", "e8d8", "f3g1", "d8e8",
+    ]
+    .iter()
+    .map(|move_| parse_uci_move(move_).unwrap())
+    .collect();
+    client.set_position(&start, &moves);
+
+    let result = client.search(depth(3), &|_: &_| {}, &AtomicBool::new(false));
+    assert_eq!(result.best_move, Some(parse_uci_move("g1f3").unwrap()));
+    assert_eq!(result.score, Some(Score::Centipawns(0)));
+}
+
+#[test]
+fn test_analyze_returns_several_lines() {
+    let mut client = odyn();
+    client.set_option("MultiPV", "2").unwrap();
+    let start = Chessboard::from_fen(START_FEN, " ");
+    client.set_position(&start, &[parse_uci_move("e2e4").unwrap()]);
+    let lines = client.analyze(depth(3), 3, &|_: &_| {}, &AtomicBool::new(false));
+    assert_eq!(lines.len(), 3);
+    let board = Chessboard::from_fen(
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+        " ",
+    );
+    let legal = board.legal_moves_vec(board.current_turn());
+    assert!(lines
+        .iter()
+        .all(|(move_, _, pv)| legal.contains(move_) && pv[0] == *move_));
+
+    // MultiPV is back to its previous value afterwards.
+    let result = client.search(depth(2), &|_: &_| {}, &AtomicBool::new(false));
+    assert_eq!(result.lines.len(), 2);
+}
+
+#[test]
+fn test_stop_ends_an_infinite_search() {
+    let mut client = odyn();
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            stop.store(true, Ordering::Relaxed);
+        });
+    }
+    let started = Instant::now();
+    let limits = SearchLimits {
+        infinite: true,
+        ..Default::default()
+    };
+    let result = client.search(limits, &|_: &_| {}, &stop);
+    assert!(result.best_move.is_some());
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn test_late_bestmove_is_not_taken_for_the_next_search() {
+    // Answers every `go` after 1.5 s, stop or not: first e2e4, then d2d4.
+    let script = "n=0; while read command; do case $command in \
+        uci) echo 'id name Slow'; echo uciok;; \
+        isready) echo readyok;; \
+        go*) n=$((n + 1)); sleep 1.5; \
+            if [ $n = 1 ]; then echo 'bestmove e2e4'; else echo 'bestmove d2d4'; fi;; \
+        quit) exit;; \
+        esac; done";
+    let mut client =
+        UciClient::spawn(Path::new("sh"), &["-c".to_string(), script.to_string()]).unwrap();
+    client.set_position(&Chessboard::from_fen(START_FEN, " "), &[]);
+
+    let result = client.search(depth(1), &|_: &_| {}, &AtomicBool::new(true));
+    assert_eq!(result.best_move, None);
+
+    let result = client.search(depth(1), &|_: &_| {}, &AtomicBool::new(false));
+    assert_eq!(result.best_move, Some(parse_uci_move("d2d4").unwrap()));
+}
+
+#[test]
+fn test_client_plays_through_engine_player() {
+    let mut engine = EnginePlayer::new(odyn(), depth(1));
+    let mut random = RandomPlayer::with_seed(3);
+    let record = play_game(&mut engine, &mut random, START_FEN);
+    assert!(
+        !matches!(
+            record.termination,
+            Termination::IllegalMove(_) | Termination::Forfeit(_)
+        ),
+        "{}",
+        record
+    );
+}
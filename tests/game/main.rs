@@ -2,4 +2,5 @@ mod test_chessboard;
 mod test_mailbox;
 mod test_move_generator;
 mod test_perft;
+mod test_player;
 mod test_utility;
@@ -60,6 +60,26 @@ fn test_to_from_identity_en_passant_black_fen() {
     assert_eq!(FEN3, cboard.to_fen(" "));
 }
 
+#[test]
+fn test_to_fen_tracks_move_counters() {
+    let mut cboard = chessboard::Chessboard::from_fen(START_FEN, " ");
+    for (from, to) in [("e2", "e4"), ("e7", "e5"), ("g1", "f3"), ("b8", "c6")] {
+        cboard.make_move_unchecked(mv(from, to));
+    }
+
+    const FEN_AFTER_MOVES: &str =
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    assert_eq!(FEN_AFTER_MOVES, cboard.to_fen(" "));
+
+    let mut cboard = chessboard::Chessboard::from_fen(&cboard.to_fen(" "), " ");
+    assert_eq!(cboard.halfmove_clock(), 2);
+    cboard.make_move_unchecked(mv("f1", "c4"));
+    assert_eq!(
+        "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3",
+        cboard.to_fen(" ")
+    );
+}
+
 #[test]
 fn test_correct_after_pawn_move() {
     let mut cboard = chessboard::Chessboard::from_fen(START_FEN, " ");
@@ -80,7 +100,7 @@ fn test_correct_after_bishop_move() {
     let move_ = chess_move::Move::new_no_promotion(5, 26);
     cboard.make_move_unchecked(move_);
 
-    const FEN_AFTER_MOVE: &str = "rnbqk1nr/pppp1ppp/8/2b1p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 0 1";
+    const FEN_AFTER_MOVE: &str = "rnbqk1nr/pppp1ppp/8/2b1p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 1 1";
 
     assert_eq!(FEN_AFTER_MOVE, cboard.to_fen(" "))
 }
@@ -94,7 +114,7 @@ fn test_correct_for_en_passant() {
     cboard.make_move_unchecked(move_);
 
     const FEN_AFTER_MOVE: &str =
-        "r1bqk2r/pppp1p1p/3b1n2/4p1pP/2BnP3/5N2/PPPP1PP1/RNBQK2R w KQkq g6 0 1";
+        "r1bqk2r/pppp1p1p/3b1n2/4p1pP/2BnP3/5N2/PPPP1PP1/RNBQK2R w KQkq g6 0 2";
 
     assert_eq!(FEN_AFTER_MOVE, cboard.to_fen(" "));
 
@@ -102,7 +122,7 @@ fn test_correct_for_en_passant() {
     cboard.make_move_unchecked(en_passant_move);
 
     const FEN_AFTER_EN_PASSANT: &str =
-        "r1bqk2r/pppp1p1p/3b1nP1/4p3/2BnP3/5N2/PPPP1PP1/RNBQK2R b KQkq - 0 1";
+        "r1bqk2r/pppp1p1p/3b1nP1/4p3/2BnP3/5N2/PPPP1PP1/RNBQK2R b KQkq - 0 2";
     assert_eq!(FEN_AFTER_EN_PASSANT, cboard.to_fen(" "));
 }
 
@@ -115,7 +135,7 @@ fn test_correct_for_en_passant_without_taking() {
     cboard.make_move_unchecked(move_);
 
     const FEN_AFTER_MOVE: &str =
-        "r1bqk2r/pppp1p1p/3b1n2/4p1pP/2BnP3/5N2/PPPP1PP1/RNBQK2R w KQkq g6 0 1";
+        "r1bqk2r/pppp1p1p/3b1n2/4p1pP/2BnP3/5N2/PPPP1PP1/RNBQK2R w KQkq g6 0 2";
 
     assert_eq!(FEN_AFTER_MOVE, cboard.to_fen(" "));
 
@@ -123,7 +143,7 @@ fn test_correct_for_en_passant_without_taking() {
     cboard.make_move_unchecked(not_en_passant);
 
     const FEN_AFTER_EN_PASSANT: &str =
-        "r1bqk2r/pppp1p1p/3b1n2/4p1pP/2BnP3/2N2N2/PPPP1PP1/R1BQK2R b KQkq - 0 1";
+        "r1bqk2r/pppp1p1p/3b1n2/4p1pP/2BnP3/2N2N2/PPPP1PP1/R1BQK2R b KQkq - 1 2";
     assert_eq!(FEN_AFTER_EN_PASSANT, cboard.to_fen(" "));
 }
 
@@ -137,7 +157,7 @@ fn test_correct_for_white_short_castle() {
     cboard.make_move_unchecked(move_);
 
     const FEN_AFTER_MOVE: &str =
-        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 0 1";
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 1 1";
 
     assert_eq!(FEN_AFTER_MOVE, cboard.to_fen(" "));
 }
@@ -152,7 +172,7 @@ fn test_correct_for_white_long_castle() {
     cboard.make_move_unchecked(move_);
 
     const FEN_AFTER_MOVE: &str =
-        "r1b1k2r/ppppqppp/2n2n2/2b1p3/4P3/2NP1Q2/PPPB1PPP/2KR1BNR b kq - 0 1";
+        "r1b1k2r/ppppqppp/2n2n2/2b1p3/4P3/2NP1Q2/PPPB1PPP/2KR1BNR b kq - 1 1";
 
     assert_eq!(FEN_AFTER_MOVE, cboard.to_fen(" "));
 }
@@ -167,7 +187,7 @@ fn test_correct_for_black_short_castle() {
     cboard.make_move_unchecked(move_);
 
     const FEN_AFTER_MOVE: &str =
-        "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2P2N2/PP1P1PPP/RNBQ1RK1 w - - 0 1";
+        "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2P2N2/PP1P1PPP/RNBQ1RK1 w - - 1 2";
 
     assert_eq!(FEN_AFTER_MOVE, cboard.to_fen(" "));
 }
@@ -182,7 +202,7 @@ fn test_correct_for_black_long_castle() {
     cboard.make_move_unchecked(move_);
 
     const FEN_AFTER_MOVE: &str =
-        "2kr3r/ppp1qppp/2npbn2/2b1p3/4P3/2NP1Q1N/PPPBBPPP/R3K2R w KQ - 0 1";
+        "2kr3r/ppp1qppp/2npbn2/2b1p3/4P3/2NP1Q1N/PPPBBPPP/R3K2R w KQ - 1 2";
 
     assert_eq!(FEN_AFTER_MOVE, cboard.to_fen(" "));
 }
@@ -365,7 +385,7 @@ fn test_promotion_black_capture() {
     let move_ = chess_move::Move::new(50, 57, constants::KNIGHT_ID);
     cboard.make_move_unchecked(move_);
 
-    const FEN_AFTER_MOVE: &str = "8/2k5/4n1P1/1n6/4K3/6N1/8/1n6 w - - 0 1";
+    const FEN_AFTER_MOVE: &str = "8/2k5/4n1P1/1n6/4K3/6N1/8/1n6 w - - 0 2";
     assert_eq!(FEN_AFTER_MOVE, cboard.to_fen(" "));
 }
 
@@ -452,3 +472,78 @@ fn test_en_passant_disallowed_for_horizontally_pinned_pawn() {
     // exposing the king on h5 to the rook on a5.
     assert!(!legal_moves.contains(&mv("g5", "f6")));
 }
+
+#[test]
+fn test_zobrist_key_matches_for_transposed_move_orders() {
+    let first = chessboard::Chessboard::from_moves(
+        START_FEN,
+        vec![mv("g1", "f3"), mv("g8", "f6"), mv("b1", "c3")],
+    );
+    let second = chessboard::Chessboard::from_moves(
+        START_FEN,
+        vec![mv("b1", "c3"), mv("g8", "f6"), mv("g1", "f3")],
+    );
+
+    assert_eq!(first.zobrist_key(), second.zobrist_key());
+}
+
+#[test]
+fn test_zobrist_key_depends_on_side_to_move_and_castle_rights() {
+    let white = chessboard::Chessboard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", " ");
+    let black = chessboard::Chessboard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", " ");
+    let no_castle = chessboard::Chessboard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1", " ");
+
+    assert_ne!(white.zobrist_key(), black.zobrist_key());
+    assert_ne!(white.zobrist_key(), no_castle.zobrist_key());
+}
+
+#[test]
+fn test_null_move_passes_turn_and_clears_en_passant() {
+    let mut board = chessboard::Chessboard::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", " ");
+    board.make_null_move();
+
+    let expected = chessboard::Chessboard::from_fen("4k3/8/8/3pP3/8/8/8/4K3 b - - 0 1", " ");
+    assert_eq!(board.current_turn(), constants::BLACK_ID);
+    assert_eq!(board.zobrist_key(), expected.zobrist_key());
+}
+
+#[test]
+fn test_has_non_pawn_material() {
+    let board = chessboard::Chessboard::from_fen("4k3/pppp4/8/8/8/8/4PN2/4K3 w - - 0 1", " ");
+
+    assert!(board.has_non_pawn_material(constants::WHITE_ID));
+    assert!(!board.has_non_pawn_material(constants::BLACK_ID));
+}
+
+#[test]
+fn test_halfmove_clock_resets_on_pawn_moves_and_captures() {
+    let mut board = chessboard::Chessboard::from_fen("4k3/8/8/3p4/8/2N5/4P3/4K3 w - - 7 30", " ");
+    assert_eq!(board.halfmove_clock(), 7);
+
+    board.make_move_unchecked(mv("e1", "d1"));
+    assert_eq!(board.halfmove_clock(), 8);
+
+    board.make_move_unchecked(mv("e8", "d8"));
+    board.make_move_unchecked(mv("c3", "d5"));
+    assert_eq!(board.halfmove_clock(), 0);
+
+    board.make_move_unchecked(mv("d8", "c8"));
+    board.make_move_unchecked(mv("e2", "e4"));
+    assert_eq!(board.halfmove_clock(), 0);
+}
+
+#[test]
+fn test_pawn_key_only_depends_on_pawns() {
+    let board = chessboard::Chessboard::from_fen("4k3/pp6/8/8/8/8/4PN2/4K3 w - - 0 1", " ");
+    let other_pieces =
+        chessboard::Chessboard::from_fen("r5k1/pp6/8/8/8/8/4P3/2B1K3 b - - 3 9", " ");
+    let other_pawns = chessboard::Chessboard::from_fen("4k3/p1p5/8/8/8/8/4PN2/4K3 w - - 0 1", " ");
+
+    assert_eq!(board.pawn_key(), other_pieces.pawn_key());
+    assert_ne!(board.pawn_key(), other_pawns.pawn_key());
+    assert_eq!(
+        board.piece_board(constants::PAWN_ID, constants::WHITE_ID),
+        1 << 52
+    );
+    assert_eq!(board.occupancy().count_ones(), 6);
+}
@@ -0,0 +1,150 @@
+use std::io::Cursor;
+
+use anyhow::Result;
+use odyn::constants::START_FEN;
+use odyn::engine::{
+    engine::EnginePlayer, implementations::odyn_engine::OdynEngine,
+    implementations::odyn_evaluator::OdynEvaluator, search::SearchLimits,
+};
+use odyn::game::chess_move::Move;
+use odyn::game::chessboard::Chessboard;
+use odyn::game::player::{
+    game_end, is_insufficient_material, play_game, GameResult, HumanPlayer, Player, RandomPlayer,
+    Termination,
+};
+use odyn::uci::protocol::parse_uci_move;
+
+/// Player replaying fixed moves, legal or not.
+struct ScriptedPlayer(Vec<&'static str>);
+
+impl Player for ScriptedPlayer {
+    fn next_move(&mut self, _start: &Chessboard, _moves: &[Move]) -> Result<Move> {
+        parse_uci_move(self.0.remove(0))
+    }
+}
+
+fn human(input: &str) -> HumanPlayer<Cursor<String>, Vec<u8>> {
+    HumanPlayer::new(Cursor::new(input.to_string()), Vec::new())
+}
+
+#[test]
+fn test_play_game_detects_checkmate() {
+    let mut white = ScriptedPlayer(vec!["f2f3", "g2g4"]);
+    let mut black = ScriptedPlayer(vec!["e7e5", "d8h4"]);
+    let record = play_game(&mut white, &mut black, START_FEN);
+
+    assert_eq!(record.result, GameResult::BlackWins);
+    assert_eq!(record.termination, Termination::Checkmate);
+    assert_eq!(record.moves.len(), 4);
+    assert_eq!(record.to_string(), "0-1 (checkmate)");
+}
+
+#[test]
+fn test_play_game_forfeits_illegal_moves() {
+    let mut white = ScriptedPlayer(vec!["e2e5"]);
+    let mut black = RandomPlayer::with_seed(1);
+    let record = play_game(&mut white, &mut black, START_FEN);
+
+    assert_eq!(record.result, GameResult::BlackWins);
+    assert_eq!(
+        record.termination,
+        Termination::IllegalMove(parse_uci_move("e2e5").unwrap())
+    );
+    assert!(record.moves.is_empty());
+}
+
+#[test]
+fn test_random_games_end_legally_and_replay_by_seed() {
+    let play = |seed| {
+        let mut white = RandomPlayer::with_seed(seed);
+        let mut black = RandomPlayer::with_seed(seed + 1);
+        play_game(&mut white, &mut black, START_FEN)
+    };
+
+    for seed in 0..5 {
+        let record = play(seed);
+        assert_eq!(record, play(seed));
+
+        let mut board = Chessboard::from_fen(START_FEN, " ");
+        let mut history = Vec::new();
+        for move_ in &record.moves {
+            assert!(game_end(&board, &history).is_none());
+            assert!(board.legal_moves(board.current_turn()).contains(move_));
+            history.push(board.zobrist_key());
+            board.make_move_unchecked(*move_);
+        }
+        assert_eq!(
+            game_end(&board, &history),
+            Some((record.result, record.termination.clone()))
+        );
+    }
+}
+
+#[test]
+fn test_game_end_draws() {
+    let board = |fen| Chessboard::from_fen(fen, " ");
+    assert_eq!(
+        game_end(&board("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), &[]),
+        Some((GameResult::Draw, Termination::Stalemate))
+    );
+    assert_eq!(
+        game_end(&board("4k3/8/8/8/8/8/4P3/4K3 w - - 100 80"), &[]),
+        Some((GameResult::Draw, Termination::FiftyMoveRule))
+    );
+
+    assert!(is_insufficient_material(&board(
+        "4k3/8/8/8/8/8/8/4KN2 w - - 0 1"
+    )));
+    assert!(is_insufficient_material(&board(
+        "2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1"
+    )));
+    assert!(!is_insufficient_material(&board(
+        "1b2k3/8/8/8/8/8/8/4KB2 w - - 0 1"
+    )));
+    assert!(!is_insufficient_material(&board(
+        "4k3/8/8/8/8/8/8/3NKN2 w - - 0 1"
+    )));
+    assert!(!is_insufficient_material(&board(
+        "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"
+    )));
+
+    let mut white = ScriptedPlayer(vec!["g1f3", "f3g1", "g1f3", "f3g1"]);
+    let mut black = ScriptedPlayer(vec!["g8f6", "f6g8", "g8f6", "f6g8"]);
+    let record = play_game(&mut white, &mut black, START_FEN);
+    assert_eq!(record.termination, Termination::ThreefoldRepetition);
+    assert_eq!(record.moves.len(), 8);
+}
+
+#[test]
+fn test_human_player_reads_uci_and_san_moves() {
+    let board = Chessboard::from_fen(START_FEN, " ");
+    let mut player = human("e2e5\nhello\n\nNf3\n");
+    assert_eq!(
+        player.next_move(&board, &[]).unwrap(),
+        parse_uci_move("g1f3").unwrap()
+    );
+    assert!(player.next_move(&board, &[]).is_err());
+
+    let mut white = human("e2e4\n");
+    let mut black = RandomPlayer::with_seed(3);
+    let record = play_game(&mut white, &mut black, START_FEN);
+    assert_eq!(record.moves.len(), 2);
+    assert_eq!(
+        record.termination,
+        Termination::Forfeit("end of input".to_string())
+    );
+}
+
+#[test]
+fn test_engine_player_mates_random_player() {
+    let limits = SearchLimits {
+        depth: Some(2),
+        ..Default::default()
+    };
+    let mut engine = EnginePlayer::new(OdynEngine::new(OdynEvaluator), limits);
+    let mut random = RandomPlayer::with_seed(7);
+    let record = play_game(&mut engine, &mut random, "6k1/8/6K1/8/8/8/8/Q7 w - - 0 1");
+
+    assert_eq!(record.result, GameResult::WhiteWins);
+    assert_eq!(record.termination, Termination::Checkmate);
+}
@@ -2,7 +2,7 @@ use std::collections::HashSet;
 
 use odyn::assert_eq_bitboard;
 use odyn::{
-    assert_eq_u8,
+    assert_eq_u8, constants,
     game::utility::{self, board_to_rook_ranks},
 };
 
@@ -112,6 +112,15 @@ fn test_north_one_no_overflow() {
     assert_eq_bitboard!(0, res);
 }
 
+#[test]
+fn test_north_and_south_fill() {
+    // c6 (index 18)
+    let bitboard = 1 << 18;
+    assert_eq_bitboard!(0x0000_0000_0004_0404, utility::north_fill(bitboard));
+    assert_eq_bitboard!(0x0404_0404_0404_0000, utility::south_fill(bitboard));
+    assert_eq_bitboard!(constants::C_FILE_MASK, utility::file_fill(bitboard));
+}
+
 #[test]
 fn test_flip_diag_a8h1() {
     let board: u64 = 0b11110000;
@@ -160,3 +169,10 @@ fn test_board_to_rook_ranks() {
     assert_eq_u8!(row_expected, row);
     assert_eq_u8!(col_expected, col);
 }
+
+#[test]
+fn test_string_to_square_rejects_squares_off_the_board() {
+    assert_eq!(None, utility::string_to_square("z9"));
+    assert_eq!(None, utility::string_to_square("a0"));
+    assert_eq!(None, utility::string_to_square("i1"));
+}